@@ -1,8 +1,11 @@
 use crate::class::{ClassDefinition, ClassFunction, ClassInstance};
 use crate::error::{Context, Error, ErrorKind};
+use crate::file::FileHandle;
 use crate::function::{BuiltinFunction, Function};
+use crate::json;
+use crate::map::{self, MapKey};
 use crate::session::{ExecSession, ParseSession};
-use crate::variable::{AnnotatedIdentifier, Value, Variable};
+use crate::variable::{AnnotatedIdentifier, FileValue, Value, Variable};
 
 use rand::Rng;
 use std::collections::HashMap;
@@ -20,6 +23,12 @@ pub const TYPEID_RESULT: usize = 7;
 pub const TYPEID_FILE: usize = 8;
 pub const TYPEID_FILESYSTEM: usize = 9;
 pub const TYPEID_MATH: usize = 10;
+pub const TYPEID_MAP: usize = 11;
+pub const TYPEID_FUNCTION: usize = 12;
+pub const TYPEID_ENV: usize = 13;
+pub const TYPEID_PROCESS: usize = 14;
+pub const TYPEID_FILE_HANDLE: usize = 15;
+pub const TYPEID_JSON: usize = 16;
 
 // Required function signature:
 // fn(&mut ExecSession, &ParseSession, Context, usize) -> Result<Value, Error>
@@ -37,14 +46,11 @@ pub fn load_builtin_functions(function_store: &mut HashMap<String, Function>) {
         Function::BuiltinFunction(BuiltinFunction::new(arguments, print)),
     );
 
-    #[cfg(not(test))]
-    {
-        let arguments = vec![AnnotatedIdentifier::new("a".to_string(), TYPEID_STRING)];
-        function_store.insert(
-            String::from("input"),
-            Function::BuiltinFunction(BuiltinFunction::new(arguments, input)),
-        );
-    }
+    let arguments = vec![AnnotatedIdentifier::new("a".to_string(), TYPEID_STRING)];
+    function_store.insert(
+        String::from("input"),
+        Function::BuiltinFunction(BuiltinFunction::new(arguments, input)),
+    );
 
     let arguments = vec![
         AnnotatedIdentifier::new("a".to_string(), TYPEID_INT),
@@ -69,6 +75,17 @@ pub fn load_builtin_functions(function_store: &mut HashMap<String, Function>) {
         String::from("panic"),
         Function::BuiltinFunction(BuiltinFunction::new(arguments, panic)),
     );
+
+    function_store.insert(
+        String::from("args"),
+        Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), args)),
+    );
+
+    let arguments = vec![AnnotatedIdentifier::new("a".to_string(), TYPEID_INT)];
+    function_store.insert(
+        String::from("exit"),
+        Function::BuiltinFunction(BuiltinFunction::new(arguments, exit)),
+    );
 }
 
 pub fn load_builtin_class_definitions(class_definitions: &mut Vec<ClassDefinition>) {
@@ -81,72 +98,74 @@ pub fn load_builtin_class_definitions(class_definitions: &mut Vec<ClassDefinitio
     class_definitions.push(make_file_class());
     class_definitions.push(make_filesystem_class());
     class_definitions.push(make_math_class());
+    class_definitions.push(make_map_class());
+    class_definitions.push(make_function_class());
+    class_definitions.push(make_env_class());
+    class_definitions.push(make_process_class());
+    class_definitions.push(make_file_handle_class());
+    class_definitions.push(make_json_class());
 }
 
 fn printline(
     exec_session: &mut ExecSession,
     parse_session: &ParseSession,
-    _: Context,
-    _: usize,
+    context: Context,
+    pos: usize,
 ) -> Result<Value, Error> {
-    #[cfg(test)]
-    {
-        let operand = exec_session.get_variable("a").unwrap();
-        let text = operand.get_value().to_string(parse_session) + "\n";
-        exec_session.output_stream.push_str(&text);
-    }
-    #[cfg(not(test))]
-    {
-        let operand = exec_session.get_variable("a").unwrap();
-        println!("{}", operand.get_value().to_string(parse_session));
+    let operand = exec_session.get_variable("a").unwrap();
+    let text = operand.get_value().to_string(parse_session) + "\n";
+    match exec_session.output().write_str(&text) {
+        Ok(_) => Ok(Value::None),
+        Err(e) => Err(Error::new(
+            context,
+            pos,
+            ErrorKind::CustomError(e.to_string()),
+        )),
     }
-    Ok(Value::None)
 }
 
 fn print(
     exec_session: &mut ExecSession,
     parse_session: &ParseSession,
-    _: Context,
-    _: usize,
+    context: Context,
+    pos: usize,
 ) -> Result<Value, Error> {
-    #[cfg(test)]
-    {
-        let operand = exec_session.get_variable("a").unwrap();
-        let text = operand.get_value().to_string(parse_session);
-        exec_session.output_stream.push_str(&text);
-    }
-    #[cfg(not(test))]
-    {
-        let operand = exec_session.get_variable("a").unwrap();
-        print!("{}", operand.get_value().to_string(parse_session));
-        std::io::stdout().flush().expect("Failed to flush stdout");
+    let operand = exec_session.get_variable("a").unwrap();
+    let text = operand.get_value().to_string(parse_session);
+    match exec_session.output().write_str(&text) {
+        Ok(_) => Ok(Value::None),
+        Err(e) => Err(Error::new(
+            context,
+            pos,
+            ErrorKind::CustomError(e.to_string()),
+        )),
     }
-    Ok(Value::None)
 }
 
-#[cfg(not(test))]
 fn input(
     exec_session: &mut ExecSession,
     _: &ParseSession,
-    _: Context,
-    _: usize,
+    context: Context,
+    pos: usize,
 ) -> Result<Value, Error> {
     let operand = exec_session.get_variable("a").unwrap();
     let prompt = match operand.get_value() {
-        Value::Str(prompt) => prompt,
+        Value::Str(prompt) => prompt.borrow().clone(),
         _ => panic!("Invalid value in built-in function"),
     };
-    print!("{}", prompt.borrow());
-    std::io::stdout().flush().expect("Failed to flush stdout");
 
-    let mut input = String::new();
-    std::io::stdin()
-        .read_line(&mut input)
-        .expect("Failed to read from stdin");
-    if input.ends_with('\n') {
-        input.pop();
+    let result = exec_session
+        .output()
+        .write_str(&prompt)
+        .and_then(|_| exec_session.input().read_line());
+    match result {
+        Ok(input) => Ok(Value::new_string(input)),
+        Err(e) => Err(Error::new(
+            context,
+            pos,
+            ErrorKind::CustomError(e.to_string()),
+        )),
     }
-    Ok(Value::new_string(input))
 }
 
 fn rand(
@@ -211,6 +230,45 @@ fn panic(
     ))
 }
 
+fn args(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    Ok(Value::new_vec_instance_from(
+        exec_session
+            .args()
+            .iter()
+            .map(|arg| Value::new_string(arg.clone()))
+            .collect(),
+    ))
+}
+
+// Stops the script, the error is not reported
+fn exit(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    context: Context,
+    pos: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("a").unwrap();
+    let code = match operand.get_value() {
+        Value::Int(code) => *code,
+        _ => panic!("Invalid value in built-in function"),
+    };
+    // the operating system only keeps the lowest byte of the status
+    if (0..=255).contains(&code) {
+        Err(Error::new(context, pos, ErrorKind::Exit(code as i32)))
+    } else {
+        Err(Error::new(
+            context,
+            pos,
+            ErrorKind::CustomError(format!("Exit code '{}' is out of range", code)),
+        ))
+    }
+}
+
 fn self_to_string(
     exec_session: &mut ExecSession,
     parse_session: &ParseSession,
@@ -940,6 +998,26 @@ fn make_vec_class() -> ClassDefinition {
             true,
         ),
     );
+    let args: Vec<AnnotatedIdentifier> =
+        vec![AnnotatedIdentifier::new(String::from("a"), TYPEID_FUNCTION)];
+    functions.insert(
+        String::from("map"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(args, vec_map)),
+            true,
+            true,
+        ),
+    );
+    let args: Vec<AnnotatedIdentifier> =
+        vec![AnnotatedIdentifier::new(String::from("a"), TYPEID_FUNCTION)];
+    functions.insert(
+        String::from("filter"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(args, vec_filter)),
+            true,
+            true,
+        ),
+    );
 
     ClassDefinition::new_without_constructor(functions, TYPEID_VEC)
 }
@@ -1104,142 +1182,473 @@ fn vec_len(
     Ok(Value::Int(vec.borrow().len() as isize))
 }
 
+fn vec_map(
+    exec_session: &mut ExecSession,
+    parse_session: &ParseSession,
+    context: Context,
+    pos: usize,
+) -> Result<Value, Error> {
+    // the function is called on a snapshot so it is free to modify the Vec
+    let items = match exec_session.get_variable("self").unwrap().get_value() {
+        Value::Vector(vec) => vec.borrow().clone(),
+        _ => panic!("Invalid value in built-in function"),
+    };
+    let function = match exec_session.get_variable("a").unwrap().get_value() {
+        Value::Function(f) => f.clone(),
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    let mut result = Vec::with_capacity(items.len());
+    for item in items {
+        result.push(function.call(vec![item], exec_session, parse_session, context, pos, None)?);
+    }
+
+    Ok(Value::new_vec_instance_from(result))
+}
+
+fn vec_filter(
+    exec_session: &mut ExecSession,
+    parse_session: &ParseSession,
+    context: Context,
+    pos: usize,
+) -> Result<Value, Error> {
+    let items = match exec_session.get_variable("self").unwrap().get_value() {
+        Value::Vector(vec) => vec.borrow().clone(),
+        _ => panic!("Invalid value in built-in function"),
+    };
+    let function = match exec_session.get_variable("a").unwrap().get_value() {
+        Value::Function(f) => f.clone(),
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    let mut result = Vec::new();
+    for item in items {
+        match function.call(
+            vec![item.clone()],
+            exec_session,
+            parse_session,
+            context,
+            pos,
+            None,
+        )? {
+            Value::Bool(true) => result.push(item),
+            Value::Bool(false) => (),
+            value => {
+                return Err(Error::new(
+                    context,
+                    pos,
+                    ErrorKind::InvalidReturnType(
+                        parse_session.get_typename(value.typeid()),
+                        parse_session.get_typename(TYPEID_BOOL),
+                    ),
+                ));
+            }
+        }
+    }
+
+    Ok(Value::new_vec_instance_from(result))
+}
+
+// Functions used as values have no associated functions of their own
 #[inline]
-fn make_result_class() -> ClassDefinition {
+fn make_function_class() -> ClassDefinition {
+    ClassDefinition::new_without_constructor(HashMap::new(), TYPEID_FUNCTION)
+}
+
+#[inline]
+fn make_map_class() -> ClassDefinition {
     let mut functions = HashMap::new();
 
+    functions.insert(
+        String::from("new"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), map_new)),
+            false,
+            true,
+        ),
+    );
     let args: Vec<AnnotatedIdentifier> = vec![
-        AnnotatedIdentifier::new(String::from("a"), TYPEID_BOOL),
+        AnnotatedIdentifier::new(String::from("a"), TYPEID_DYN),
         AnnotatedIdentifier::new(String::from("b"), TYPEID_DYN),
     ];
     functions.insert(
-        String::from("new"),
+        String::from("insert"),
         ClassFunction::new(
-            Function::BuiltinFunction(BuiltinFunction::new(args, result_new)),
-            false,
+            Function::BuiltinFunction(BuiltinFunction::new(args, map_insert)),
+            true,
             true,
         ),
     );
+    let args: Vec<AnnotatedIdentifier> =
+        vec![AnnotatedIdentifier::new(String::from("a"), TYPEID_DYN)];
     functions.insert(
-        String::from("is_ok"),
+        String::from("get"),
         ClassFunction::new(
-            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), result_is_ok)),
+            Function::BuiltinFunction(BuiltinFunction::new(args, map_get)),
             true,
             true,
         ),
     );
+    let args: Vec<AnnotatedIdentifier> =
+        vec![AnnotatedIdentifier::new(String::from("a"), TYPEID_DYN)];
     functions.insert(
-        String::from("value"),
+        String::from("remove"),
         ClassFunction::new(
-            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), result_value)),
+            Function::BuiltinFunction(BuiltinFunction::new(args, map_remove)),
             true,
             true,
         ),
     );
+    let args: Vec<AnnotatedIdentifier> =
+        vec![AnnotatedIdentifier::new(String::from("a"), TYPEID_DYN)];
     functions.insert(
-        String::from("unwrap"),
+        String::from("contains_key"),
         ClassFunction::new(
-            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), result_unwrap)),
+            Function::BuiltinFunction(BuiltinFunction::new(args, map_contains_key)),
+            true,
+            true,
+        ),
+    );
+    functions.insert(
+        String::from("keys"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), map_keys)),
+            true,
+            true,
+        ),
+    );
+    functions.insert(
+        String::from("values"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), map_values)),
+            true,
+            true,
+        ),
+    );
+    functions.insert(
+        String::from("clear"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), map_clear)),
+            true,
+            true,
+        ),
+    );
+    functions.insert(
+        String::from("len"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), map_len)),
+            true,
+            true,
+        ),
+    );
+    functions.insert(
+        String::from("to_string"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), self_to_string)),
             true,
             true,
         ),
     );
 
-    ClassDefinition::new_without_constructor(functions, TYPEID_RESULT)
+    ClassDefinition::new_without_constructor(functions, TYPEID_MAP)
 }
 
-fn make_result(is_ok: bool, value: Value) -> Value {
-    let mut result = ClassInstance::new(TYPEID_RESULT);
-    result.add_property("is_ok", Variable::new(Value::Bool(is_ok), false), false);
-    result.add_property("value", Variable::new(value, true), false);
-    result.add_property(
-        "is_checked",
-        Variable::new(Value::Bool(false), false),
-        false,
-    );
-    Value::new_class_instance(result)
+fn map_new(_: &mut ExecSession, _: &ParseSession, _: Context, _: usize) -> Result<Value, Error> {
+    Ok(Value::new_map_instance())
 }
 
-fn result_new(
+fn map_insert(
     exec_session: &mut ExecSession,
-    _: &ParseSession,
-    _: Context,
-    _: usize,
+    parse_session: &ParseSession,
+    context: Context,
+    pos: usize,
 ) -> Result<Value, Error> {
-    let operand = exec_session.get_variable("a").unwrap();
-    let is_ok: bool = match operand.get_value() {
-        Value::Bool(is_ok) => *is_ok,
+    let operand = exec_session.get_variable("self").unwrap();
+    let map = match operand.get_value() {
+        Value::Map(map) => map,
         _ => panic!("Invalid value in built-in function"),
     };
-    let operand = exec_session.get_variable("b").unwrap();
-    let value: Value = operand.get_value_clone();
 
-    Ok(make_result(is_ok, value))
+    let operand = exec_session.get_variable("a").unwrap();
+    let key = map::get_key(operand.get_value(), parse_session)
+        .map_err(|e| Error::new(context, pos, e))?;
+
+    let operand = exec_session.get_variable("b").unwrap();
+    map.borrow_mut().insert(key, operand.get_value_clone());
+    Ok(Value::None)
 }
 
-fn result_is_ok(
+fn map_get(
     exec_session: &mut ExecSession,
     parse_session: &ParseSession,
-    _: Context,
-    _: usize,
+    context: Context,
+    pos: usize,
 ) -> Result<Value, Error> {
     let operand = exec_session.get_variable("self").unwrap();
-    let result = match operand.get_value() {
-        Value::Class(c) => c,
+    let map = match operand.get_value() {
+        Value::Map(map) => map,
         _ => panic!("Invalid value in built-in function"),
     };
 
-    let is_ok: bool = match result
-        .borrow()
-        .get_property("is_ok", true, parse_session)
-        .unwrap()
-        .get_value()
-    {
-        Value::Bool(b) => *b,
-        _ => unreachable!(),
-    };
-
-    result
-        .borrow_mut()
-        .set_property("is_checked", true, Value::Bool(true), parse_session)
-        .unwrap();
+    let operand = exec_session.get_variable("a").unwrap();
+    let key = map::get_key(operand.get_value(), parse_session)
+        .map_err(|e| Error::new(context, pos, e))?;
 
-    Ok(Value::Bool(is_ok))
+    match map.borrow().get(&key) {
+        Some(value) => Ok(value.clone()),
+        None => Err(Error::new(
+            context,
+            pos,
+            ErrorKind::KeyNotFound(key.to_string()),
+        )),
+    }
 }
 
-fn result_value(
+fn map_remove(
     exec_session: &mut ExecSession,
     parse_session: &ParseSession,
     context: Context,
     pos: usize,
 ) -> Result<Value, Error> {
     let operand = exec_session.get_variable("self").unwrap();
-    let result = match operand.get_value() {
-        Value::Class(c) => c,
+    let map = match operand.get_value() {
+        Value::Map(map) => map,
         _ => panic!("Invalid value in built-in function"),
     };
 
-    let is_checked: bool = match result
-        .borrow()
-        .get_property("is_checked", true, parse_session)
-        .unwrap()
-        .get_value()
-    {
-        Value::Bool(b) => *b,
-        _ => unreachable!(),
-    };
+    let operand = exec_session.get_variable("a").unwrap();
+    let key = map::get_key(operand.get_value(), parse_session)
+        .map_err(|e| Error::new(context, pos, e))?;
 
-    if !is_checked {
-        return Err(Error::new(
+    let removed = map.borrow_mut().remove(&key);
+    match removed {
+        Some(value) => Ok(value),
+        None => Err(Error::new(
             context,
             pos,
-            ErrorKind::CustomError(String::from(
-                "A 'Result' must be checked with 'is_ok()' before accessing its value",
-            )),
-        ));
+            ErrorKind::KeyNotFound(key.to_string()),
+        )),
     }
+}
 
-    let value: Value = result
+fn map_contains_key(
+    exec_session: &mut ExecSession,
+    parse_session: &ParseSession,
+    context: Context,
+    pos: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("self").unwrap();
+    let map = match operand.get_value() {
+        Value::Map(map) => map,
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    let operand = exec_session.get_variable("a").unwrap();
+    let key = map::get_key(operand.get_value(), parse_session)
+        .map_err(|e| Error::new(context, pos, e))?;
+
+    Ok(Value::Bool(map.borrow().contains_key(&key)))
+}
+
+fn map_keys(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("self").unwrap();
+    let map = match operand.get_value() {
+        Value::Map(map) => map,
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    let keys = map.borrow().keys().map(|key| key.to_value()).collect();
+    Ok(Value::new_vec_instance_from(keys))
+}
+
+fn map_values(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("self").unwrap();
+    let map = match operand.get_value() {
+        Value::Map(map) => map,
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    let values = map.borrow().values().cloned().collect();
+    Ok(Value::new_vec_instance_from(values))
+}
+
+fn map_clear(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("self").unwrap();
+    let map = match operand.get_value() {
+        Value::Map(map) => map,
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    map.borrow_mut().clear();
+    Ok(Value::None)
+}
+
+fn map_len(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("self").unwrap();
+    let map = match operand.get_value() {
+        Value::Map(map) => map,
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    Ok(Value::Int(map.borrow().len() as isize))
+}
+
+#[inline]
+fn make_result_class() -> ClassDefinition {
+    let mut functions = HashMap::new();
+
+    let args: Vec<AnnotatedIdentifier> = vec![
+        AnnotatedIdentifier::new(String::from("a"), TYPEID_BOOL),
+        AnnotatedIdentifier::new(String::from("b"), TYPEID_DYN),
+    ];
+    functions.insert(
+        String::from("new"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(args, result_new)),
+            false,
+            true,
+        ),
+    );
+    functions.insert(
+        String::from("is_ok"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), result_is_ok)),
+            true,
+            true,
+        ),
+    );
+    functions.insert(
+        String::from("value"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), result_value)),
+            true,
+            true,
+        ),
+    );
+    functions.insert(
+        String::from("unwrap"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), result_unwrap)),
+            true,
+            true,
+        ),
+    );
+
+    ClassDefinition::new_without_constructor(functions, TYPEID_RESULT)
+}
+
+fn make_result(is_ok: bool, value: Value) -> Value {
+    let mut result = ClassInstance::new(TYPEID_RESULT);
+    result.add_property("is_ok", Variable::new(Value::Bool(is_ok), false), false);
+    result.add_property("value", Variable::new(value, true), false);
+    result.add_property(
+        "is_checked",
+        Variable::new(Value::Bool(false), false),
+        false,
+    );
+    Value::new_class_instance(result)
+}
+
+fn result_new(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("a").unwrap();
+    let is_ok: bool = match operand.get_value() {
+        Value::Bool(is_ok) => *is_ok,
+        _ => panic!("Invalid value in built-in function"),
+    };
+    let operand = exec_session.get_variable("b").unwrap();
+    let value: Value = operand.get_value_clone();
+
+    Ok(make_result(is_ok, value))
+}
+
+fn result_is_ok(
+    exec_session: &mut ExecSession,
+    parse_session: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("self").unwrap();
+    let result = match operand.get_value() {
+        Value::Class(c) => c,
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    let is_ok: bool = match result
+        .borrow()
+        .get_property("is_ok", true, parse_session)
+        .unwrap()
+        .get_value()
+    {
+        Value::Bool(b) => *b,
+        _ => unreachable!(),
+    };
+
+    result
+        .borrow_mut()
+        .set_property("is_checked", true, Value::Bool(true), parse_session)
+        .unwrap();
+
+    Ok(Value::Bool(is_ok))
+}
+
+fn result_value(
+    exec_session: &mut ExecSession,
+    parse_session: &ParseSession,
+    context: Context,
+    pos: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("self").unwrap();
+    let result = match operand.get_value() {
+        Value::Class(c) => c,
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    let is_checked: bool = match result
+        .borrow()
+        .get_property("is_checked", true, parse_session)
+        .unwrap()
+        .get_value()
+    {
+        Value::Bool(b) => *b,
+        _ => unreachable!(),
+    };
+
+    if !is_checked {
+        return Err(Error::new(
+            context,
+            pos,
+            ErrorKind::CustomError(String::from(
+                "A 'Result' must be checked with 'is_ok()' before accessing its value",
+            )),
+        ));
+    }
+
+    let value: Value = result
         .borrow()
         .get_property("value", true, parse_session)
         .unwrap()
@@ -1328,81 +1737,288 @@ fn make_file_class() -> ClassDefinition {
         ),
     );
 
+    let args: Vec<AnnotatedIdentifier> = vec![
+        AnnotatedIdentifier::new(String::from("a"), TYPEID_STRING),
+        AnnotatedIdentifier::new(String::from("b"), TYPEID_STRING),
+    ];
+    functions.insert(
+        String::from("open"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(args, file_open)),
+            false,
+            true,
+        ),
+    );
+
     ClassDefinition::new_without_constructor(functions, TYPEID_FILE)
 }
 
-fn file_read(
+fn file_read(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("a").unwrap();
+    let path = match operand.get_value() {
+        Value::Str(path) => path,
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    match std::fs::read_to_string(path.borrow().as_str()) {
+        Ok(file_contents) => Ok(make_result(true, Value::new_string(file_contents))),
+        Err(e) => Ok(make_result(false, Value::new_string(e.to_string()))),
+    }
+}
+
+fn file_write(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("a").unwrap();
+    let path = match operand.get_value() {
+        Value::Str(path) => path,
+        _ => panic!("Invalid value in built-in function"),
+    };
+    let operand = exec_session.get_variable("b").unwrap();
+    let contents = match operand.get_value() {
+        Value::Str(file_contents) => file_contents,
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    match std::fs::write(path.borrow().as_str(), contents.borrow().as_str()) {
+        Ok(()) => Ok(make_result(true, Value::None)),
+        Err(e) => Ok(make_result(false, Value::new_string(e.to_string()))),
+    }
+}
+
+fn file_append(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("a").unwrap();
+    let path = match operand.get_value() {
+        Value::Str(path) => path,
+        _ => panic!("Invalid value in built-in function"),
+    };
+    let operand = exec_session.get_variable("b").unwrap();
+    let contents = match operand.get_value() {
+        Value::Str(contents) => contents,
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    let mut file = match std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path.borrow().as_str())
+    {
+        Ok(file) => file,
+        Err(e) => {
+            return Ok(make_result(false, Value::new_string(e.to_string())));
+        }
+    };
+
+    match file.write_all(contents.borrow().as_str().as_bytes()) {
+        Ok(()) => Ok(make_result(true, Value::None)),
+        Err(e) => Ok(make_result(false, Value::new_string(e.to_string()))),
+    }
+}
+
+fn file_open(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("a").unwrap();
+    let path = match operand.get_value() {
+        Value::Str(path) => path,
+        _ => panic!("Invalid value in built-in function"),
+    };
+    let operand = exec_session.get_variable("b").unwrap();
+    let mode = match operand.get_value() {
+        Value::Str(mode) => mode,
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    match FileHandle::open(path.borrow().as_str(), mode.borrow().as_str()) {
+        Ok(file) => Ok(make_result(true, Value::new_file(file))),
+        Err(e) => Ok(make_result(false, Value::new_string(e))),
+    }
+}
+
+#[inline]
+fn make_file_handle_class() -> ClassDefinition {
+    let mut functions = HashMap::new();
+
+    functions.insert(
+        String::from("read_line"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), file_handle_read_line)),
+            true,
+            true,
+        ),
+    );
+    functions.insert(
+        String::from("lines"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), file_handle_lines)),
+            true,
+            true,
+        ),
+    );
+    let args: Vec<AnnotatedIdentifier> =
+        vec![AnnotatedIdentifier::new(String::from("a"), TYPEID_STRING)];
+    functions.insert(
+        String::from("write"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(args, file_handle_write)),
+            true,
+            true,
+        ),
+    );
+    functions.insert(
+        String::from("flush"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), file_handle_flush)),
+            true,
+            true,
+        ),
+    );
+    let args: Vec<AnnotatedIdentifier> =
+        vec![AnnotatedIdentifier::new(String::from("a"), TYPEID_INT)];
+    functions.insert(
+        String::from("seek"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(args, file_handle_seek)),
+            true,
+            true,
+        ),
+    );
+    functions.insert(
+        String::from("close"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), file_handle_close)),
+            true,
+            true,
+        ),
+    );
+
+    ClassDefinition::new_without_constructor(functions, TYPEID_FILE_HANDLE)
+}
+
+#[inline]
+fn get_file_handle(exec_session: &ExecSession) -> FileValue {
+    let operand = exec_session.get_variable("self").unwrap();
+    match operand.get_value() {
+        Value::File(file) => file.clone(),
+        _ => panic!("Invalid value in built-in function"),
+    }
+}
+
+fn file_handle_read_line(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let file = get_file_handle(exec_session);
+
+    let result = file.borrow_mut().read_line();
+    match result {
+        Ok(Some(line)) => Ok(make_result(true, Value::new_string(line))),
+        Ok(None) => Ok(make_result(true, Value::None)),
+        Err(e) => Ok(make_result(false, Value::new_string(e))),
+    }
+}
+
+// The handle itself is iterated by for loops, one line at a time
+fn file_handle_lines(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    Ok(Value::File(get_file_handle(exec_session)))
+}
+
+fn file_handle_write(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let file = get_file_handle(exec_session);
+    let operand = exec_session.get_variable("a").unwrap();
+    let text = match operand.get_value() {
+        Value::Str(text) => text,
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    let result = file.borrow_mut().write(text.borrow().as_str());
+    match result {
+        Ok(()) => Ok(make_result(true, Value::None)),
+        Err(e) => Ok(make_result(false, Value::new_string(e))),
+    }
+}
+
+fn file_handle_flush(
     exec_session: &mut ExecSession,
     _: &ParseSession,
     _: Context,
     _: usize,
 ) -> Result<Value, Error> {
-    let operand = exec_session.get_variable("a").unwrap();
-    let path = match operand.get_value() {
-        Value::Str(path) => path,
-        _ => panic!("Invalid value in built-in function"),
-    };
+    let file = get_file_handle(exec_session);
 
-    match std::fs::read_to_string(path.borrow().as_str()) {
-        Ok(file_contents) => Ok(make_result(true, Value::new_string(file_contents))),
-        Err(e) => Ok(make_result(false, Value::new_string(e.to_string()))),
+    let result = file.borrow_mut().flush();
+    match result {
+        Ok(()) => Ok(make_result(true, Value::None)),
+        Err(e) => Ok(make_result(false, Value::new_string(e))),
     }
 }
 
-fn file_write(
+fn file_handle_seek(
     exec_session: &mut ExecSession,
     _: &ParseSession,
     _: Context,
     _: usize,
 ) -> Result<Value, Error> {
+    let file = get_file_handle(exec_session);
     let operand = exec_session.get_variable("a").unwrap();
-    let path = match operand.get_value() {
-        Value::Str(path) => path,
-        _ => panic!("Invalid value in built-in function"),
-    };
-    let operand = exec_session.get_variable("b").unwrap();
-    let contents = match operand.get_value() {
-        Value::Str(file_contents) => file_contents,
+    let position = match operand.get_value() {
+        Value::Int(position) => *position,
         _ => panic!("Invalid value in built-in function"),
     };
 
-    match std::fs::write(path.borrow().as_str(), contents.borrow().as_str()) {
-        Ok(()) => Ok(make_result(true, Value::None)),
-        Err(e) => Ok(make_result(false, Value::new_string(e.to_string()))),
+    if position < 0 {
+        return Ok(make_result(
+            false,
+            Value::new_string(format!("Invalid position '{}'", position)),
+        ));
+    }
+    let result = file.borrow_mut().seek(position as u64);
+    match result {
+        Ok(position) => Ok(make_result(true, Value::Int(position as isize))),
+        Err(e) => Ok(make_result(false, Value::new_string(e))),
     }
 }
 
-fn file_append(
+fn file_handle_close(
     exec_session: &mut ExecSession,
     _: &ParseSession,
     _: Context,
     _: usize,
 ) -> Result<Value, Error> {
-    let operand = exec_session.get_variable("a").unwrap();
-    let path = match operand.get_value() {
-        Value::Str(path) => path,
-        _ => panic!("Invalid value in built-in function"),
-    };
-    let operand = exec_session.get_variable("b").unwrap();
-    let contents = match operand.get_value() {
-        Value::Str(contents) => contents,
-        _ => panic!("Invalid value in built-in function"),
-    };
-
-    let mut file = match std::fs::OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(path.borrow().as_str())
-    {
-        Ok(file) => file,
-        Err(e) => {
-            return Ok(make_result(false, Value::new_string(e.to_string())));
-        }
-    };
+    let file = get_file_handle(exec_session);
 
-    match file.write_all(contents.borrow().as_str().as_bytes()) {
+    let result = file.borrow_mut().close();
+    match result {
         Ok(()) => Ok(make_result(true, Value::None)),
-        Err(e) => Ok(make_result(false, Value::new_string(e.to_string()))),
+        Err(e) => Ok(make_result(false, Value::new_string(e))),
     }
 }
 
@@ -2045,3 +2661,378 @@ fn builtin_log(
     };
     Ok(Value::Float(number.log(10.0_f64)))
 }
+
+fn make_env_class() -> ClassDefinition {
+    let mut functions = HashMap::new();
+
+    let args: Vec<AnnotatedIdentifier> =
+        vec![AnnotatedIdentifier::new(String::from("a"), TYPEID_STRING)];
+    functions.insert(
+        String::from("get"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(args, env_get)),
+            false,
+            true,
+        ),
+    );
+    let args: Vec<AnnotatedIdentifier> = vec![
+        AnnotatedIdentifier::new(String::from("a"), TYPEID_STRING),
+        AnnotatedIdentifier::new(String::from("b"), TYPEID_STRING),
+    ];
+    functions.insert(
+        String::from("set"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(args, env_set)),
+            false,
+            true,
+        ),
+    );
+    let args: Vec<AnnotatedIdentifier> =
+        vec![AnnotatedIdentifier::new(String::from("a"), TYPEID_STRING)];
+    functions.insert(
+        String::from("remove"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(args, env_remove)),
+            false,
+            true,
+        ),
+    );
+    functions.insert(
+        String::from("vars"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(Vec::new(), env_vars)),
+            false,
+            true,
+        ),
+    );
+
+    ClassDefinition::new_without_constructor(functions, TYPEID_ENV)
+}
+
+// Setting or removing a variable with such a name would panic
+#[inline]
+fn is_valid_env_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['=', '\0'])
+}
+
+fn env_get(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("a").unwrap();
+    let name = match operand.get_value() {
+        Value::Str(name) => name,
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    match exec_session.env_var(name.borrow().as_str()) {
+        Ok(value) => Ok(make_result(true, Value::new_string(value))),
+        Err(e) => Ok(make_result(false, Value::new_string(e.to_string()))),
+    }
+}
+
+fn env_set(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("a").unwrap();
+    let name = match operand.get_value() {
+        Value::Str(name) => name.borrow().clone(),
+        _ => panic!("Invalid value in built-in function"),
+    };
+    let operand = exec_session.get_variable("b").unwrap();
+    let value = match operand.get_value() {
+        Value::Str(value) => value.borrow().clone(),
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    if !is_valid_env_name(&name) || value.contains('\0') {
+        return Ok(make_result(
+            false,
+            Value::new_string(format!("Invalid environment variable '{}'", name)),
+        ));
+    }
+    exec_session.set_env_var(name, value);
+    Ok(make_result(true, Value::None))
+}
+
+fn env_remove(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("a").unwrap();
+    let name = match operand.get_value() {
+        Value::Str(name) => name.borrow().clone(),
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    if !is_valid_env_name(&name) {
+        return Ok(make_result(
+            false,
+            Value::new_string(format!("Invalid environment variable '{}'", name)),
+        ));
+    }
+    exec_session.remove_env_var(name);
+    Ok(make_result(true, Value::None))
+}
+
+fn env_vars(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let vars = exec_session
+        .env_vars()
+        .into_iter()
+        .map(|(name, value)| (MapKey::Str(name), Value::new_string(value)))
+        .collect();
+    Ok(Value::new_map_instance_from(vars))
+}
+
+fn make_process_class() -> ClassDefinition {
+    let mut functions = HashMap::new();
+
+    let args: Vec<AnnotatedIdentifier> = vec![
+        AnnotatedIdentifier::new(String::from("a"), TYPEID_STRING),
+        AnnotatedIdentifier::new(String::from("b"), TYPEID_VEC),
+    ];
+    functions.insert(
+        String::from("run"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(args, process_run)),
+            false,
+            true,
+        ),
+    );
+    let args: Vec<AnnotatedIdentifier> = vec![
+        AnnotatedIdentifier::new(String::from("a"), TYPEID_STRING),
+        AnnotatedIdentifier::new(String::from("b"), TYPEID_VEC),
+        AnnotatedIdentifier::new(String::from("c"), TYPEID_MAP),
+    ];
+    functions.insert(
+        String::from("run_with"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(args, process_run_with)),
+            false,
+            true,
+        ),
+    );
+
+    ClassDefinition::new_without_constructor(functions, TYPEID_PROCESS)
+}
+
+fn process_run(
+    exec_session: &mut ExecSession,
+    parse_session: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let command = process_command(exec_session, parse_session);
+    Ok(run_process(command, None))
+}
+
+// The options are given in a Map with the keys 'stdin', 'cwd' and 'env'
+fn process_run_with(
+    exec_session: &mut ExecSession,
+    parse_session: &ParseSession,
+    context: Context,
+    pos: usize,
+) -> Result<Value, Error> {
+    let mut command = process_command(exec_session, parse_session);
+    let operand = exec_session.get_variable("c").unwrap();
+    let options = match operand.get_value() {
+        Value::Map(options) => options.borrow(),
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    let mut stdin = None;
+    let invalid_type = |value: &Value, expected: usize| {
+        Error::new(
+            context,
+            pos,
+            ErrorKind::InvalidArgumentType(
+                parse_session.get_typename(value.typeid()),
+                parse_session.get_typename(expected),
+            ),
+        )
+    };
+    for (key, value) in options.iter() {
+        let key = key.to_value().to_string(parse_session);
+        match (key.as_str(), value) {
+            ("stdin", Value::Str(input)) => stdin = Some(input.borrow().clone()),
+            ("cwd", Value::Str(path)) => {
+                command.current_dir(path.borrow().as_str());
+            }
+            ("env", Value::Map(vars)) => {
+                for (name, value) in vars.borrow().iter() {
+                    command.env(
+                        name.to_value().to_string(parse_session),
+                        value.to_string(parse_session),
+                    );
+                }
+            }
+            ("stdin" | "cwd", _) => return Err(invalid_type(value, TYPEID_STRING)),
+            ("env", _) => return Err(invalid_type(value, TYPEID_MAP)),
+            _ => return Err(Error::new(context, pos, ErrorKind::KeyNotFound(key))),
+        }
+    }
+
+    Ok(run_process(command, stdin))
+}
+
+// The command and its arguments, any value can be used as an argument
+fn process_command(
+    exec_session: &ExecSession,
+    parse_session: &ParseSession,
+) -> std::process::Command {
+    let operand = exec_session.get_variable("a").unwrap();
+    let mut command = match operand.get_value() {
+        Value::Str(program) => std::process::Command::new(program.borrow().as_str()),
+        _ => panic!("Invalid value in built-in function"),
+    };
+    exec_session.apply_env(&mut command);
+    let operand = exec_session.get_variable("b").unwrap();
+    match operand.get_value() {
+        Value::Vector(args) => {
+            for arg in args.borrow().iter() {
+                command.arg(arg.to_string(parse_session));
+            }
+        }
+        _ => panic!("Invalid value in built-in function"),
+    }
+    command
+}
+
+// A Result that holds a Map with the exit status, the standard output and
+// the standard error of the process, or why it could not be started
+fn run_process(mut command: std::process::Command, stdin: Option<String>) -> Value {
+    use std::process::Stdio;
+
+    command
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => return make_result(false, Value::new_string(e.to_string())),
+    };
+
+    // the input is written while the output is read, so neither side
+    // can fill up its pipe and wait for the other one forever
+    let writer = match (stdin, child.stdin.take()) {
+        (Some(input), Some(mut pipe)) => Some(std::thread::spawn(move || {
+            // a process that exits without reading all of its input is not an error
+            _ = pipe.write_all(input.as_bytes());
+        })),
+        _ => None,
+    };
+    let output = child.wait_with_output();
+    if let Some(writer) = writer {
+        _ = writer.join();
+    }
+
+    match output {
+        Ok(output) => {
+            let status = match output.status.code() {
+                Some(code) => Value::Int(code as isize),
+                // the process was stopped by a signal
+                None => Value::None,
+            };
+            let fields = [
+                ("status", status),
+                (
+                    "stdout",
+                    Value::new_string(String::from_utf8_lossy(&output.stdout).to_string()),
+                ),
+                (
+                    "stderr",
+                    Value::new_string(String::from_utf8_lossy(&output.stderr).to_string()),
+                ),
+            ];
+            let map = fields
+                .into_iter()
+                .map(|(key, value)| (MapKey::Str(key.to_string()), value))
+                .collect();
+            make_result(true, Value::new_map_instance_from(map))
+        }
+        Err(e) => make_result(false, Value::new_string(e.to_string())),
+    }
+}
+
+fn make_json_class() -> ClassDefinition {
+    let mut functions = HashMap::new();
+
+    let args: Vec<AnnotatedIdentifier> =
+        vec![AnnotatedIdentifier::new(String::from("a"), TYPEID_STRING)];
+    functions.insert(
+        String::from("parse"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(args, json_parse)),
+            false,
+            true,
+        ),
+    );
+    let args: Vec<AnnotatedIdentifier> = vec![
+        AnnotatedIdentifier::new(String::from("a"), TYPEID_DYN),
+        AnnotatedIdentifier::new(String::from("b"), TYPEID_BOOL),
+    ];
+    functions.insert(
+        String::from("stringify"),
+        ClassFunction::new(
+            Function::BuiltinFunction(BuiltinFunction::new(args, json_stringify)),
+            false,
+            true,
+        ),
+    );
+
+    ClassDefinition::new_without_constructor(functions, TYPEID_JSON)
+}
+
+fn json_parse(
+    exec_session: &mut ExecSession,
+    _: &ParseSession,
+    _: Context,
+    _: usize,
+) -> Result<Value, Error> {
+    let operand = exec_session.get_variable("a").unwrap();
+    let text = match operand.get_value() {
+        Value::Str(text) => text,
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    let result = json::parse(text.borrow().as_str());
+    match result {
+        Ok(value) => Ok(make_result(true, value)),
+        Err(e) => Ok(make_result(false, Value::new_string(e))),
+    }
+}
+
+fn json_stringify(
+    exec_session: &mut ExecSession,
+    parse_session: &ParseSession,
+    context: Context,
+    pos: usize,
+) -> Result<Value, Error> {
+    let value = exec_session.get_variable("a").unwrap().get_value_clone();
+    let operand = exec_session.get_variable("b").unwrap();
+    let pretty = match operand.get_value() {
+        Value::Bool(pretty) => *pretty,
+        _ => panic!("Invalid value in built-in function"),
+    };
+
+    match json::stringify(&value, pretty, parse_session) {
+        Ok(text) => Ok(Value::new_string(text)),
+        Err(e) => Err(Error::new(context, pos, ErrorKind::CustomError(e))),
+    }
+}
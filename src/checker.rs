@@ -0,0 +1,920 @@
+use crate::builtin::{
+    TYPEID_BOOL, TYPEID_DYN, TYPEID_FILE_HANDLE, TYPEID_FLOAT, TYPEID_FUNCTION, TYPEID_INT,
+    TYPEID_MAP, TYPEID_NONE, TYPEID_STRING, TYPEID_VEC,
+};
+use crate::class::PropertyDefinition;
+use crate::error::{Context, Error, ErrorKind};
+use crate::expression::Expression;
+use crate::function::{CallArguments, Function, FunctionCall, UserFunction};
+use crate::instruction::Instruction;
+use crate::literal::InterpolationPart;
+use crate::operation;
+use crate::session::{ExecSession, FnQueryOptions, ParseSession};
+use crate::token::{Operator, TokenKind};
+use crate::variable::Value;
+use std::collections::HashMap;
+
+// Static analysis that runs before any code is executed. The types of
+// expressions are inferred from literals, annotations, function signatures
+// and property definitions. Only errors that are certain to happen when the
+// offending code runs are reported, values of unknown or dyn type are not
+// checked. A static type is an upper bound, at runtime the value may have
+// any type that is a subtype of it.
+
+// None when the type is not known
+type Type = Option<usize>;
+
+// Types of the parameters and the return value of a function
+type Signature = (Vec<usize>, Type);
+
+// Checks the instructions and the functions and classes
+// that were defined since the given function count and typeid.
+// In strict mode calls to functions that do not exist and identifiers
+// that are used before they are declared are reported as well, otherwise
+// they are left to be found when the code runs
+pub fn check(
+    instructions: &[Instruction],
+    first_function: usize,
+    first_typeid: usize,
+    parse_session: &ParseSession,
+    exec_session: &ExecSession,
+    strict: bool,
+) -> Vec<Error> {
+    let mut checker = Checker::new(parse_session, strict);
+
+    // constants are visible in functions as well
+    for (name, var) in exec_session.global_variables() {
+        if var.is_constant() {
+            checker
+                .constants
+                .insert(name.clone(), Some(var.get_value().typeid()));
+        }
+    }
+    for item in instructions {
+        if let Instruction::ConstantInit(ci) = item {
+            for id in ci.identifiers() {
+                let typeid = id.typeid().and_then(annotation);
+                checker.constants.insert(id.name().to_string(), typeid);
+            }
+        }
+    }
+
+    for name in &parse_session.user_functions()[first_function..] {
+        if let Ok(Function::UserFunction(f)) = parse_session.get_function(name, None) {
+            checker.check_function(f, HashMap::new(), None);
+        }
+    }
+
+    for typeid in first_typeid..parse_session.get_next_typeid() {
+        checker.check_class(typeid);
+    }
+
+    // functions cannot see global variables, so they are only known at the top level
+    checker.scope = exec_session
+        .global_variables()
+        .filter(|(_, var)| !var.is_constant())
+        .map(|(name, var)| {
+            let typeid = (!var.is_dynamic()).then(|| var.get_value().typeid());
+            (name.clone(), typeid)
+        })
+        .collect();
+    checker.check_body(instructions);
+
+    let mut errors = checker.errors;
+    errors.sort_by_key(|e| e.position());
+    errors.dedup_by(|a, b| {
+        a.position() == b.position() && a.kind().to_string() == b.kind().to_string()
+    });
+    errors
+}
+
+// The function that is being checked
+#[derive(Debug, Clone, Copy)]
+struct FunctionState {
+    // None if the function may return any type
+    return_typeid: Type,
+    // the instruction of the function body that is being checked,
+    // return type errors are reported in its context
+    body_item: Context,
+}
+
+struct Checker<'a> {
+    parse_session: &'a ParseSession,
+    scope: HashMap<String, Type>,
+    constants: HashMap<String, Type>,
+    function: Option<FunctionState>,
+    // number of loops around the instruction in the function that is being checked
+    loops: usize,
+    errors: Vec<Error>,
+    strict: bool,
+}
+
+impl<'a> Checker<'a> {
+    fn new(parse_session: &'a ParseSession, strict: bool) -> Self {
+        Checker {
+            parse_session,
+            scope: HashMap::new(),
+            constants: HashMap::new(),
+            function: None,
+            loops: 0,
+            errors: Vec::new(),
+            strict,
+        }
+    }
+
+    fn check_class(&mut self, typeid: usize) {
+        let class_definition = match self.parse_session.get_class_definition(typeid) {
+            Some(c) if !c.is_interface() => c,
+            _ => return,
+        };
+
+        // properties are initialized with the arguments of the constructor in scope
+        let mut constructor_scope = HashMap::new();
+        if let Some(constructor) = class_definition.get_class_function("new") {
+            for argument in constructor.function().arguments() {
+                constructor_scope
+                    .insert(argument.name().to_string(), annotation(argument.typeid()));
+            }
+        }
+
+        for property in class_definition.property_definitions() {
+            self.scope = constructor_scope.clone();
+            self.function = None;
+            let value_type = self.expr_type(property.init_expression());
+            let expected = property.id().typeid();
+            if self.is_incompatible(value_type, expected) {
+                self.errors.push(Error::new(
+                    property.context(),
+                    property.assign_pos(),
+                    ErrorKind::InvalidAssignment(
+                        self.typename(Some(expected)),
+                        self.typename(value_type),
+                    ),
+                ));
+            }
+        }
+
+        for classfunc in class_definition.class_functions().values() {
+            if let Function::UserFunction(f) = classfunc.function() {
+                let self_type = classfunc.uses_self().then_some(typeid);
+                self.check_function(f, HashMap::new(), self_type);
+            }
+        }
+    }
+
+    fn check_function(
+        &mut self,
+        function: &UserFunction,
+        mut scope: HashMap<String, Type>,
+        self_type: Option<usize>,
+    ) {
+        for argument in function.arguments() {
+            scope.insert(argument.name().to_string(), annotation(argument.typeid()));
+        }
+        if let Some(typeid) = self_type {
+            scope.insert("self".to_string(), Some(typeid));
+        }
+
+        let outer_scope = std::mem::replace(&mut self.scope, scope);
+        let outer_function = self.function.take();
+        let outer_loops = std::mem::take(&mut self.loops);
+
+        let return_typeid = function.return_typeid();
+        for item in function.body() {
+            self.function = Some(FunctionState {
+                return_typeid,
+                body_item: item.context(),
+            });
+            self.check_instruction(item);
+            if is_jump(item) {
+                break;
+            }
+        }
+
+        // a function that never returns cannot produce the value its signature promises
+        if let (Some(expected), Some(pos)) = (return_typeid, function.return_type_pos()) {
+            if expected != TYPEID_NONE && !contains_return(function.body()) {
+                self.errors.push(Error::new(
+                    function.context(),
+                    pos - function.context().start,
+                    ErrorKind::InvalidReturnType(
+                        self.typename(Some(TYPEID_NONE)),
+                        self.typename(Some(expected)),
+                    ),
+                ));
+            }
+        }
+
+        self.scope = outer_scope;
+        self.function = outer_function;
+        self.loops = outer_loops;
+    }
+
+    fn check_body(&mut self, body: &[Instruction]) {
+        for item in body {
+            self.check_instruction(item);
+            // the rest of the body is unreachable
+            if is_jump(item) {
+                break;
+            }
+        }
+    }
+
+    // Variables declared in a block are not visible after it
+    fn check_block(&mut self, body: &[Instruction]) {
+        let before = self.scope.clone();
+        self.check_body(body);
+        self.scope = before;
+    }
+
+    fn check_instruction(&mut self, instruction: &Instruction) {
+        match instruction {
+            Instruction::Expression(e) => {
+                self.expr_type(e);
+            }
+            Instruction::Branch(b) => {
+                let mut branches = vec![b.main_branch()];
+                branches.extend(b.else_if_branches());
+                for branch in branches {
+                    let condition = self.expr_type(branch.condition());
+                    self.check_condition(condition, b.context());
+                    self.check_block(branch.body());
+                }
+                self.check_block(b.else_branch());
+            }
+            Instruction::WhileLoop(wl) => {
+                let condition = self.expr_type(wl.condition());
+                self.check_condition(condition, wl.context());
+                self.loops += 1;
+                self.check_block(wl.body());
+                self.loops -= 1;
+            }
+            Instruction::ForLoop(fl) => {
+                let operand = self.expr_type(fl.operand());
+                if let Some(typeid) = operand {
+                    if ![TYPEID_VEC, TYPEID_MAP, TYPEID_FILE_HANDLE, TYPEID_DYN].contains(&typeid) {
+                        self.errors.push(Error::new(
+                            fl.context(),
+                            fl.operand_pos(),
+                            ErrorKind::ForLoopNotVec(self.typename(operand)),
+                        ));
+                    }
+                }
+                // the loop variable belongs to the block of the body
+                let before = self.scope.clone();
+                self.scope.insert(fl.alias().to_string(), None);
+                self.loops += 1;
+                self.check_body(fl.body());
+                self.loops -= 1;
+                self.scope = before;
+            }
+            Instruction::Return(r) => {
+                let value_type = match r.expr() {
+                    Some(e) => self.expr_type(e),
+                    None => Some(TYPEID_NONE),
+                };
+                if let Some(state) = self.function {
+                    if let Some(expected) = state.return_typeid {
+                        if self.is_incompatible(value_type, expected) {
+                            let pos = r.expr().map(|e| e.context().start).unwrap_or(0);
+                            self.errors.push(Error::new(
+                                state.body_item,
+                                pos.saturating_sub(state.body_item.start),
+                                ErrorKind::InvalidReturnType(
+                                    self.typename(value_type),
+                                    self.typename(Some(expected)),
+                                ),
+                            ));
+                        }
+                    }
+                }
+            }
+            // at the top level they end the instruction they are in instead
+            Instruction::Break(b) if self.function.is_some() && self.loops == 0 => {
+                let kind = ErrorKind::OutsideLoop("break".to_string());
+                self.errors.push(Error::new(b.context(), 0, kind));
+            }
+            Instruction::Continue(c) if self.function.is_some() && self.loops == 0 => {
+                let kind = ErrorKind::OutsideLoop("continue".to_string());
+                self.errors.push(Error::new(c.context(), 0, kind));
+            }
+            Instruction::Break(_) | Instruction::Continue(_) => (),
+            Instruction::GlobalDeclaration(gd) => {
+                // the types of global variables are not known in functions
+                if self.function.is_some() {
+                    for name in gd.names() {
+                        self.scope.insert(name.clone(), None);
+                    }
+                }
+            }
+            Instruction::VariableInit(vi) | Instruction::ConstantInit(vi) => {
+                // an anonymous function can refer to the variable it is assigned to
+                if let [id] = vi.identifiers().as_slice() {
+                    if vi.expr().is_lambda() {
+                        let name = id.name().to_string();
+                        match instruction {
+                            Instruction::ConstantInit(_) => {
+                                self.constants.insert(name, Some(TYPEID_FUNCTION))
+                            }
+                            _ => self.scope.insert(name, Some(TYPEID_FUNCTION)),
+                        };
+                    }
+                }
+                let value_type = self.expr_type(vi.expr());
+                for id in vi.identifiers() {
+                    let variable_type = match id.typeid() {
+                        Some(expected) => {
+                            if self.is_incompatible(value_type, expected) {
+                                self.errors.push(Error::new(
+                                    vi.context(),
+                                    vi.assign_pos(),
+                                    ErrorKind::InvalidAssignment(
+                                        self.typename(Some(expected)),
+                                        self.typename(value_type),
+                                    ),
+                                ));
+                            }
+                            annotation(expected)
+                        }
+                        None => value_type,
+                    };
+                    if let Instruction::ConstantInit(_) = instruction {
+                        self.constants.insert(id.name().to_string(), variable_type);
+                    } else {
+                        self.scope.insert(id.name().to_string(), variable_type);
+                    }
+                }
+            }
+            Instruction::VariableAssign(va) => {
+                let value_type = self.expr_type(va.expr());
+                let target_type = match va.source_expr() {
+                    Some(source) => {
+                        let source_type = self.expr_type(source);
+                        self.property_type(source_type, va.identifier())
+                    }
+                    None => match self.scope.get(va.identifier()) {
+                        Some(t) => *t,
+                        None if self.constants.contains_key(va.identifier()) => {
+                            self.errors.push(Error::new(
+                                va.context(),
+                                0,
+                                ErrorKind::ConstantAssignment(va.identifier().to_string()),
+                            ));
+                            None
+                        }
+                        None => {
+                            if self.strict {
+                                self.errors.push(Error::new(
+                                    va.context(),
+                                    0,
+                                    ErrorKind::IdentifierNotFound,
+                                ));
+                            }
+                            None
+                        }
+                    },
+                };
+                let value_type = match va.compound_operator() {
+                    Some(operator) => self.binary(
+                        operator,
+                        target_type,
+                        value_type,
+                        va.context(),
+                        va.assign_pos(),
+                    ),
+                    None => value_type,
+                };
+                if let Some(expected) = target_type {
+                    if self.is_incompatible(value_type, expected) {
+                        self.errors.push(Error::new(
+                            va.context(),
+                            va.assign_pos(),
+                            ErrorKind::InvalidAssignment(
+                                self.typename(Some(expected)),
+                                self.typename(value_type),
+                            ),
+                        ));
+                    }
+                }
+            }
+            Instruction::IndexAssign(ia) => {
+                self.expr_type(ia.expr());
+                self.expr_type(ia.container());
+                self.expr_type(ia.index());
+            }
+        }
+    }
+
+    #[inline]
+    fn check_condition(&mut self, condition: Type, context: Context) {
+        if let Some(typeid) = condition {
+            if typeid != TYPEID_BOOL && typeid != TYPEID_DYN {
+                self.errors.push(Error::new(
+                    context,
+                    0,
+                    ErrorKind::ConditionalExpressionNotBool(self.typename(condition)),
+                ));
+            }
+        }
+    }
+
+    fn expr_type(&mut self, expression: &Expression) -> Type {
+        let context = expression.context();
+        let mut stack: Vec<Item> = Vec::new();
+
+        for token in expression.tokens() {
+            let pos = token.pos();
+            match token.kind() {
+                TokenKind::Value(v) => stack.push(Item::Type(Some(v.typeid()))),
+                TokenKind::Identifier(id) => stack.push(Item::Identifier(id, pos)),
+                TokenKind::FunctionCall(f) => stack.push(Item::Call(f, pos)),
+                TokenKind::Arguments(a) => stack.push(Item::Arguments(a)),
+                TokenKind::VecLiteral(v) => {
+                    for item in v.items() {
+                        self.expr_type(item);
+                    }
+                    stack.push(Item::Type(Some(TYPEID_VEC)));
+                }
+                TokenKind::MapLiteral(m) => {
+                    for (key, value) in m.entries() {
+                        self.expr_type(key);
+                        self.expr_type(value);
+                    }
+                    stack.push(Item::Type(Some(TYPEID_MAP)));
+                }
+                TokenKind::Interpolation(s) => {
+                    for part in s.parts() {
+                        if let InterpolationPart::Expression(e) = part {
+                            self.expr_type(e);
+                        }
+                    }
+                    stack.push(Item::Type(Some(TYPEID_STRING)));
+                }
+                TokenKind::Lambda(f) => {
+                    if let Function::UserFunction(f) = f.as_ref() {
+                        // the lambda captures the variables in scope
+                        self.check_function(f, self.scope.clone(), None);
+                    }
+                    stack.push(Item::Type(Some(TYPEID_FUNCTION)));
+                }
+                TokenKind::Operator(op) => {
+                    let result = match op {
+                        Operator::Dot => {
+                            let rhs = stack.pop().unwrap();
+                            let lhs = stack.pop().unwrap();
+                            let lhs = self.resolve(lhs, context);
+                            match rhs {
+                                Item::Identifier(name, _) => self.property_type(lhs, name),
+                                Item::Call(f, _) => {
+                                    let pos = f.context().start - context.start;
+                                    self.call_type(f, Some(lhs), context, pos)
+                                }
+                                _ => None,
+                            }
+                        }
+                        Operator::Call => {
+                            let arguments = match stack.pop().unwrap() {
+                                Item::Arguments(a) => a,
+                                _ => unreachable!(
+                                    "The call operator should be followed by its arguments"
+                                ),
+                            };
+                            let callee = stack.pop().unwrap();
+                            let callee = self.resolve(callee, context);
+                            for argument in arguments.arguments() {
+                                self.expr_type(argument);
+                            }
+                            if let Some(typeid) = callee {
+                                if typeid != TYPEID_FUNCTION && typeid != TYPEID_DYN {
+                                    self.errors.push(Error::new(
+                                        context,
+                                        pos,
+                                        ErrorKind::NotCallable(self.typename(callee)),
+                                    ));
+                                }
+                            }
+                            None
+                        }
+                        Operator::Index => {
+                            let rhs = stack.pop().unwrap();
+                            self.resolve(rhs, context);
+                            let lhs = stack.pop().unwrap();
+                            match self.resolve(lhs, context) {
+                                Some(TYPEID_STRING) => Some(TYPEID_STRING),
+                                _ => None,
+                            }
+                        }
+                        Operator::Typeof => {
+                            let operand = stack.pop().unwrap();
+                            self.resolve(operand, context);
+                            Some(TYPEID_STRING)
+                        }
+                        Operator::Neg | Operator::Not => {
+                            let operand = stack.pop().unwrap();
+                            let operand = self.resolve(operand, context);
+                            self.unary(*op, operand, context, pos)
+                        }
+                        _ => {
+                            let rhs = stack.pop().unwrap();
+                            let rhs = self.resolve(rhs, context);
+                            let lhs = stack.pop().unwrap();
+                            let lhs = self.resolve(lhs, context);
+                            self.binary(*op, lhs, rhs, context, pos)
+                        }
+                    };
+                    stack.push(Item::Type(result));
+                }
+                TokenKind::Parenthesis(_) => unreachable!(),
+            }
+        }
+
+        match stack.pop() {
+            Some(item) => self.resolve(item, context),
+            None => None,
+        }
+    }
+
+    fn resolve(&mut self, item: Item, context: Context) -> Type {
+        match item {
+            Item::Type(t) => t,
+            Item::Identifier(name, pos) => match self.scope.get(name) {
+                Some(t) => *t,
+                None if self.constants.contains_key(name) => self.constants[name],
+                None => {
+                    // the name of a function can be used as a value
+                    if self.parse_session.get_function(name, None).is_ok() {
+                        Some(TYPEID_FUNCTION)
+                    } else {
+                        if self.strict {
+                            self.errors.push(Error::new(
+                                context,
+                                pos,
+                                ErrorKind::IdentifierNotFound,
+                            ));
+                        }
+                        None
+                    }
+                }
+            },
+            Item::Call(f, pos) => self.call_type(f, None, context, pos),
+            Item::Arguments(_) => unreachable!("Call arguments are not a value"),
+        }
+    }
+
+    fn call_type(
+        &mut self,
+        call: &FunctionCall,
+        receiver: Option<Type>,
+        context: Context,
+        pos: usize,
+    ) -> Type {
+        let argument_types: Vec<Type> = call
+            .arguments()
+            .iter()
+            .map(|argument| self.expr_type(argument))
+            .collect();
+
+        let (parameters, return_type) = match self.signature(call, receiver) {
+            Some(signature) => signature,
+            None => {
+                if self.strict && !self.is_defined(call, receiver) {
+                    self.errors.push(Error::new(
+                        context,
+                        pos + call.name_pos(),
+                        ErrorKind::FunctionNotFound,
+                    ));
+                }
+                return None;
+            }
+        };
+
+        if parameters.len() != argument_types.len() {
+            self.errors.push(Error::new(
+                context,
+                pos + call.name_pos(),
+                ErrorKind::InvalidNumberOfArguments,
+            ));
+            return return_type;
+        }
+
+        for ((argument, argument_type), expected) in
+            call.arguments().iter().zip(argument_types).zip(parameters)
+        {
+            if self.is_incompatible(argument_type, expected) {
+                self.errors.push(Error::new(
+                    context,
+                    argument.context().start - call.context().start,
+                    ErrorKind::InvalidArgumentType(
+                        self.typename(argument_type),
+                        self.typename(Some(expected)),
+                    ),
+                ));
+            }
+        }
+
+        return_type
+    }
+
+    // The signature of the function that a call resolves to,
+    // if it is the same for every possible type of the receiver
+    fn signature(&self, call: &FunctionCall, receiver: Option<Type>) -> Option<Signature> {
+        match receiver {
+            None => match call.associated_typeid() {
+                Some(typeid) => {
+                    let options = FnQueryOptions::new(typeid, false, true);
+                    let function = self
+                        .parse_session
+                        .get_function(call.name(), Some(options))
+                        .ok()?;
+                    let (parameters, return_type) = signature_of(function);
+                    if call.name() == "new" {
+                        Some((parameters, Some(typeid)))
+                    } else {
+                        Some((parameters, return_type))
+                    }
+                }
+                None => {
+                    // a variable that holds a function takes precedence
+                    if self.scope.contains_key(call.name()) {
+                        return None;
+                    }
+                    let name = call.qualified_name().unwrap_or(call.name());
+                    let function = self.parse_session.get_function(name, None).ok()?;
+                    Some(signature_of(function))
+                }
+            },
+            Some(None) => None,
+            Some(Some(typeid)) => {
+                if let Some(parent) = call.super_typeid() {
+                    let options = FnQueryOptions::new(parent, true, true);
+                    let function = self
+                        .parse_session
+                        .get_function(call.name(), Some(options))
+                        .ok()?;
+                    return Some(signature_of(function));
+                }
+
+                // an overriding method may have a different signature
+                let mut result: Option<Signature> = None;
+                for subtype in self.subtypes(typeid) {
+                    let options = FnQueryOptions::new(subtype, true, true);
+                    if let Ok(function) =
+                        self.parse_session.get_function(call.name(), Some(options))
+                    {
+                        let signature = signature_of(function);
+                        match result {
+                            Some(ref s) if *s != signature => return None,
+                            _ => result = Some(signature),
+                        }
+                    }
+                }
+                result
+            }
+        }
+    }
+
+    // Whether a call may find a function to call when it runs
+    fn is_defined(&self, call: &FunctionCall, receiver: Option<Type>) -> bool {
+        let function_exists = |name: &str, options: Option<FnQueryOptions>| {
+            self.parse_session.get_function(name, options).is_ok()
+        };
+        match receiver {
+            None => match call.associated_typeid() {
+                Some(typeid) => {
+                    function_exists(call.name(), Some(FnQueryOptions::new(typeid, false, true)))
+                }
+                None => {
+                    self.scope.contains_key(call.name())
+                        || function_exists(call.qualified_name().unwrap_or(call.name()), None)
+                }
+            },
+            Some(None) => true,
+            Some(Some(typeid)) => match call.super_typeid() {
+                Some(parent) => {
+                    function_exists(call.name(), Some(FnQueryOptions::new(parent, true, true)))
+                }
+                None => {
+                    // a property that holds a function can be called like a method
+                    let subtypes = self.subtypes(typeid);
+                    subtypes.is_empty()
+                        || subtypes.into_iter().any(|subtype| {
+                            function_exists(
+                                call.name(),
+                                Some(FnQueryOptions::new(subtype, true, true)),
+                            ) || self.find_property(subtype, call.name()).is_some()
+                        })
+                }
+            },
+        }
+    }
+
+    // The declared type of a property, if it is the same
+    // for every possible type of the object
+    fn property_type(&self, object: Type, name: &str) -> Type {
+        let typeid = object?;
+        let mut result: Option<usize> = None;
+        for subtype in self.subtypes(typeid) {
+            if let Some(property) = self.find_property(subtype, name) {
+                let property_type = annotation(property.id().typeid())?;
+                match result {
+                    Some(t) if t != property_type => return None,
+                    _ => result = Some(property_type),
+                }
+            }
+        }
+        result
+    }
+
+    // The definition of a property of a class or one of its parents
+    fn find_property(&self, typeid: usize, name: &str) -> Option<&'a PropertyDefinition> {
+        let mut current = self.parse_session.get_class_definition(typeid);
+        while let Some(class_definition) = current {
+            let property = class_definition
+                .property_definitions()
+                .iter()
+                .find(|p| p.id().name() == name);
+            if property.is_some() {
+                return property;
+            }
+            current = class_definition
+                .parent()
+                .and_then(|id| self.parse_session.get_class_definition(id));
+        }
+        None
+    }
+
+    // Classes whose instances a value of the given type can be
+    fn subtypes(&self, typeid: usize) -> Vec<usize> {
+        if typeid == TYPEID_DYN || typeid == TYPEID_NONE {
+            return Vec::new();
+        }
+        (TYPEID_INT..self.parse_session.get_next_typeid())
+            .filter(|id| self.parse_session.is_subtype(*id, typeid))
+            .filter(|id| match self.parse_session.get_class_definition(*id) {
+                Some(c) => !c.is_interface(),
+                None => false,
+            })
+            .collect()
+    }
+
+    fn binary(
+        &mut self,
+        operator: Operator,
+        lhs: Type,
+        rhs: Type,
+        context: Context,
+        pos: usize,
+    ) -> Type {
+        let is_comparison = matches!(
+            operator,
+            Operator::Equal | Operator::NotEqual | Operator::Is
+        );
+        match (lhs.and_then(sample_value), rhs.and_then(sample_value)) {
+            (Some(lhs), Some(rhs)) => {
+                // operators are applied to sample values to find
+                // the type of their result, exactly like at runtime
+                let result = operation::apply_binary(operator, lhs, rhs, self.parse_session)
+                    .map_err(|e| Error::new(context, pos, e));
+                self.operation_result(result)
+            }
+            _ if is_comparison => Some(TYPEID_BOOL),
+            _ => None,
+        }
+    }
+
+    fn unary(&mut self, operator: Operator, operand: Type, context: Context, pos: usize) -> Type {
+        let operand = operand.and_then(sample_value)?;
+        let result = operation::apply_unary(operator, operand, self.parse_session)
+            .map_err(|e| Error::new(context, pos, e));
+        self.operation_result(result)
+    }
+
+    #[inline]
+    fn operation_result(&mut self, result: Result<Value, Error>) -> Type {
+        match result {
+            Ok(value) => Some(value.typeid()),
+            Err(e) => {
+                if let ErrorKind::InvalidOperationForType(..)
+                | ErrorKind::InvalidOperationForTypes(..) = e.kind()
+                {
+                    self.errors.push(e);
+                }
+                None
+            }
+        }
+    }
+
+    // Whether no value of the given type can ever be used
+    // where a value of the expected type is required
+    fn is_incompatible(&self, value: Type, expected: usize) -> bool {
+        let typeid = match value {
+            Some(typeid) => typeid,
+            None => return false,
+        };
+        if typeid == TYPEID_DYN || expected == TYPEID_DYN {
+            return false;
+        }
+        if self.parse_session.is_subtype(typeid, expected) {
+            return false;
+        }
+        // builtin types have no subtypes and are not subtypes of anything else
+        if self.is_builtin_type(typeid) || self.is_builtin_type(expected) {
+            return true;
+        }
+        // a subclass of a class may implement any interface
+        if self.is_interface(typeid) || self.is_interface(expected) {
+            return false;
+        }
+        // the value may be an instance of a subclass of the expected class
+        !self.parse_session.is_subtype(expected, typeid)
+    }
+
+    #[inline]
+    fn is_builtin_type(&self, typeid: usize) -> bool {
+        if typeid < TYPEID_INT {
+            return true;
+        }
+        match self.parse_session.get_class_definition(typeid) {
+            Some(c) => c.is_builtin(),
+            None => true,
+        }
+    }
+
+    #[inline]
+    fn is_interface(&self, typeid: usize) -> bool {
+        if typeid < TYPEID_INT {
+            return false;
+        }
+        match self.parse_session.get_class_definition(typeid) {
+            Some(c) => c.is_interface(),
+            None => false,
+        }
+    }
+
+    #[inline]
+    fn typename(&self, typeid: Type) -> String {
+        self.parse_session
+            .get_typename(typeid.unwrap_or(TYPEID_DYN))
+    }
+}
+
+// An operand of an expression that is being checked
+enum Item<'e> {
+    Type(Type),
+    Identifier(&'e str, usize),
+    Call(&'e FunctionCall, usize),
+    Arguments(&'e CallArguments),
+}
+
+// Variables declared with dyn are not checked
+#[inline]
+fn annotation(typeid: usize) -> Type {
+    (typeid != TYPEID_DYN).then_some(typeid)
+}
+
+#[inline]
+fn signature_of(function: &Function) -> Signature {
+    let parameters = function.arguments().iter().map(|a| a.typeid()).collect();
+    let return_type = match function {
+        Function::UserFunction(f) => f.return_typeid(),
+        Function::BuiltinFunction(_) => None,
+    };
+    (parameters, return_type)
+}
+
+// A value of a primitive type that operators can be applied to
+#[inline]
+fn sample_value(typeid: usize) -> Option<Value> {
+    match typeid {
+        TYPEID_NONE => Some(Value::None),
+        TYPEID_INT => Some(Value::Int(2)),
+        TYPEID_FLOAT => Some(Value::Float(2.0)),
+        TYPEID_STRING => Some(Value::new_string("a".to_string())),
+        TYPEID_BOOL => Some(Value::Bool(true)),
+        _ => None,
+    }
+}
+
+#[inline]
+fn is_jump(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Return(_) | Instruction::Break(_) | Instruction::Continue(_)
+    )
+}
+
+fn contains_return(body: &[Instruction]) -> bool {
+    body.iter().any(|item| match item {
+        Instruction::Return(_) => true,
+        Instruction::Branch(b) => {
+            contains_return(b.main_branch().body())
+                || b.else_if_branches()
+                    .iter()
+                    .any(|branch| contains_return(branch.body()))
+                || contains_return(b.else_branch())
+        }
+        Instruction::WhileLoop(wl) => contains_return(wl.body()),
+        Instruction::ForLoop(fl) => contains_return(fl.body()),
+        _ => false,
+    })
+}
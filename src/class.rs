@@ -1,16 +1,22 @@
 use crate::builtin::TYPEID_DYN;
+use crate::compiler::{self, Chunk};
 use crate::error::{Context, Error, ErrorKind};
 use crate::expression::Expression;
 use crate::function::{BuiltinFunction, Function};
 use crate::session::{ExecSession, ParseSession};
 use crate::variable::{AnnotatedIdentifier, Value, Variable};
+use crate::vm;
+use std::cell::OnceCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub struct PropertyDefinition {
     is_public: bool,
     id: AnnotatedIdentifier,
     init_expression: Expression,
+    // compiled when the first instance is created
+    init_chunk: OnceCell<Rc<Chunk>>,
 
     context: Context,
     assign_pos: usize,
@@ -29,10 +35,45 @@ impl PropertyDefinition {
             is_public,
             id,
             init_expression,
+            init_chunk: OnceCell::new(),
             context,
             assign_pos,
         }
     }
+
+    #[inline]
+    pub fn id(&self) -> &AnnotatedIdentifier {
+        &self.id
+    }
+
+    #[inline]
+    pub fn init_expression(&self) -> &Expression {
+        &self.init_expression
+    }
+
+    // The initializer runs in the scope of the constructor
+    // and refers to its arguments by name
+    #[inline]
+    pub fn init_chunk(&self, parse_session: &ParseSession) -> Rc<Chunk> {
+        self.init_chunk
+            .get_or_init(|| {
+                Rc::new(compiler::compile_expression(
+                    &self.init_expression,
+                    parse_session,
+                ))
+            })
+            .clone()
+    }
+
+    #[inline]
+    pub fn context(&self) -> Context {
+        self.context
+    }
+
+    #[inline]
+    pub fn assign_pos(&self) -> usize {
+        self.assign_pos
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -51,6 +92,89 @@ impl ClassFunction {
             is_public,
         }
     }
+
+    #[inline]
+    pub fn function(&self) -> &Function {
+        &self.function
+    }
+
+    #[inline]
+    pub fn uses_self(&self) -> bool {
+        self.uses_self
+    }
+
+    #[inline]
+    pub fn is_public(&self) -> bool {
+        self.is_public
+    }
+}
+
+// A method that a class has to define to implement an interface,
+// or that a class has to keep when it overrides a method of its parent
+
+#[derive(Debug, Clone)]
+pub struct MethodSignature {
+    name: String,
+    arguments: Vec<AnnotatedIdentifier>,
+    return_typeid: Option<usize>,
+    uses_self: bool,
+}
+
+impl MethodSignature {
+    #[inline]
+    pub fn new(
+        name: String,
+        arguments: Vec<AnnotatedIdentifier>,
+        return_typeid: Option<usize>,
+        uses_self: bool,
+    ) -> MethodSignature {
+        MethodSignature {
+            name,
+            arguments,
+            return_typeid,
+            uses_self,
+        }
+    }
+
+    // None for built-in functions, their signatures are not known
+    pub fn of(name: &str, classfunc: &ClassFunction) -> Option<MethodSignature> {
+        match &classfunc.function {
+            Function::UserFunction(f) => Some(MethodSignature::new(
+                name.to_string(),
+                f.arguments().clone(),
+                f.return_typeid(),
+                classfunc.uses_self,
+            )),
+            Function::BuiltinFunction(_) => None,
+        }
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Whether the method is public and can be called like this signature describes
+    pub fn matches(&self, classfunc: &ClassFunction) -> bool {
+        classfunc.is_public && self.matches_private(classfunc)
+    }
+
+    // Same as matches, but the method may be private
+    pub fn matches_private(&self, classfunc: &ClassFunction) -> bool {
+        let function = match &classfunc.function {
+            Function::UserFunction(f) => f,
+            Function::BuiltinFunction(_) => return false,
+        };
+
+        classfunc.uses_self == self.uses_self
+            && function.return_typeid() == self.return_typeid
+            && function.arguments().len() == self.arguments.len()
+            && function
+                .arguments()
+                .iter()
+                .zip(&self.arguments)
+                .all(|(l, r)| l.typeid() == r.typeid())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -58,6 +182,12 @@ pub struct ClassDefinition {
     property_definitions: Vec<PropertyDefinition>,
     function_definitions: HashMap<String, ClassFunction>,
     typeid: usize,
+    parent: Option<usize>,
+    interfaces: Vec<usize>,
+    is_builtin: bool,
+
+    // only set for interfaces
+    method_signatures: Option<Vec<MethodSignature>>,
 
     context: Context,
 }
@@ -87,6 +217,10 @@ impl ClassDefinition {
             property_definitions,
             function_definitions,
             typeid,
+            parent: None,
+            interfaces: Vec::new(),
+            is_builtin: false,
+            method_signatures: None,
             context,
         }
     }
@@ -100,17 +234,124 @@ impl ClassDefinition {
             property_definitions: Vec::new(),
             function_definitions,
             typeid,
+            parent: None,
+            interfaces: Vec::new(),
+            is_builtin: true,
+            method_signatures: None,
             context: Context { start: 0, end: 0 },
         }
     }
 
+    #[inline]
+    pub fn new_interface(
+        method_signatures: Vec<MethodSignature>,
+        typeid: usize,
+        context: Context,
+    ) -> ClassDefinition {
+        ClassDefinition {
+            property_definitions: Vec::new(),
+            function_definitions: HashMap::new(),
+            typeid,
+            parent: None,
+            interfaces: Vec::new(),
+            is_builtin: false,
+            method_signatures: Some(method_signatures),
+            context,
+        }
+    }
+
     #[inline]
     pub fn property_definitions(&self) -> &Vec<PropertyDefinition> {
         &self.property_definitions
     }
 
     #[inline]
-    pub fn get_function(
+    pub fn set_parent(&mut self, parent: usize) {
+        self.parent = Some(parent);
+    }
+
+    #[inline]
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    #[inline]
+    pub fn is_builtin(&self) -> bool {
+        self.is_builtin
+    }
+
+    #[inline]
+    pub fn set_interfaces(&mut self, interfaces: Vec<usize>) {
+        self.interfaces = interfaces;
+    }
+
+    #[inline]
+    pub fn interfaces(&self) -> &Vec<usize> {
+        &self.interfaces
+    }
+
+    #[inline]
+    pub fn is_interface(&self) -> bool {
+        self.method_signatures.is_some()
+    }
+
+    #[inline]
+    pub fn method_signatures(&self) -> Option<&Vec<MethodSignature>> {
+        self.method_signatures.as_ref()
+    }
+
+    #[inline]
+    pub fn get_class_function(&self, name: &str) -> Option<&ClassFunction> {
+        self.function_definitions.get(name)
+    }
+
+    #[inline]
+    pub fn class_functions(&self) -> &HashMap<String, ClassFunction> {
+        &self.function_definitions
+    }
+
+    // The function that the class or the closest of its parents defines,
+    // along with the type of the class that defines it
+    pub fn find_class_function<'a>(
+        &'a self,
+        name: &str,
+        parse_session: &'a ParseSession,
+    ) -> Option<(usize, &'a ClassFunction)> {
+        let mut current = Some(self);
+        while let Some(class_definition) = current {
+            if let Some(classfunc) = class_definition.get_class_function(name) {
+                return Some((class_definition.typeid, classfunc));
+            }
+            current = class_definition
+                .parent
+                .and_then(|id| parse_session.get_class_definition(id));
+        }
+        None
+    }
+
+    // Functions that are not defined by the class itself
+    // are looked up in its parent classes
+    pub fn get_function<'a>(
+        &'a self,
+        name: &str,
+        is_member: bool,
+        private_access: bool,
+        parse_session: &'a ParseSession,
+    ) -> Result<&'a Function, ErrorKind> {
+        match self.get_own_function(name, is_member, private_access) {
+            Err(ErrorKind::FunctionNotFound) => match self.parent {
+                Some(parent) => parse_session
+                    .get_class_definition(parent)
+                    .expect("Parent class should be defined")
+                    .get_function(name, is_member, private_access, parse_session),
+                None => Err(ErrorKind::FunctionNotFound),
+            },
+            result => result,
+        }
+    }
+
+    #[inline]
+    fn get_own_function(
         &self,
         name: &str,
         is_member: bool,
@@ -159,13 +400,18 @@ impl ClassDefinition {
 #[derive(Debug, Clone)]
 struct Property {
     var: Variable,
+    typeid: usize,
     is_public: bool,
 }
 
 impl Property {
     #[inline]
-    fn new(var: Variable, is_public: bool) -> Property {
-        Property { var, is_public }
+    fn new(var: Variable, typeid: usize, is_public: bool) -> Property {
+        Property {
+            var,
+            typeid,
+            is_public,
+        }
     }
 }
 
@@ -191,8 +437,21 @@ impl ClassInstance {
 
     #[inline]
     pub fn add_property(&mut self, name: &str, var: Variable, is_public: bool) {
+        let typeid = var.get_value().typeid();
+        self.add_typed_property(name, var, typeid, is_public);
+    }
+
+    // The property accepts values of the given type and its subtypes
+    #[inline]
+    pub fn add_typed_property(
+        &mut self,
+        name: &str,
+        var: Variable,
+        typeid: usize,
+        is_public: bool,
+    ) {
         self.properties
-            .insert(name.to_string(), Property::new(var, is_public));
+            .insert(name.to_string(), Property::new(var, typeid, is_public));
     }
 
     #[inline]
@@ -217,6 +476,30 @@ impl ClassInstance {
         }
     }
 
+    #[inline]
+    pub fn properties(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.properties
+            .iter()
+            .map(|(name, prop)| (name.as_str(), prop.var.get_value()))
+    }
+
+    #[inline]
+    pub fn property_value(&self, name: &str) -> Option<&Value> {
+        self.properties.get(name).map(|prop| prop.var.get_value())
+    }
+
+    // Sorted by name so the order does not change between runs
+    pub fn public_properties(&self) -> Vec<(&str, &Value)> {
+        let mut properties: Vec<(&str, &Value)> = self
+            .properties
+            .iter()
+            .filter(|(_, prop)| prop.is_public)
+            .map(|(name, prop)| (name.as_str(), prop.var.get_value()))
+            .collect();
+        properties.sort_by(|l, r| l.0.cmp(r.0));
+        properties
+    }
+
     #[inline]
     pub fn set_property(
         &mut self,
@@ -230,12 +513,12 @@ impl ClassInstance {
                 if !prop.is_public && !private_access {
                     return Err(ErrorKind::MemberIsPrivate(name.to_string()));
                 }
-                if prop.var.is_dynamic() || prop.var.get_value().typeid() == value.typeid() {
+                if prop.var.is_dynamic() || session.is_subtype(value.typeid(), prop.typeid) {
                     prop.var.set_value(value);
                     Ok(())
                 } else {
                     Err(ErrorKind::InvalidAssignment(
-                        session.get_typename(prop.var.get_value().typeid()),
+                        session.get_typename(prop.typeid),
                         session.get_typename(value.typeid()),
                     ))
                 }
@@ -259,21 +542,32 @@ fn class_constructor(
         _ => panic!("Invalid value in built-in function"),
     };
 
-    let class_definition = parse_session.get_class_definition(typeid).unwrap();
+    // inherited properties are initialized first
+    let mut class_definitions = Vec::new();
+    let mut current = Some(typeid);
+    while let Some(id) = current {
+        let class_definition = parse_session.get_class_definition(id).unwrap();
+        class_definitions.push(class_definition);
+        current = class_definition.parent();
+    }
+
     let mut class_instance = ClassInstance::new(typeid);
 
-    for item in class_definition.property_definitions() {
-        let value = item
-            .init_expression
-            .exec(exec_session, parse_session)?
-            .expect("Expressions should always return a value on success");
+    let property_definitions = class_definitions
+        .iter()
+        .rev()
+        .flat_map(|class_definition| class_definition.property_definitions());
+
+    for item in property_definitions {
+        let value = vm::run(&item.init_chunk(parse_session), exec_session, parse_session)?;
 
         if item.id.typeid() == TYPEID_DYN {
             class_instance.add_property(item.id.name(), Variable::new(value, true), item.is_public);
-        } else if item.id.typeid() == value.typeid() {
-            class_instance.add_property(
+        } else if parse_session.is_subtype(value.typeid(), item.id.typeid()) {
+            class_instance.add_typed_property(
                 item.id.name(),
                 Variable::new(value, false),
+                item.id.typeid(),
                 item.is_public,
             );
         } else {
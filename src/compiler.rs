@@ -0,0 +1,881 @@
+use crate::branch::BranchBody;
+use crate::builtin::TYPEID_NONE;
+use crate::error::{Context, Error, ErrorKind};
+use crate::expression::Expression;
+use crate::function::{CallArguments, Function, FunctionCall, UserFunction};
+use crate::instruction::Instruction;
+use crate::literal::{InterpolationPart, MapLiteral, StringInterpolation, VecLiteral};
+use crate::session::{ParseSession, Scope};
+use crate::token::{Operator, TokenKind};
+use crate::variable::Value;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+// Instructions and expressions are lowered into bytecode before they are
+// executed. Variables are resolved to the slots of the scope they live in
+// and functions that are not associated with a type to their index,
+// so that running the code does not require looking up names.
+// Operands are evaluated from left to right and errors are reported
+// at the positions of the instructions the code was compiled from.
+
+#[derive(Debug, Clone, Copy)]
+pub enum Op {
+    Constant(usize),
+    Pop,
+    // strings are copied when they are the result of an expression
+    CopyString,
+
+    Load(Target),
+    // the value stays on the stack so more than one variable can be set
+    Init(Target, Option<usize>),
+    InitConstant(Target, Option<usize>),
+    Assign(Target, Option<Operator>),
+    IterNext(Target, /*exit*/ usize),
+    SetProperty(/*property site*/ usize),
+    SetIndex(Option<(Operator, /*operator position*/ usize)>),
+
+    Binary(Operator),
+    Unary(Operator),
+    GetProperty(/*property site*/ usize),
+    Call(/*call site*/ usize),
+    // the callee of a call made on the result of an expression
+    CheckCallable,
+    CallValue(
+        /*number of arguments*/ usize,
+        /*line and column*/ (usize, usize),
+    ),
+
+    MakeVec(usize),
+    CheckMapKey,
+    MakeMap(usize),
+    Concat(usize),
+    Closure(/*closure site*/ usize),
+
+    Jump(usize),
+    JumpIfFalse(usize),
+    IterStart,
+    IterEnd,
+    // the iterations of each loop are counted
+    LoopStart,
+    Iteration,
+    LoopEnd,
+    Return,
+    Fail(usize),
+}
+
+// How a variable is found in the scope the code runs in
+
+#[derive(Debug, Clone, Copy)]
+pub enum Target {
+    Slot(usize),
+    // code that runs in a scope it was not compiled for,
+    // such as the initializers of properties
+    Name(usize),
+    // a variable of the global scope used by a function
+    Global(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct CallSite {
+    call: FunctionCall,
+    shadow: Option<Target>,
+    is_method: bool,
+}
+
+impl CallSite {
+    #[inline]
+    pub fn call(&self) -> &FunctionCall {
+        &self.call
+    }
+
+    // A variable with the name of the function that is called
+    // in its place if it holds a function
+    #[inline]
+    pub fn shadow(&self) -> Option<Target> {
+        self.shadow
+    }
+
+    // Whether the object the function is called on is below the arguments
+    #[inline]
+    pub fn is_method(&self) -> bool {
+        self.is_method
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClosureSite {
+    pub function: Rc<Function>,
+    // the names and slots of the variables the closure captures,
+    // None when it captures every variable in scope
+    pub captured: Option<Vec<(String, usize)>>,
+    // the variable a closure is declared with, e.g. let f = fn() { f() },
+    // so that it can refer to itself
+    pub binding: Option<(String, Target)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PropertySite {
+    pub name: String,
+    pub private_access_typeid: Option<usize>,
+    pub operator: Option<Operator>,
+    // position of the access operator
+    pub pos: usize,
+    pub id_pos: usize,
+    pub assign_pos: usize,
+}
+
+#[derive(Debug)]
+pub struct Chunk {
+    code: Vec<Op>,
+    // the context and position errors of each operation are reported at
+    spans: Vec<(Context, usize)>,
+    constants: Vec<Value>,
+    names: Vec<String>,
+    calls: Vec<CallSite>,
+    properties: Vec<PropertySite>,
+    closures: Vec<ClosureSite>,
+    failures: Vec<Error>,
+    layout: Rc<HashMap<String, usize>>,
+    // the variables of blocks have slots past the ones in the layout
+    slot_count: usize,
+    parameters: Vec<usize>,
+    return_typeid: Option<usize>,
+}
+
+impl Chunk {
+    #[inline]
+    pub fn code(&self) -> &Vec<Op> {
+        &self.code
+    }
+
+    #[inline]
+    pub fn span(&self, ip: usize) -> (Context, usize) {
+        self.spans[ip]
+    }
+
+    #[inline]
+    pub fn constant(&self, index: usize) -> &Value {
+        &self.constants[index]
+    }
+
+    #[inline]
+    pub fn name(&self, index: usize) -> &str {
+        &self.names[index]
+    }
+
+    // Only needed when a variable is not found, so the layout is searched
+    #[inline]
+    pub fn slot_name(&self, slot: usize) -> &str {
+        self.layout
+            .iter()
+            .find(|(_, s)| **s == slot)
+            .map(|(name, _)| name.as_str())
+            .unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn call_site(&self, index: usize) -> &CallSite {
+        &self.calls[index]
+    }
+
+    #[inline]
+    pub fn property_site(&self, index: usize) -> &PropertySite {
+        &self.properties[index]
+    }
+
+    #[inline]
+    pub fn closure_site(&self, index: usize) -> &ClosureSite {
+        &self.closures[index]
+    }
+
+    #[inline]
+    pub fn failure(&self, index: usize) -> &Error {
+        &self.failures[index]
+    }
+
+    // The names of the variables of a call and their slots
+    #[inline]
+    pub fn layout(&self) -> &Rc<HashMap<String, usize>> {
+        &self.layout
+    }
+
+    #[inline]
+    pub fn slot_count(&self) -> usize {
+        self.slot_count
+    }
+
+    // The slots the arguments of a call are stored in
+    #[inline]
+    pub fn parameters(&self) -> &Vec<usize> {
+        &self.parameters
+    }
+
+    #[inline]
+    pub fn return_typeid(&self) -> Option<usize> {
+        self.return_typeid
+    }
+}
+
+// The top level instructions of the input, each of them is compiled
+// into its own chunk and they share the layout of the global scope
+
+#[derive(Debug)]
+pub struct Program {
+    chunks: Vec<Chunk>,
+    layout: Rc<HashMap<String, usize>>,
+    slot_count: usize,
+}
+
+impl Program {
+    #[inline]
+    pub fn chunks(&self) -> &Vec<Chunk> {
+        &self.chunks
+    }
+
+    #[inline]
+    pub fn layout(&self) -> &Rc<HashMap<String, usize>> {
+        &self.layout
+    }
+
+    #[inline]
+    pub fn slot_count(&self) -> usize {
+        self.slot_count
+    }
+}
+
+pub fn compile_program(
+    instructions: &[Instruction],
+    global_scope: &Scope,
+    parse_session: &ParseSession,
+) -> Program {
+    let mut compiler = Compiler::new(
+        parse_session,
+        Some(global_scope.layout().as_ref().clone()),
+        None,
+    );
+    compiler.slot_count = global_scope.slot_count();
+
+    let mut chunks = Vec::with_capacity(instructions.len());
+    for item in instructions {
+        compiler.statement = item.context();
+        match item {
+            // the value of an expression is the result of the chunk
+            Instruction::Expression(expr) => compiler.expression(expr),
+            _ => {
+                compiler.instruction(item);
+                compiler.emit_constant(Value::None);
+            }
+        }
+        compiler.emit(Op::Return);
+        chunks.push(compiler.take_chunk());
+    }
+
+    let layout = Rc::new(compiler.layout.unwrap_or_default());
+    for chunk in chunks.iter_mut() {
+        chunk.layout = layout.clone();
+        chunk.slot_count = compiler.slot_count;
+    }
+    Program {
+        chunks,
+        layout,
+        slot_count: compiler.slot_count,
+    }
+}
+
+pub fn compile_function(function: &UserFunction, parse_session: &ParseSession) -> Chunk {
+    let mut compiler = Compiler::new(
+        parse_session,
+        Some(HashMap::new()),
+        Some(function.return_typeid()),
+    );
+
+    let parameters = function
+        .arguments()
+        .iter()
+        .map(|argument| compiler.slot(argument.name()))
+        .collect();
+
+    for item in function.body() {
+        compiler.statement = item.context();
+        compiler.instruction(item);
+    }
+
+    let context = function.context();
+    match function.return_typeid() {
+        Some(expected_typeid) if expected_typeid != TYPEID_NONE => {
+            let pos = function.return_type_pos().unwrap() - context.start;
+            compiler.emit_failure(Error::new(
+                context,
+                pos,
+                ErrorKind::InvalidReturnType(
+                    parse_session.get_typename(TYPEID_NONE),
+                    parse_session.get_typename(expected_typeid),
+                ),
+            ));
+        }
+        _ => {
+            compiler.emit_constant(Value::None);
+            compiler.emit(Op::Return);
+        }
+    }
+
+    let mut chunk = compiler.take_chunk();
+    chunk.layout = Rc::new(compiler.layout.unwrap_or_default());
+    chunk.slot_count = compiler.slot_count;
+    chunk.parameters = parameters;
+    chunk
+}
+
+// An expression that refers to variables by name,
+// the result of the expression is the result of the chunk
+pub fn compile_expression(expr: &Expression, parse_session: &ParseSession) -> Chunk {
+    let mut compiler = Compiler::new(parse_session, None, None);
+    compiler.statement = expr.context();
+    compiler.expression(expr);
+    compiler.emit(Op::Return);
+    compiler.take_chunk()
+}
+
+// An expression turned back from postfix notation into a tree,
+// so that its operands can be compiled in the order they appear in
+
+enum Node<'a> {
+    Value(&'a Value),
+    Identifier(&'a str, usize),
+    FunctionCall(&'a FunctionCall, usize),
+    VecLiteral(&'a VecLiteral),
+    MapLiteral(&'a MapLiteral),
+    Interpolation(&'a StringInterpolation),
+    Lambda(&'a Rc<Function>),
+    Arguments(&'a CallArguments),
+    Unary(Operator, Box<Node<'a>>, usize),
+    Binary(Operator, Box<Node<'a>>, Box<Node<'a>>, usize),
+}
+
+impl<'a> Node<'a> {
+    fn from_postfix(expr: &'a Expression) -> Node<'a> {
+        let mut stack: Vec<Node> = Vec::new();
+
+        for token in expr.tokens() {
+            let node = match token.kind() {
+                TokenKind::Value(val) => Node::Value(val),
+                TokenKind::Identifier(id) => Node::Identifier(id, token.pos()),
+                TokenKind::FunctionCall(f) => Node::FunctionCall(f, token.pos()),
+                TokenKind::VecLiteral(v) => Node::VecLiteral(v),
+                TokenKind::MapLiteral(m) => Node::MapLiteral(m),
+                TokenKind::Interpolation(s) => Node::Interpolation(s),
+                TokenKind::Lambda(f) => Node::Lambda(f),
+                TokenKind::Arguments(a) => Node::Arguments(a),
+                TokenKind::Operator(op) if op.is_unary() => {
+                    let operand = stack.pop().unwrap();
+                    Node::Unary(*op, Box::new(operand), token.pos())
+                }
+                TokenKind::Operator(op) => {
+                    let rhs = stack.pop().unwrap();
+                    let lhs = stack.pop().unwrap();
+                    Node::Binary(*op, Box::new(lhs), Box::new(rhs), token.pos())
+                }
+                TokenKind::Parenthesis(_) => unreachable!(),
+            };
+            stack.push(node);
+        }
+
+        stack.pop().unwrap()
+    }
+}
+
+// Jumps of the loop that is being compiled
+struct Loop {
+    start: usize,
+    breaks: Vec<usize>,
+}
+
+struct Compiler<'a> {
+    parse_session: &'a ParseSession,
+    chunk: Chunk,
+    // None when variables are looked up by name
+    layout: Option<HashMap<String, usize>>,
+    slot_count: usize,
+    // the variables declared in the blocks that are being compiled,
+    // they hide the ones with the same name outside of them
+    blocks: Vec<HashMap<String, usize>>,
+    // the names declared global in the function
+    globals: HashSet<String>,
+    // None for top level code, otherwise the return type of the function
+    function: Option<Option<usize>>,
+    loops: Vec<Loop>,
+    // the variable that the next anonymous function is assigned to
+    binding: Option<(String, Target)>,
+    // the top level instruction or the instruction of
+    // the function body that is being compiled
+    statement: Context,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(
+        parse_session: &'a ParseSession,
+        layout: Option<HashMap<String, usize>>,
+        function: Option<Option<usize>>,
+    ) -> Self {
+        Compiler {
+            parse_session,
+            chunk: Compiler::empty_chunk(function.flatten()),
+            layout,
+            slot_count: 0,
+            blocks: Vec::new(),
+            globals: HashSet::new(),
+            function,
+            loops: Vec::new(),
+            binding: None,
+            statement: Context { start: 0, end: 0 },
+        }
+    }
+
+    #[inline]
+    fn empty_chunk(return_typeid: Option<usize>) -> Chunk {
+        Chunk {
+            code: Vec::new(),
+            spans: Vec::new(),
+            constants: Vec::new(),
+            names: Vec::new(),
+            calls: Vec::new(),
+            properties: Vec::new(),
+            closures: Vec::new(),
+            failures: Vec::new(),
+            layout: Rc::new(HashMap::new()),
+            slot_count: 0,
+            parameters: Vec::new(),
+            return_typeid,
+        }
+    }
+
+    #[inline]
+    fn take_chunk(&mut self) -> Chunk {
+        let empty = Compiler::empty_chunk(self.function.flatten());
+        std::mem::replace(&mut self.chunk, empty)
+    }
+
+    #[inline]
+    fn emit(&mut self, op: Op) -> usize {
+        self.emit_at(op, Context { start: 0, end: 0 }, 0)
+    }
+
+    #[inline]
+    fn emit_at(&mut self, op: Op, context: Context, pos: usize) -> usize {
+        self.chunk.code.push(op);
+        self.chunk.spans.push((context, pos));
+        self.chunk.code.len() - 1
+    }
+
+    #[inline]
+    fn emit_constant(&mut self, value: Value) {
+        self.chunk.constants.push(value);
+        self.emit(Op::Constant(self.chunk.constants.len() - 1));
+    }
+
+    #[inline]
+    fn emit_failure(&mut self, error: Error) {
+        self.chunk.failures.push(error);
+        self.emit(Op::Fail(self.chunk.failures.len() - 1));
+    }
+
+    // Point a jump that was already emitted to the next operation
+    #[inline]
+    fn patch(&mut self, jump: usize) {
+        let target = self.chunk.code.len();
+        match &mut self.chunk.code[jump] {
+            Op::Jump(t) | Op::JumpIfFalse(t) | Op::IterNext(_, t) => *t = target,
+            _ => unreachable!("Only jumps can be patched"),
+        }
+    }
+
+    #[inline]
+    fn slot(&mut self, name: &str) -> usize {
+        let layout = self
+            .layout
+            .as_mut()
+            .expect("Slots are only used when the layout is known");
+        let next = self.slot_count;
+        let slot = *layout.entry(name.to_string()).or_insert(next);
+        if slot == next {
+            self.slot_count += 1;
+        }
+        slot
+    }
+
+    // The variable that the name refers to in the innermost block it is declared in
+    #[inline]
+    fn target(&mut self, name: &str) -> Target {
+        if self.layout.is_some() {
+            match self.blocks.iter().rev().find_map(|block| block.get(name)) {
+                Some(slot) => Target::Slot(*slot),
+                None if self.globals.contains(name) => Target::Global(self.name(name)),
+                None => Target::Slot(self.slot(name)),
+            }
+        } else {
+            Target::Name(self.name(name))
+        }
+    }
+
+    #[inline]
+    fn name(&mut self, name: &str) -> usize {
+        self.chunk.names.push(name.to_string());
+        self.chunk.names.len() - 1
+    }
+
+    // A new variable in the innermost block, it is
+    // declared again if the block already has one
+    #[inline]
+    fn declare(&mut self, name: &str) -> Target {
+        if self.layout.is_none() || self.blocks.is_empty() {
+            return self.target(name);
+        }
+        let next = self.slot_count;
+        let block = self.blocks.last_mut().unwrap();
+        let slot = *block.entry(name.to_string()).or_insert(next);
+        if slot == next {
+            self.slot_count += 1;
+        }
+        Target::Slot(slot)
+    }
+
+    fn block(&mut self, body: &[Instruction]) {
+        self.blocks.push(HashMap::new());
+        self.body(body);
+        self.blocks.pop();
+    }
+
+    // The variables a closure can see, None when it captures the whole scope
+    fn visible_variables(&self) -> Option<Vec<(String, usize)>> {
+        if self.blocks.is_empty() {
+            return None;
+        }
+        let mut visible = self.layout.clone().unwrap_or_default();
+        for block in &self.blocks {
+            visible.extend(block.iter().map(|(name, slot)| (name.clone(), *slot)));
+        }
+        Some(visible.into_iter().collect())
+    }
+
+    fn body(&mut self, body: &[Instruction]) {
+        for item in body {
+            self.instruction(item);
+        }
+    }
+
+    fn instruction(&mut self, item: &Instruction) {
+        match item {
+            Instruction::Expression(expr) => {
+                self.expression(expr);
+                self.emit(Op::Pop);
+            }
+            Instruction::Branch(b) => {
+                let mut exits = Vec::new();
+                let branches = std::iter::once(b.main_branch()).chain(b.else_if_branches());
+                for branch in branches {
+                    exits.push(self.branch_body(branch, b.context()));
+                }
+                self.block(b.else_branch());
+                for jump in exits {
+                    self.patch(jump);
+                }
+            }
+            Instruction::WhileLoop(wl) => {
+                self.emit(Op::LoopStart);
+                let start = self.chunk.code.len();
+                self.expression(wl.condition());
+                let exit = self.emit_at(Op::JumpIfFalse(0), wl.context(), 0);
+                self.emit_at(Op::Iteration, wl.context(), 0);
+                self.start_loop(start);
+                self.block(wl.body());
+                self.emit(Op::Jump(start));
+                self.patch(exit);
+                self.end_loop();
+            }
+            Instruction::ForLoop(fl) => {
+                self.expression(fl.operand());
+                self.emit_at(Op::IterStart, fl.context(), fl.operand_pos());
+                self.emit(Op::LoopStart);
+                let start = self.chunk.code.len();
+                // the loop variable belongs to the block of the body
+                self.blocks.push(HashMap::new());
+                let alias = self.declare(fl.alias());
+                let exit = self.emit(Op::IterNext(alias, 0));
+                self.emit_at(Op::Iteration, fl.context(), 0);
+                self.start_loop(start);
+                self.body(fl.body());
+                self.blocks.pop();
+                self.emit(Op::Jump(start));
+                self.patch(exit);
+                self.end_loop();
+                self.emit(Op::IterEnd);
+            }
+            Instruction::Return(r) => {
+                let pos = match r.expr() {
+                    Some(expr) => {
+                        self.expression(expr);
+                        expr.context().start
+                    }
+                    None => {
+                        self.emit_constant(Value::None);
+                        0
+                    }
+                };
+                let statement = self.statement;
+                self.emit_at(Op::Return, statement, pos.saturating_sub(statement.start));
+            }
+            Instruction::Break(_) => match self.loops.last() {
+                Some(_) => {
+                    let jump = self.emit(Op::Jump(0));
+                    self.loops.last_mut().unwrap().breaks.push(jump);
+                }
+                None => self.outside_loop("break"),
+            },
+            Instruction::Continue(_) => match self.loops.last() {
+                Some(l) => {
+                    let start = l.start;
+                    self.emit(Op::Jump(start));
+                }
+                None => self.outside_loop("continue"),
+            },
+            Instruction::GlobalDeclaration(gd) => {
+                // at the top level the names are global already
+                if self.function.is_some() {
+                    self.globals.extend(gd.names().iter().cloned());
+                }
+            }
+            Instruction::VariableInit(vi) | Instruction::ConstantInit(vi) => {
+                // an anonymous function can refer to the variable it is assigned to
+                let binding = match vi.identifiers().as_slice() {
+                    [id] if vi.expr().is_lambda() => Some(self.declare(id.name())),
+                    _ => None,
+                };
+                self.binding =
+                    binding.map(|target| (vi.identifiers()[0].name().to_string(), target));
+                self.expression(vi.expr());
+                let is_constant = matches!(item, Instruction::ConstantInit(_));
+                for id in vi.identifiers() {
+                    let target = match binding {
+                        Some(target) => target,
+                        None => self.declare(id.name()),
+                    };
+                    let op = if is_constant {
+                        Op::InitConstant(target, id.typeid())
+                    } else {
+                        Op::Init(target, id.typeid())
+                    };
+                    self.emit_at(op, vi.context(), vi.assign_pos());
+                }
+                self.emit(Op::Pop);
+            }
+            Instruction::VariableAssign(va) => {
+                self.expression(va.expr());
+                match va.source_expr() {
+                    Some(source) => {
+                        self.expression(source);
+                        self.chunk.properties.push(PropertySite {
+                            name: va.identifier().to_string(),
+                            private_access_typeid: va.private_access_typeid(),
+                            operator: va.compound_operator(),
+                            pos: va.source_pos().unwrap(),
+                            id_pos: va.id_pos(),
+                            assign_pos: va.assign_pos(),
+                        });
+                        let site = self.chunk.properties.len() - 1;
+                        self.emit_at(Op::SetProperty(site), va.context(), va.assign_pos());
+                    }
+                    None => {
+                        let target = self.target(va.identifier());
+                        self.emit_at(
+                            Op::Assign(target, va.compound_operator()),
+                            va.context(),
+                            va.assign_pos(),
+                        );
+                    }
+                }
+            }
+            Instruction::IndexAssign(ia) => {
+                self.expression(ia.expr());
+                self.expression(ia.container());
+                self.expression(ia.index());
+                self.emit_at(
+                    Op::SetIndex(ia.compound_operator()),
+                    ia.context(),
+                    ia.index_pos(),
+                );
+            }
+        }
+    }
+
+    // Returns the jump past the other branches that follows the body
+    fn branch_body(&mut self, branch: &BranchBody, context: Context) -> usize {
+        self.expression(branch.condition());
+        let next = self.emit_at(Op::JumpIfFalse(0), context, 0);
+        self.block(branch.body());
+        let exit = self.emit(Op::Jump(0));
+        self.patch(next);
+        exit
+    }
+
+    fn start_loop(&mut self, start: usize) {
+        self.loops.push(Loop {
+            start,
+            breaks: Vec::new(),
+        });
+    }
+
+    fn end_loop(&mut self) {
+        let l = self.loops.pop().unwrap();
+        for jump in l.breaks {
+            self.patch(jump);
+        }
+        self.emit(Op::LoopEnd);
+    }
+
+    // A break or continue that is not in a loop ends the top level
+    // instruction it is in, in a function it is an error
+    fn outside_loop(&mut self, keyword: &str) {
+        if self.function.is_some() {
+            let kind = ErrorKind::OutsideLoop(keyword.to_string());
+            self.emit_failure(Error::new(self.statement, 0, kind));
+        } else {
+            self.emit_constant(Value::None);
+            self.emit(Op::Return);
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        let node = Node::from_postfix(expr);
+        self.node(&node, expr);
+        self.emit(Op::CopyString);
+    }
+
+    fn node(&mut self, node: &Node, expr: &Expression) {
+        let context = expr.context();
+        match node {
+            Node::Value(val) => self.emit_constant((*val).clone()),
+            Node::Identifier(id, pos) => {
+                let target = self.target(id);
+                self.emit_at(Op::Load(target), context, *pos);
+            }
+            Node::FunctionCall(f, pos) => self.call(f, context, *pos, false),
+            Node::VecLiteral(v) => {
+                for item in v.items() {
+                    self.expression(item);
+                }
+                self.emit(Op::MakeVec(v.items().len()));
+            }
+            Node::MapLiteral(m) => {
+                for (key, value) in m.entries() {
+                    self.expression(key);
+                    self.emit_at(Op::CheckMapKey, key.context(), 0);
+                    self.expression(value);
+                }
+                self.emit(Op::MakeMap(m.entries().len()));
+            }
+            Node::Interpolation(s) => {
+                for part in s.parts() {
+                    match part {
+                        InterpolationPart::Text(text) => {
+                            self.emit_constant(Value::new_string(text.clone()))
+                        }
+                        InterpolationPart::Expression(e) => self.expression(e),
+                    }
+                }
+                self.emit(Op::Concat(s.parts().len()));
+            }
+            Node::Lambda(f) => {
+                self.chunk.closures.push(ClosureSite {
+                    function: Rc::clone(f),
+                    captured: self.visible_variables(),
+                    binding: self.binding.take(),
+                });
+                self.emit(Op::Closure(self.chunk.closures.len() - 1));
+            }
+            Node::Arguments(_) => unreachable!("Call arguments are not a value"),
+            Node::Unary(op, operand, pos) => {
+                self.node(operand, expr);
+                self.emit_at(Op::Unary(*op), context, *pos);
+            }
+            Node::Binary(Operator::Dot, lhs, rhs, pos) => {
+                self.node(lhs, expr);
+                match rhs.as_ref() {
+                    Node::Identifier(name, idpos) => {
+                        self.chunk.properties.push(PropertySite {
+                            name: name.to_string(),
+                            private_access_typeid: expr.private_access_typeid(),
+                            operator: None,
+                            pos: *pos,
+                            id_pos: *idpos,
+                            assign_pos: 0,
+                        });
+                        let site = self.chunk.properties.len() - 1;
+                        self.emit_at(Op::GetProperty(site), context, *pos);
+                    }
+                    Node::FunctionCall(f, _) => match f.scope_res_pos() {
+                        Some(scope_res_pos) => self.emit_failure(Error::new(
+                            f.context(),
+                            scope_res_pos,
+                            ErrorKind::InvalidScopeAccess,
+                        )),
+                        None => self.call(f, context, f.context().start - context.start, true),
+                    },
+                    _ => {
+                        self.node(rhs, expr);
+                        self.emit_failure(Error::new(
+                            context,
+                            *pos,
+                            ErrorKind::InvalidMemberAccess,
+                        ));
+                    }
+                }
+            }
+            Node::Binary(Operator::Call, callee, arguments, pos) => {
+                self.node(callee, expr);
+                self.emit_at(Op::CheckCallable, context, *pos);
+                let arguments = match arguments.as_ref() {
+                    Node::Arguments(arguments) => arguments,
+                    _ => unreachable!("The call operator should be followed by its arguments"),
+                };
+                for argument in arguments.arguments() {
+                    self.expression(argument);
+                }
+                self.emit_at(
+                    Op::CallValue(arguments.arguments().len(), arguments.line_col()),
+                    context,
+                    *pos,
+                );
+            }
+            Node::Binary(op, lhs, rhs, pos) => {
+                self.node(lhs, expr);
+                self.node(rhs, expr);
+                self.emit_at(Op::Binary(*op), context, *pos);
+            }
+        }
+    }
+
+    // The arguments of the call are evaluated before it is made,
+    // the object of a method call is already on the stack
+    fn call(&mut self, f: &FunctionCall, context: Context, pos: usize, is_method: bool) {
+        for argument in f.arguments() {
+            self.expression(argument);
+        }
+
+        let mut call = f.clone();
+        let mut shadow = None;
+        if !is_method && f.associated_typeid().is_none() {
+            let name = f.qualified_name().unwrap_or(f.name());
+            if let Some(index) = self.parse_session.function_index(name) {
+                call.set_function_index(index);
+            }
+            shadow = Some(self.target(f.name()));
+        }
+
+        self.chunk.calls.push(CallSite {
+            call,
+            shadow,
+            is_method,
+        });
+        self.emit_at(Op::Call(self.chunk.calls.len() - 1), context, pos);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::parser;
-use crate::session::BacktraceItem;
+use crate::session::{BacktraceItem, SourceFile};
 use crate::token::Operator;
 
 #[derive(Debug, Clone)]
@@ -8,6 +8,7 @@ pub enum ErrorKind {
 
     // General errors
     SyntaxError,
+    UnexpectedToken(Box<SyntaxErrorDetails>),
     ZeroDivision,
     FunctionNotFound,
     FunctionAlreadyDefined,
@@ -16,17 +17,32 @@ pub enum ErrorKind {
     IdentifierIsTypename,
     InvalidNumberOfArguments,
     IterationLimitReached,
+    CallDepthLimitReached,
+    StepLimitReached,
+    TimeLimitReached,
+    Interrupted,
     IndexOutOfRange(isize, usize),
+    KeyNotFound(String),
+    InvalidEscapeSequence(String),
+    ImportFailed(String, String),
+    CircularImport(String),
+    VariableInImport,
+    OutsideLoop(String),
+    // raised by 'exit' to stop the script that is running
+    Exit(i32),
 
     // Type related errors
     UnknownType(String),
     InvalidOperationForType(Operator, String),
     InvalidOperationForTypes(Operator, String, String),
+    NotCallable(String),
     ConditionalExpressionNotBool(String),
     InvalidAssignment(String, String),
+    ConstantAssignment(String),
     InvalidArgumentType(String, String),
     InvalidReturnType(String, String),
     ForLoopNotVec(String),
+    InvalidKeyType(String),
     MissingAnnotation,
 
     // Class related errors
@@ -36,6 +52,13 @@ pub enum ErrorKind {
     SelfOutsideMethod,
     MemberAlreadyDefined,
     RecursiveType,
+    InvalidParentClass(String),
+    InvalidSuper,
+    MissingParentParameter(String, String, String),
+    OverrideMismatch(String, String),
+    NotAnInterface(String),
+    InterfaceNotImplemented(String, String),
+    InterfaceMethodMismatch(String, String),
     MemberIsPrivate(String),
     MemberFunctionIsPrivate(String),
 }
@@ -47,6 +70,7 @@ impl std::fmt::Display for ErrorKind {
 
             // General errors
             ErrorKind::SyntaxError => write!(f, "Syntax error"),
+            ErrorKind::UnexpectedToken(details) => write!(f, "{details}"),
             ErrorKind::ZeroDivision => write!(f, "Division by zero"),
             ErrorKind::FunctionNotFound => write!(f, "Function not found"),
             ErrorKind::FunctionAlreadyDefined => write!(f, "Function is already defined"),
@@ -57,9 +81,27 @@ impl std::fmt::Display for ErrorKind {
                 write!(f, "Invalid number of arguments passed to function")
             }
             ErrorKind::IterationLimitReached => write!(f, "Maximum iteration count reached"),
+            ErrorKind::CallDepthLimitReached => write!(f, "Maximum call depth reached"),
+            ErrorKind::StepLimitReached => write!(f, "Maximum number of steps reached"),
+            ErrorKind::TimeLimitReached => write!(f, "Time limit reached"),
+            ErrorKind::Interrupted => write!(f, "Execution was interrupted"),
             ErrorKind::IndexOutOfRange(i, s) => {
                 write!(f, "Index '{}' is out of range for size '{}'", i, s)
             }
+            ErrorKind::KeyNotFound(key) => write!(f, "Key '{}' not found", key),
+            ErrorKind::InvalidEscapeSequence(s) => write!(f, "Invalid escape sequence '{}'", s),
+            ErrorKind::ImportFailed(path, reason) => {
+                write!(f, "Cannot import '{}': {}", path, reason)
+            }
+            ErrorKind::CircularImport(path) => write!(f, "Circular import of '{}'", path),
+            ErrorKind::VariableInImport => write!(
+                f,
+                "Variables cannot be declared at the top level of an imported file"
+            ),
+            ErrorKind::OutsideLoop(keyword) => {
+                write!(f, "'{}' can only be used inside of a loop", keyword)
+            }
+            ErrorKind::Exit(code) => write!(f, "Exited with code '{}'", code),
 
             // Type related errors
             ErrorKind::UnknownType(t) => {
@@ -75,6 +117,7 @@ impl std::fmt::Display for ErrorKind {
                     op, t1, t2
                 )
             }
+            ErrorKind::NotCallable(t) => write!(f, "Value of type '{}' cannot be called", t),
             ErrorKind::ConditionalExpressionNotBool(t) => {
                 write!(
                     f,
@@ -89,6 +132,9 @@ impl std::fmt::Display for ErrorKind {
                     t1, t2
                 )
             }
+            ErrorKind::ConstantAssignment(name) => {
+                write!(f, "Cannot assign to constant '{}'", name)
+            }
             ErrorKind::InvalidArgumentType(t1, t2) => {
                 write!(f, "Invalid argument type '{}', expected '{}'", t1, t2)
             }
@@ -102,11 +148,17 @@ impl std::fmt::Display for ErrorKind {
             ErrorKind::ForLoopNotVec(t) => {
                 write!(
                     f,
-                    "For loop operand is of type '{}' but it must be of type 'Vec'",
+                    "For loop operand is of type '{}' but it must be of type 'Vec', 'Map' or 'FileHandle'",
                     t
                 )
             }
-            ErrorKind::MissingAnnotation => write!(f, "Missing type annotation"),
+            ErrorKind::InvalidKeyType(t) => {
+                write!(f, "Type '{}' cannot be used as a map key", t)
+            }
+            ErrorKind::MissingAnnotation => write!(
+                f,
+                "Missing type annotation\nHint: parameters have to declare their type, for example 'x: int'"
+            ),
 
             // Class related errors
             ErrorKind::HasNoMember(t, name) => {
@@ -120,6 +172,32 @@ impl std::fmt::Display for ErrorKind {
             ErrorKind::MemberAlreadyDefined => {
                 write!(f, "A member with this name is already defined")
             }
+            ErrorKind::InvalidParentClass(t) => write!(f, "Cannot inherit from type '{}'", t),
+            ErrorKind::InvalidSuper => {
+                write!(
+                    f,
+                    "'super' can only be used to call a method of the parent class"
+                )
+            }
+            ErrorKind::MissingParentParameter(c, name, t) => write!(
+                f,
+                "The constructor has to take the parameter '{}: {}' of parent class '{}'",
+                name, t, c
+            ),
+            ErrorKind::OverrideMismatch(c, m) => write!(
+                f,
+                "Method '{}' does not match the method of class '{}' that it overrides",
+                m, c
+            ),
+            ErrorKind::NotAnInterface(t) => write!(f, "Type '{}' is not an interface", t),
+            ErrorKind::InterfaceNotImplemented(i, m) => {
+                write!(f, "Method '{}' of interface '{}' is not implemented", m, i)
+            }
+            ErrorKind::InterfaceMethodMismatch(i, m) => write!(
+                f,
+                "Method '{}' does not match its signature in interface '{}'",
+                m, i
+            ),
             ErrorKind::RecursiveType => {
                 write!(f, "Recursive types are not allowed during initialization")
             }
@@ -133,6 +211,35 @@ impl std::fmt::Display for ErrorKind {
     }
 }
 
+// Describes input that does not match the grammar
+#[derive(Debug, Clone)]
+pub struct SyntaxErrorDetails {
+    // what could have come at the position of the error
+    pub expected: Vec<String>,
+    // the text at the position of the error
+    pub found: String,
+    // what was being parsed, such as a while loop
+    pub construct: Option<String>,
+    pub hint: Option<String>,
+}
+
+impl std::fmt::Display for SyntaxErrorDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Syntax error")?;
+        if !self.expected.is_empty() {
+            write!(f, ", expected {}", join_alternatives(&self.expected))?;
+        }
+        if let Some(ref construct) = self.construct {
+            write!(f, " in {}", construct)?;
+        }
+        write!(f, ", found {}", self.found)?;
+        if let Some(ref hint) = self.hint {
+            write!(f, "\nHint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Context {
     pub start: usize,
@@ -151,33 +258,174 @@ impl Error {
         Error { context, pos, kind }
     }
 
+    #[inline]
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    // Position of the error in the source code
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.context.start + self.pos
+    }
+
     pub fn print_to_string(&self, source_code: &str, backtrace: &Vec<BacktraceItem>) -> String {
+        self.print_to_string_with_files(source_code, &[], backtrace)
+    }
+
+    // Errors that occur in imported files are reported
+    // with the name of the file and the line within it
+    pub fn print_to_string_with_files(
+        &self,
+        source_code: &str,
+        files: &[SourceFile],
+        backtrace: &Vec<BacktraceItem>,
+    ) -> String {
         match self.kind {
-            ErrorKind::IterationLimitReached => {
+            ErrorKind::IterationLimitReached
+            | ErrorKind::CallDepthLimitReached
+            | ErrorKind::StepLimitReached
+            | ErrorKind::TimeLimitReached
+            | ErrorKind::Interrupted => {
                 format!("Error: {}", self.kind)
             }
             _ => {
+                let (text, context, file_name) = self.locate(source_code, files);
                 let mut result = String::new();
-                let padding = source_code.lines().count().to_string().len();
+                let padding = text.lines().count().to_string().len();
 
                 if !backtrace.is_empty() {
                     result += print_backtrace_to_string(backtrace, padding).as_str();
                 }
 
-                result += print_to_string_with_marker(source_code, self.context, self.pos, padding)
+                result += print_to_string_with_marker(text, context, self.pos, padding, file_name)
                     .as_ref();
                 result += format!("\nError: {}", self.kind).as_ref();
                 result
             }
         }
     }
+
+    // Find the text the error is in along with
+    // the context relative to the start of that text
+    fn locate<'a>(
+        &self,
+        source_code: &'a str,
+        files: &'a [SourceFile],
+    ) -> (&'a str, Context, Option<&'a str>) {
+        let file = files
+            .iter()
+            .find(|file| file.start <= self.context.start && self.context.start < file.end);
+
+        match file {
+            Some(file) => (
+                &source_code[file.start..file.end],
+                Context {
+                    start: self.context.start - file.start,
+                    end: self.context.end - file.start,
+                },
+                Some(file.name.as_str()),
+            ),
+            None => (source_code, self.context, None),
+        }
+    }
+}
+
+// Lists the items as "a, b or c"
+fn join_alternatives(items: &[String]) -> String {
+    match items.split_last() {
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+// Error returned to applications that embed the interpreter,
+// it owns everything needed to report the error after the fact
+
+#[derive(Debug, Clone)]
+pub struct DustError {
+    kind: Box<ErrorKind>,
+    file: Option<String>,
+    line: usize,
+    column: usize,
+    message: String,
+    backtrace: Vec<BacktraceItem>,
+}
+
+impl DustError {
+    pub fn new(
+        error: Error,
+        source_code: &str,
+        files: &[SourceFile],
+        backtrace: Vec<BacktraceItem>,
+    ) -> DustError {
+        let (text, context, file) = error.locate(source_code, files);
+        let (line, column) = parser::get_line_column(context.start + error.pos, text);
+        let file = file.map(|name| name.to_string());
+        let mut message = error.print_to_string_with_files(source_code, files, &backtrace);
+        if message.ends_with('\n') {
+            message.pop();
+        }
+
+        DustError {
+            kind: Box::new(error.kind),
+            file,
+            line,
+            column,
+            message,
+            backtrace,
+        }
+    }
+
+    // The imported file the error occurred in, none for the main script
+    #[inline]
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    #[inline]
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    #[inline]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    #[inline]
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    // The error rendered the same way the interpreter prints it,
+    // including the source code excerpt and the backtrace
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    #[inline]
+    pub fn backtrace(&self) -> &[BacktraceItem] {
+        &self.backtrace
+    }
 }
 
+impl std::fmt::Display for DustError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for DustError {}
+
 fn print_to_string_with_marker(
     text: &str,
     mut context: Context,
     mut pos: usize,
     padding: usize,
+    file_name: Option<&str>,
 ) -> String {
     let mut result = String::new();
     let padding = padding + 1;
@@ -219,7 +467,12 @@ fn print_to_string_with_marker(
     let line_count_to_line_with_marker = line_count_to_line_with_marker - 1;
 
     let line_count_total = line_count_to_context_start + line_count_to_line_with_marker;
-    result.push_str(format!("In line {}:\n\n", line_count_total).as_str());
+    match file_name {
+        Some(name) => {
+            result.push_str(format!("In file '{}', line {}:\n\n", name, line_count_total).as_str())
+        }
+        None => result.push_str(format!("In line {}:\n\n", line_count_total).as_str()),
+    }
 
     // print all the lines that are between the start of the context
     // and the beginning of the line that contains the position of the marker
@@ -1,9 +1,5 @@
 use crate::error::Context;
-use crate::instruction::{self, ReturnValue};
-use crate::operation::{self, Operand, OperationArgs};
-use crate::session::{ExecSession, ParseSession};
-use crate::token::{Operator, Parenthesis, Token, TokenKind};
-use crate::variable::Value;
+use crate::token::{Parenthesis, Token, TokenKind};
 
 #[derive(Debug, Clone)]
 pub struct Expression {
@@ -24,57 +20,17 @@ impl Expression {
         self.context
     }
 
-    pub fn exec(
-        &self,
-        exec_session: &mut ExecSession,
-        parse_session: &ParseSession,
-    ) -> instruction::Result {
-        let mut stack: Vec<Operand> = Vec::new();
-
-        let mut args = OperationArgs {
-            stack: &mut stack,
-            exec_session,
-            parse_session,
-            private_access_typeid: self.private_access_typeid,
-            context: self.context,
-        };
-
-        for token in &self.tokens {
-            match token.kind() {
-                TokenKind::Value(val) => args.stack.push(Operand::Value(val.clone())),
-                TokenKind::Operator(op) => match op {
-                    Operator::Add => operation::add(&mut args, token.pos())?,
-                    Operator::Sub => operation::subtract(&mut args, token.pos())?,
-                    Operator::Mult => operation::multiply(&mut args, token.pos())?,
-                    Operator::Div => operation::divide(&mut args, token.pos())?,
-                    Operator::Mod => operation::modulo(&mut args, token.pos())?,
-                    Operator::Pow => operation::power(&mut args, token.pos())?,
-                    Operator::And => operation::and(&mut args, token.pos())?,
-                    Operator::Or => operation::or(&mut args, token.pos())?,
-                    Operator::LessThan => operation::less_than(&mut args, token.pos())?,
-                    Operator::GreaterThan => operation::greater_than(&mut args, token.pos())?,
-                    Operator::Equal => operation::equal(&mut args, token.pos())?,
-                    Operator::NotEqual => operation::not_equal(&mut args, token.pos())?,
-                    Operator::Neg => operation::negate(&mut args, token.pos())?,
-                    Operator::Not => operation::not(&mut args, token.pos())?,
-                    Operator::Dot => operation::member_access(&mut args, token.pos())?,
-                    Operator::Typeof => operation::gettype(&mut args)?,
-                },
-                TokenKind::Identifier(id) => args.stack.push(Operand::Identifier(&id, token.pos())),
-                TokenKind::FunctionCall(f) => {
-                    args.stack.push(Operand::FunctionCall(&f, token.pos()))
-                }
-                _ => unreachable!(),
-            }
-        }
+    // Member access in the expression may reach the private
+    // members of this type and its subtypes
+    #[inline]
+    pub fn private_access_typeid(&self) -> Option<usize> {
+        self.private_access_typeid
+    }
 
-        match args.stack.pop().unwrap().get_value(&mut args) {
-            Ok(value) => match value {
-                Value::Str(s) => Ok(ReturnValue::Value(Value::new_string(s.borrow().clone()))),
-                _ => Ok(ReturnValue::Value(value)),
-            },
-            Err(e) => Err(e),
-        }
+    // An anonymous function on its own, e.g. fn(a: int) { a }
+    #[inline]
+    pub fn is_lambda(&self) -> bool {
+        matches!(self.tokens.as_slice(), [token] if matches!(token.kind(), TokenKind::Lambda(_)))
     }
 
     // Postfix Conversion Functions
@@ -88,7 +44,14 @@ impl Expression {
 
         for token in input {
             match token.kind() {
-                TokenKind::Value(_) | TokenKind::Identifier(_) | TokenKind::FunctionCall(_) => {
+                TokenKind::Value(_)
+                | TokenKind::Identifier(_)
+                | TokenKind::FunctionCall(_)
+                | TokenKind::VecLiteral(_)
+                | TokenKind::MapLiteral(_)
+                | TokenKind::Interpolation(_)
+                | TokenKind::Lambda(_)
+                | TokenKind::Arguments(_) => {
                     output.push(token);
                 }
                 TokenKind::Operator(_) => {
@@ -0,0 +1,101 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+
+// An open file, reads and writes go through a buffer.
+// A file is opened either for reading or for writing.
+
+#[derive(Debug)]
+enum Stream {
+    Reader(BufReader<File>),
+    Writer(BufWriter<File>),
+}
+
+#[derive(Debug)]
+pub struct FileHandle {
+    stream: Option<Stream>,
+}
+
+impl FileHandle {
+    // The mode is "r" to read, "w" to truncate and write or "a" to append,
+    // the file is created by "w" and "a" if it does not exist
+    pub fn open(path: &str, mode: &str) -> Result<FileHandle, String> {
+        let stream = match mode {
+            "r" => Stream::Reader(BufReader::new(File::open(path).map_err(to_message)?)),
+            "w" => Stream::Writer(BufWriter::new(File::create(path).map_err(to_message)?)),
+            "a" => Stream::Writer(BufWriter::new(
+                OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(path)
+                    .map_err(to_message)?,
+            )),
+            _ => return Err(format!("Invalid file mode '{}'", mode)),
+        };
+        Ok(FileHandle {
+            stream: Some(stream),
+        })
+    }
+
+    // None at the end of the file, the line break is not included
+    pub fn read_line(&mut self) -> Result<Option<String>, String> {
+        let reader = match &mut self.stream {
+            Some(Stream::Reader(reader)) => reader,
+            Some(Stream::Writer(_)) => return Err("File is not open for reading".to_string()),
+            None => return Err("File is closed".to_string()),
+        };
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(to_message)? == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+
+    pub fn write(&mut self, text: &str) -> Result<(), String> {
+        self.writer()?
+            .write_all(text.as_bytes())
+            .map_err(to_message)
+    }
+
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.writer()?.flush().map_err(to_message)
+    }
+
+    // Moves to a byte offset from the start of the file,
+    // buffered output is written out first
+    pub fn seek(&mut self, position: u64) -> Result<u64, String> {
+        let result = match &mut self.stream {
+            Some(Stream::Reader(reader)) => reader.seek(SeekFrom::Start(position)),
+            Some(Stream::Writer(writer)) => writer.seek(SeekFrom::Start(position)),
+            None => return Err("File is closed".to_string()),
+        };
+        result.map_err(to_message)
+    }
+
+    // Closing a file that is already closed does nothing
+    pub fn close(&mut self) -> Result<(), String> {
+        match self.stream.take() {
+            Some(Stream::Writer(mut writer)) => writer.flush().map_err(to_message),
+            _ => Ok(()),
+        }
+    }
+
+    #[inline]
+    fn writer(&mut self) -> Result<&mut BufWriter<File>, String> {
+        match &mut self.stream {
+            Some(Stream::Writer(writer)) => Ok(writer),
+            Some(Stream::Reader(_)) => Err("File is not open for writing".to_string()),
+            None => Err("File is closed".to_string()),
+        }
+    }
+}
+
+#[inline]
+fn to_message(e: io::Error) -> String {
+    e.to_string()
+}
@@ -1,8 +1,6 @@
-use crate::error::{Context, Error, ErrorKind};
+use crate::error::Context;
 use crate::expression::Expression;
-use crate::instruction::{Instruction, ReturnValue};
-use crate::session::{ExecSession, ParseSession};
-use crate::variable::{Value, Variable};
+use crate::instruction::Instruction;
 
 #[derive(Debug, Clone)]
 pub struct ForLoop {
@@ -37,50 +35,23 @@ impl ForLoop {
         self.context
     }
 
-    pub fn exec(
-        &self,
-        exec_session: &mut ExecSession,
-        parse_session: &ParseSession,
-    ) -> Result<ReturnValue, Error> {
-        let operand = match self.operand.exec(exec_session, parse_session) {
-            Ok(return_value) => {
-                let value =
-                    return_value.expect("Expressions should always return a value on success");
-                match value {
-                    Value::Vector(v) => v,
-                    _ => {
-                        return Err(Error::new(
-                            self.context,
-                            self.operand_pos,
-                            ErrorKind::ForLoopNotVec(parse_session.get_typename(value.typeid())),
-                        ));
-                    }
-                }
-            }
-            Err(e) => return Err(e),
-        };
+    #[inline]
+    pub fn alias(&self) -> &str {
+        &self.alias
+    }
 
-        'main_loop: for item in operand.borrow().iter() {
-            exec_session.add_variable(&self.alias, Variable::new(item.clone(), false));
+    #[inline]
+    pub fn operand(&self) -> &Expression {
+        &self.operand
+    }
 
-            for item in &self.body {
-                match item.exec(exec_session, parse_session) {
-                    Ok(return_value) => match return_value {
-                        ReturnValue::Return(value) => {
-                            return Ok(ReturnValue::Return(value));
-                        }
-                        ReturnValue::Break => {
-                            break 'main_loop;
-                        }
-                        ReturnValue::Value(_) => (),
-                    },
-                    Err(e) => {
-                        return Err(e);
-                    }
-                }
-            }
-        }
+    #[inline]
+    pub fn operand_pos(&self) -> usize {
+        self.operand_pos
+    }
 
-        Ok(ReturnValue::Value(Value::None))
+    #[inline]
+    pub fn body(&self) -> &Vec<Instruction> {
+        &self.body
     }
 }
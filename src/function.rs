@@ -1,10 +1,17 @@
 use crate::builtin::{TYPEID_DYN, TYPEID_NONE};
+use crate::compiler::{self, Chunk};
 use crate::error::{Context, Error, ErrorKind};
 use crate::expression::Expression;
-use crate::instruction::{self, Instruction, ReturnValue};
-use crate::session::{BacktraceItem, ExecSession, FnQueryOptions, ParseSession};
+use crate::instruction::Instruction;
+use crate::session::{
+    BacktraceItem, ExecSession, FnQueryOptions, ParseSession, Scope, SharedVariable,
+};
 use crate::variable::{AnnotatedIdentifier, Value, Variable};
+use crate::vm;
+use std::cell::OnceCell;
 use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub enum Function {
@@ -28,40 +35,101 @@ impl Function {
             Function::BuiltinFunction(_) => true,
         }
     }
+
+    // Call the function as a method of the object with arguments that
+    // were already evaluated, their types are checked by the caller
+    pub fn call_method(
+        &self,
+        object: Value,
+        arguments: Vec<Value>,
+        exec_session: &mut ExecSession,
+        parse_session: &ParseSession,
+        context: Context,
+        pos: usize,
+    ) -> Result<Value, Error> {
+        let variables = self
+            .arguments()
+            .iter()
+            .zip(arguments)
+            .map(|(parameter, value)| Variable::new(value, parameter.typeid() == TYPEID_DYN))
+            .collect();
+
+        let mut scope = self.new_scope(parse_session);
+        self.bind_arguments(&mut scope, variables, parse_session);
+        scope.insert("self", Variable::new(object, true));
+        run(self, scope, exec_session, parse_session, context, pos)
+    }
+
+    // An empty scope for a call of the function
+    #[inline]
+    fn new_scope(&self, parse_session: &ParseSession) -> Scope {
+        match self {
+            Function::UserFunction(f) => {
+                let chunk = f.chunk(parse_session);
+                Scope::new(chunk.layout().clone(), chunk.slot_count())
+            }
+            Function::BuiltinFunction(_) => Scope::default(),
+        }
+    }
+
+    // The arguments of user functions are stored in the
+    // slots of their parameters, built-in ones look them up by name
+    #[inline]
+    fn bind_arguments(
+        &self,
+        scope: &mut Scope,
+        arguments: Vec<Variable>,
+        parse_session: &ParseSession,
+    ) {
+        match self {
+            Function::UserFunction(f) => {
+                let chunk = f.chunk(parse_session);
+                for (slot, var) in chunk.parameters().iter().zip(arguments) {
+                    scope.set_slot(*slot, var);
+                }
+            }
+            Function::BuiltinFunction(f) => {
+                for (parameter, var) in f.arguments().iter().zip(arguments) {
+                    scope.insert(parameter.name(), var);
+                }
+            }
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+pub type BuiltinBody =
+    Rc<dyn Fn(&mut ExecSession, &ParseSession, Context, usize) -> Result<Value, Error>>;
+
+#[derive(Clone)]
 pub struct BuiltinFunction {
     arguments: Vec<AnnotatedIdentifier>,
-    body: fn(&mut ExecSession, &ParseSession, Context, usize) -> Result<Value, Error>,
+    body: BuiltinBody,
+}
+
+impl fmt::Debug for BuiltinFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BuiltinFunction")
+            .field("arguments", &self.arguments)
+            .finish_non_exhaustive()
+    }
 }
 
 impl BuiltinFunction {
     #[inline]
-    pub fn new(
-        arguments: Vec<AnnotatedIdentifier>,
-        body: fn(&mut ExecSession, &ParseSession, Context, usize) -> Result<Value, Error>,
-    ) -> Self {
-        BuiltinFunction { arguments, body }
+    pub fn new<F>(arguments: Vec<AnnotatedIdentifier>, body: F) -> Self
+    where
+        F: Fn(&mut ExecSession, &ParseSession, Context, usize) -> Result<Value, Error> + 'static,
+    {
+        BuiltinFunction {
+            arguments,
+            body: Rc::new(body),
+        }
     }
 
     #[inline]
     pub fn arguments(&self) -> &Vec<AnnotatedIdentifier> {
         &self.arguments
     }
-
-    pub fn exec(
-        &self,
-        exec_session: &mut ExecSession,
-        parse_session: &ParseSession,
-        context: Context,
-        pos: usize,
-    ) -> instruction::Result {
-        match (self.body)(exec_session, parse_session, context, pos) {
-            Ok(v) => Ok(ReturnValue::Value(v)),
-            Err(e) => Err(e),
-        }
-    }
 }
 
 #[derive(Debug, Clone)]
@@ -69,6 +137,8 @@ pub struct UserFunction {
     arguments: Vec<AnnotatedIdentifier>,
     return_typeid: Option<(/*typeid*/ usize, /*global token pos*/ usize)>,
     body: Vec<Instruction>,
+    // compiled when the function is called for the first time
+    chunk: OnceCell<Rc<Chunk>>,
 
     context: Context,
 }
@@ -85,6 +155,7 @@ impl UserFunction {
             arguments,
             return_typeid,
             body,
+            chunk: OnceCell::new(),
             context,
         }
     }
@@ -99,59 +170,28 @@ impl UserFunction {
         &self.arguments
     }
 
-    pub fn exec(
-        &self,
-        exec_session: &mut ExecSession,
-        parse_session: &ParseSession,
-    ) -> instruction::Result {
-        for item in &self.body {
-            match item.exec(exec_session, parse_session) {
-                Ok(return_value) => match return_value {
-                    ReturnValue::Return((value, pos)) => {
-                        if let Some((expected_typeid, _)) = self.return_typeid {
-                            if expected_typeid == value.typeid() {
-                                return Ok(ReturnValue::Value(value));
-                            } else {
-                                return Err(Error::new(
-                                    item.context(),
-                                    pos.saturating_sub(item.context().start),
-                                    ErrorKind::InvalidReturnType(
-                                        parse_session.get_typename(value.typeid()),
-                                        parse_session.get_typename(expected_typeid),
-                                    ),
-                                ));
-                            }
-                        } else {
-                            return Ok(ReturnValue::Value(value));
-                        }
-                    }
-                    ReturnValue::Value(_) => (),
-                    ReturnValue::Break => {
-                        return Err(Error::new(item.context(), 0, ErrorKind::SyntaxError));
-                    }
-                },
-                Err(e) => {
-                    return Err(e);
-                }
-            }
-        }
+    // None means that the function may return any type
+    #[inline]
+    pub fn return_typeid(&self) -> Option<usize> {
+        self.return_typeid.map(|(typeid, _)| typeid)
+    }
 
-        if let Some((expected_typeid, pos)) = self.return_typeid {
-            if expected_typeid == TYPEID_NONE {
-                return Ok(ReturnValue::Value(Value::None));
-            } else {
-                return Err(Error::new(
-                    self.context,
-                    pos - self.context.start,
-                    ErrorKind::InvalidReturnType(
-                        parse_session.get_typename(TYPEID_NONE),
-                        parse_session.get_typename(expected_typeid),
-                    ),
-                ));
-            }
-        } else {
-            return Ok(ReturnValue::Value(Value::None));
-        }
+    // Global position of the return type annotation
+    #[inline]
+    pub fn return_type_pos(&self) -> Option<usize> {
+        self.return_typeid.map(|(_, pos)| pos)
+    }
+
+    #[inline]
+    pub fn body(&self) -> &Vec<Instruction> {
+        &self.body
+    }
+
+    #[inline]
+    pub fn chunk(&self, parse_session: &ParseSession) -> Rc<Chunk> {
+        self.chunk
+            .get_or_init(|| Rc::new(compiler::compile_function(self, parse_session)))
+            .clone()
     }
 }
 
@@ -174,29 +214,8 @@ impl Return {
     }
 
     #[inline]
-    fn expr_pos(&self) -> usize {
-        match &self.expr {
-            Some(expr) => expr.context().start,
-            None => 0,
-        }
-    }
-
-    pub fn exec(
-        &self,
-        exec_session: &mut ExecSession,
-        parse_session: &ParseSession,
-    ) -> instruction::Result {
-        if let Some(expr) = &self.expr {
-            match expr.exec(exec_session, parse_session) {
-                Ok(output) => {
-                    let value = output.expect("");
-                    Ok(ReturnValue::Return((value, self.expr_pos())))
-                }
-                Err(e) => Err(e),
-            }
-        } else {
-            Ok(ReturnValue::Return((Value::None, self.expr_pos())))
-        }
+    pub fn expr(&self) -> Option<&Expression> {
+        self.expr.as_ref()
     }
 }
 
@@ -207,8 +226,11 @@ pub struct FunctionCall {
         /*scope resulolution operator position*/ usize,
     )>,
     name: String,
+    qualified_name: Option<String>,
     arguments: Vec<Expression>,
     private_access_typeid: Option<usize>,
+    super_typeid: Option<usize>,
+    function_index: Option<usize>,
 
     context: Context,
     name_pos: usize,
@@ -230,14 +252,47 @@ impl FunctionCall {
         FunctionCall {
             associated_type,
             name,
+            qualified_name: None,
             arguments,
             private_access_typeid,
+            super_typeid: None,
+            function_index: None,
             context,
             name_pos,
             line_col,
         }
     }
 
+    // Calls made inside an imported file to functions of the same file
+    // refer to them by their unqualified name
+    #[inline]
+    pub fn set_qualified_name(&mut self, qualified_name: String) {
+        self.qualified_name = Some(qualified_name);
+    }
+
+    #[inline]
+    pub fn qualified_name(&self) -> Option<&str> {
+        self.qualified_name.as_deref()
+    }
+
+    // Calls of the form super.method() are resolved in the parent class
+    #[inline]
+    pub fn set_super_typeid(&mut self, typeid: usize) {
+        self.super_typeid = Some(typeid);
+    }
+
+    #[inline]
+    pub fn super_typeid(&self) -> Option<usize> {
+        self.super_typeid
+    }
+
+    // Compiled calls refer to the function by its index
+    // instead of looking it up by name
+    #[inline]
+    pub fn set_function_index(&mut self, index: usize) {
+        self.function_index = Some(index);
+    }
+
     #[inline]
     pub fn associated_typeid(&self) -> Option<usize> {
         if let Some((id, _)) = self.associated_type {
@@ -281,19 +336,24 @@ impl FunctionCall {
         self.line_col
     }
 
+    // Call the function with arguments that were already evaluated
     pub fn call(
         &self,
-        parse_session: &ParseSession,
-        exec_session: &mut ExecSession,
+        arguments: Vec<Value>,
         caller_object: Option<Value>,
+        exec_session: &mut ExecSession,
+        parse_session: &ParseSession,
         context: Context,
         pos: usize,
-    ) -> instruction::Result {
+    ) -> Result<Value, Error> {
         let fn_query_options: Option<FnQueryOptions> = match caller_object {
             Some(ref c) => {
                 let typeid = c.typeid();
-                if let Some(id) = self.private_access_typeid {
-                    if typeid == id {
+                if let Some(parent) = self.super_typeid {
+                    // super.method() calls the implementation of the parent class
+                    Some(FnQueryOptions::new(parent, true, true))
+                } else if let Some(id) = self.private_access_typeid {
+                    if parse_session.is_subtype(typeid, id) {
                         Some(FnQueryOptions::new(typeid, true, true))
                     } else {
                         Some(FnQueryOptions::new(typeid, true, false))
@@ -305,7 +365,7 @@ impl FunctionCall {
             None => match self.associated_typeid() {
                 Some(typeid) => {
                     if let Some(id) = self.private_access_typeid {
-                        if typeid == id {
+                        if parse_session.is_subtype(typeid, id) {
                             Some(FnQueryOptions::new(typeid, false, true))
                         } else {
                             Some(FnQueryOptions::new(typeid, false, false))
@@ -318,9 +378,43 @@ impl FunctionCall {
             },
         };
 
-        let function = match parse_session.get_function(self.name(), fn_query_options) {
+        let name = match (&fn_query_options, &self.qualified_name) {
+            (None, Some(qualified_name)) => qualified_name.as_str(),
+            _ => self.name(),
+        };
+
+        let lookup = match (&fn_query_options, self.function_index) {
+            (None, Some(index)) => Ok(parse_session.function_at(index)),
+            _ => parse_session.get_function(name, fn_query_options),
+        };
+        let function = match lookup {
             Ok(f) => f,
             Err(errorkind) => {
+                // a property that holds a function can be called like a method
+                if let Some(Value::Class(ref c)) = caller_object {
+                    let private_access = match self.private_access_typeid {
+                        Some(id) => parse_session.is_subtype(c.borrow().typeid(), id),
+                        None => false,
+                    };
+                    let property =
+                        match c
+                            .borrow()
+                            .get_property(self.name(), private_access, parse_session)
+                        {
+                            Ok(var) => Some(var.get_value_clone()),
+                            Err(_) => None,
+                        };
+                    if let Some(Value::Function(f)) = property {
+                        return f.call(
+                            arguments,
+                            exec_session,
+                            parse_session,
+                            context,
+                            pos + self.name_pos,
+                            Some(self.line_col),
+                        );
+                    }
+                }
                 return Err(Error::new(context, pos + self.name_pos, errorkind));
             }
         };
@@ -333,19 +427,13 @@ impl FunctionCall {
             ));
         }
 
-        let mut fn_scope: HashMap<String, Variable> = HashMap::new();
-        for i in 0..function.arguments().len() {
-            let expr = &self.arguments()[i];
-            let value = expr
-                .exec(exec_session, parse_session)?
-                .expect("Expressions should always return a value on success");
-
-            let result;
+        let mut variables = Vec::with_capacity(arguments.len());
+        for (i, value) in arguments.into_iter().enumerate() {
             let expected_type_id = function.arguments()[i].typeid();
 
             if expected_type_id != TYPEID_DYN {
-                if value.typeid() != expected_type_id {
-                    let pos = expr.context().start - self.context().start;
+                if !parse_session.is_subtype(value.typeid(), expected_type_id) {
+                    let pos = self.arguments()[i].context().start - self.context().start;
                     let typename = parse_session.get_typename(value.typeid());
                     let expected_type_name = parse_session.get_typename(expected_type_id);
                     return Err(Error::new(
@@ -354,40 +442,212 @@ impl FunctionCall {
                         ErrorKind::InvalidArgumentType(typename, expected_type_name),
                     ));
                 }
-                result = Variable::new(value, false);
+                variables.push(Variable::new(value, false));
             } else {
-                result = Variable::new(value, true);
+                variables.push(Variable::new(value, true));
             }
-
-            fn_scope.insert(function.arguments()[i].name().to_string(), result);
         }
 
+        let mut fn_scope = function.new_scope(parse_session);
+        function.bind_arguments(&mut fn_scope, variables, parse_session);
         if let Some(c) = caller_object {
-            fn_scope.insert("self".to_string(), Variable::new(c, true));
+            fn_scope.insert("self", Variable::new(c, true));
         } else if let Some(id) = self.associated_typeid() {
-            fn_scope.insert(
-                "#".to_string(),
-                Variable::new(Value::Int(id as isize), false),
-            );
+            fn_scope.insert("#", Variable::new(Value::Int(id as isize), false));
+        }
+
+        let result = run(
+            function,
+            fn_scope,
+            exec_session,
+            parse_session,
+            context,
+            pos,
+        );
+
+        if result.is_err() && (!function.is_builtin() || self.name() == "new") {
+            let backtrace_item = BacktraceItem::new(self.name(), self.line_col());
+            exec_session.add_backtrace(backtrace_item)
         }
 
-        exec_session.increment_call_count()?;
-        exec_session.add_scope(fn_scope);
+        result
+    }
+}
+
+// Run a function in a new scope that already contains its arguments
+fn run(
+    function: &Function,
+    scope: Scope,
+    exec_session: &mut ExecSession,
+    parse_session: &ParseSession,
+    context: Context,
+    pos: usize,
+) -> Result<Value, Error> {
+    exec_session.increment_call_count()?;
+    exec_session.add_scope(scope);
+
+    let result = match function {
+        Function::UserFunction(f) => vm::run(&f.chunk(parse_session), exec_session, parse_session),
+        Function::BuiltinFunction(f) => (f.body)(exec_session, parse_session, context, pos),
+    };
+
+    exec_session.pop_scope();
+    exec_session.decrement_call_count();
+    result
+}
+
+// A function used as a value, either one that was defined with a name
+// or an anonymous function along with the variables it captured
 
-        let result = match function {
-            Function::UserFunction(f) => f.exec(exec_session, parse_session),
-            Function::BuiltinFunction(f) => f.exec(exec_session, parse_session, context, pos),
+#[derive(Debug, Clone)]
+pub enum FunctionValue {
+    Named(String),
+    Closure(Rc<Closure>),
+}
+
+#[derive(Debug)]
+pub struct Closure {
+    function: Rc<Function>,
+    captured: HashMap<String, SharedVariable>,
+}
+
+impl FunctionValue {
+    #[inline]
+    pub fn new_closure(function: Rc<Function>, captured: HashMap<String, SharedVariable>) -> Self {
+        FunctionValue::Closure(Rc::new(Closure { function, captured }))
+    }
+
+    #[inline]
+    pub fn ptr_eq(&self, other: &FunctionValue) -> bool {
+        match (self, other) {
+            (FunctionValue::Named(l), FunctionValue::Named(r)) => l == r,
+            (FunctionValue::Closure(l), FunctionValue::Closure(r)) => Rc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
+
+    // The parameter and return types, e.g. fn(int, string) -> bool
+    pub fn signature(&self, parse_session: &ParseSession) -> String {
+        let function = match self {
+            FunctionValue::Named(name) => match parse_session.get_function(name, None) {
+                Ok(f) => f,
+                Err(_) => return "fn".to_string(),
+            },
+            FunctionValue::Closure(c) => c.function.as_ref(),
         };
 
-        if let Err(_) = result {
-            if !function.is_builtin() || self.name() == "new" {
-                let backtrace_item = BacktraceItem::new(self.name(), self.line_col());
-                exec_session.add_backtrace(backtrace_item)
+        let arguments: Vec<String> = function
+            .arguments()
+            .iter()
+            .map(|arg| parse_session.get_typename(arg.typeid()))
+            .collect();
+        let mut result = format!("fn({})", arguments.join(", "));
+
+        if let Function::UserFunction(f) = function {
+            match f.return_typeid {
+                Some((TYPEID_NONE, _)) => (),
+                Some((typeid, _)) => {
+                    result += format!(" -> {}", parse_session.get_typename(typeid)).as_ref()
+                }
+                None => result += " -> dyn",
             }
         }
 
-        exec_session.pop_scope();
-        exec_session.decrement_call_count();
-        return result;
+        result
+    }
+
+    // Call the function with arguments that were already evaluated,
+    // the call is added to the backtrace on failure if its position is known
+    pub fn call(
+        &self,
+        arguments: Vec<Value>,
+        exec_session: &mut ExecSession,
+        parse_session: &ParseSession,
+        context: Context,
+        pos: usize,
+        line_col: Option<(usize, usize)>,
+    ) -> Result<Value, Error> {
+        let (function, name) = match self {
+            FunctionValue::Named(name) => match parse_session.get_function(name, None) {
+                Ok(f) => (f, name.as_str()),
+                Err(errorkind) => return Err(Error::new(context, pos, errorkind)),
+            },
+            FunctionValue::Closure(c) => (c.function.as_ref(), "fn"),
+        };
+
+        if function.arguments().len() != arguments.len() {
+            return Err(Error::new(
+                context,
+                pos,
+                ErrorKind::InvalidNumberOfArguments,
+            ));
+        }
+
+        let mut variables = Vec::with_capacity(arguments.len());
+        for (parameter, value) in function.arguments().iter().zip(arguments) {
+            let is_dynamic = parameter.typeid() == TYPEID_DYN;
+            if !is_dynamic && !parse_session.is_subtype(value.typeid(), parameter.typeid()) {
+                return Err(Error::new(
+                    context,
+                    pos,
+                    ErrorKind::InvalidArgumentType(
+                        parse_session.get_typename(value.typeid()),
+                        parse_session.get_typename(parameter.typeid()),
+                    ),
+                ));
+            }
+            variables.push(Variable::new(value, is_dynamic));
+        }
+
+        // the arguments take the place of captured variables with the same name
+        let mut scope = function.new_scope(parse_session);
+        if let FunctionValue::Closure(c) = self {
+            for (name, cell) in &c.captured {
+                scope.insert_shared(name, Rc::clone(cell));
+            }
+        }
+        function.bind_arguments(&mut scope, variables, parse_session);
+
+        let result = run(function, scope, exec_session, parse_session, context, pos);
+
+        match result {
+            Ok(value) => Ok(value),
+            Err(e) => {
+                if let Some(line_col) = line_col {
+                    if !function.is_builtin() {
+                        exec_session.add_backtrace(BacktraceItem::new(name, line_col));
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+// Arguments of a call made on the result of an expression, e.g. f(1)(2)
+
+#[derive(Debug, Clone)]
+pub struct CallArguments {
+    arguments: Vec<Expression>,
+    line_col: (usize, usize),
+}
+
+impl CallArguments {
+    #[inline]
+    pub fn new(arguments: Vec<Expression>, line_col: (usize, usize)) -> Self {
+        CallArguments {
+            arguments,
+            line_col,
+        }
+    }
+
+    #[inline]
+    pub fn line_col(&self) -> (usize, usize) {
+        self.line_col
+    }
+
+    #[inline]
+    pub fn arguments(&self) -> &Vec<Expression> {
+        &self.arguments
     }
 }
@@ -0,0 +1,216 @@
+use crate::error::{Error, ErrorKind};
+use crate::function::{BuiltinFunction, Function};
+use crate::session::ParseSession;
+use crate::variable::{AnnotatedIdentifier, Value};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Values exchanged between Dust scripts and the host application
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HostValue {
+    None,
+    Int(isize),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+    Vec(Vec<HostValue>),
+    Object(HostObject),
+}
+
+impl HostValue {
+    pub(crate) fn from_value(value: &Value, parse_session: &ParseSession) -> HostValue {
+        let mut visited: Vec<*const ()> = Vec::new();
+        HostValue::convert(value, parse_session, &mut visited)
+    }
+
+    fn convert(
+        value: &Value,
+        parse_session: &ParseSession,
+        visited: &mut Vec<*const ()>,
+    ) -> HostValue {
+        match value {
+            Value::None => HostValue::None,
+            Value::Int(i) => HostValue::Int(*i),
+            Value::Float(f) => HostValue::Float(*f),
+            Value::Str(s) => HostValue::Str(s.borrow().clone()),
+            Value::Bool(b) => HostValue::Bool(*b),
+            Value::Vector(v) => {
+                // a Vec that contains itself is passed as an opaque handle
+                // at the point where it would recurse
+                let ptr = Rc::as_ptr(v) as *const ();
+                if visited.contains(&ptr) {
+                    return HostValue::Object(HostObject::new(value.clone(), parse_session));
+                }
+                visited.push(ptr);
+                let result = v
+                    .borrow()
+                    .iter()
+                    .map(|item| HostValue::convert(item, parse_session, visited))
+                    .collect();
+                visited.pop();
+                HostValue::Vec(result)
+            }
+            Value::Class(_) | Value::Map(_) | Value::Function(_) | Value::File(_) => {
+                HostValue::Object(HostObject::new(value.clone(), parse_session))
+            }
+        }
+    }
+
+    pub(crate) fn into_value(self) -> Value {
+        match self {
+            HostValue::None => Value::None,
+            HostValue::Int(i) => Value::Int(i),
+            HostValue::Float(f) => Value::Float(f),
+            HostValue::Str(s) => Value::new_string(s),
+            HostValue::Bool(b) => Value::Bool(b),
+            HostValue::Vec(v) => {
+                Value::new_vec_instance_from(v.into_iter().map(|item| item.into_value()).collect())
+            }
+            HostValue::Object(o) => o.value,
+        }
+    }
+}
+
+// Opaque handle to a value that has no direct Rust representation,
+// such as a class instance. Passing it back to a script preserves identity.
+
+#[derive(Debug, Clone)]
+pub struct HostObject {
+    typename: String,
+    value: Value,
+}
+
+impl HostObject {
+    #[inline]
+    fn new(value: Value, parse_session: &ParseSession) -> HostObject {
+        HostObject {
+            typename: parse_session.get_typename(value.typeid()),
+            value,
+        }
+    }
+
+    #[inline]
+    pub fn typename(&self) -> &str {
+        &self.typename
+    }
+}
+
+impl PartialEq for HostObject {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.value, &other.value) {
+            (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+            (Value::Vector(l), Value::Vector(r)) => Rc::ptr_eq(l, r),
+            (Value::Map(l), Value::Map(r)) => Rc::ptr_eq(l, r),
+            (Value::Function(l), Value::Function(r)) => l.ptr_eq(r),
+            (Value::File(l), Value::File(r)) => Rc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
+}
+
+pub type NativeResult = Result<HostValue, String>;
+pub(crate) type NativeBody = Rc<dyn Fn(&[HostValue]) -> NativeResult>;
+
+#[derive(Clone)]
+struct NativeFunction {
+    name: String,
+    signature: Vec<(String, String)>,
+    body: NativeBody,
+}
+
+// A class registered by the host. Like the builtin 'Math' and 'Fs' classes
+// it groups associated functions under a type name, e.g. 'Name::function()'.
+
+#[derive(Clone, Default)]
+pub struct NativeClass {
+    functions: Vec<NativeFunction>,
+}
+
+impl NativeClass {
+    #[inline]
+    pub fn new() -> NativeClass {
+        NativeClass {
+            functions: Vec::new(),
+        }
+    }
+
+    pub fn function<F>(mut self, name: &str, signature: &[(&str, &str)], body: F) -> NativeClass
+    where
+        F: Fn(&[HostValue]) -> NativeResult + 'static,
+    {
+        let signature = signature
+            .iter()
+            .map(|(name, typename)| (name.to_string(), typename.to_string()))
+            .collect();
+        self.functions.push(NativeFunction {
+            name: name.to_string(),
+            signature,
+            body: Rc::new(body),
+        });
+        self
+    }
+
+    pub(crate) fn build(
+        &self,
+        parse_session: &ParseSession,
+    ) -> Result<HashMap<String, Function>, ErrorKind> {
+        let mut result = HashMap::new();
+        for item in &self.functions {
+            if result.contains_key(&item.name) {
+                return Err(ErrorKind::FunctionAlreadyDefined);
+            }
+            let arguments = resolve_signature(&item.signature, parse_session)?;
+            result.insert(
+                item.name.clone(),
+                make_native_function(arguments, item.body.clone()),
+            );
+        }
+        Ok(result)
+    }
+}
+
+pub(crate) fn resolve_signature<S: AsRef<str>>(
+    signature: &[(S, S)],
+    parse_session: &ParseSession,
+) -> Result<Vec<AnnotatedIdentifier>, ErrorKind> {
+    let type_map = parse_session.create_typemap();
+    let mut arguments = Vec::new();
+    for (name, typename) in signature {
+        match type_map.get(typename.as_ref()) {
+            Some(typeid) => {
+                arguments.push(AnnotatedIdentifier::new(name.as_ref().to_string(), *typeid))
+            }
+            None => return Err(ErrorKind::UnknownType(typename.as_ref().to_string())),
+        }
+    }
+    Ok(arguments)
+}
+
+pub(crate) fn make_native_function(
+    arguments: Vec<AnnotatedIdentifier>,
+    body: NativeBody,
+) -> Function {
+    let names: Vec<String> = arguments
+        .iter()
+        .map(|item| item.name().to_string())
+        .collect();
+
+    Function::BuiltinFunction(BuiltinFunction::new(
+        arguments,
+        move |exec_session, parse_session, context, pos| {
+            let args: Vec<HostValue> = names
+                .iter()
+                .map(|name| {
+                    let operand = exec_session.get_variable(name).unwrap();
+                    HostValue::from_value(operand.get_value(), parse_session)
+                })
+                .collect();
+
+            match body(&args) {
+                Ok(value) => Ok(value.into_value()),
+                Err(message) => Err(Error::new(context, pos, ErrorKind::CustomError(message))),
+            }
+        },
+    ))
+}
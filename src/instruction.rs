@@ -1,11 +1,10 @@
 use crate::branch::Branch;
-use crate::builtin::TYPEID_DYN;
-use crate::error::{Context, Error, ErrorKind};
+use crate::error::Context;
 use crate::expression::Expression;
 use crate::for_loop::ForLoop;
 use crate::function::Return;
-use crate::session::{ExecSession, ParseSession};
-use crate::variable::{OptionallyAnnotatedIdentifier, Value, Variable};
+use crate::token::Operator;
+use crate::variable::OptionallyAnnotatedIdentifier;
 use crate::while_loop::WhileLoop;
 
 #[derive(Debug, Clone)]
@@ -16,25 +15,16 @@ pub enum Instruction {
     ForLoop(ForLoop),
     Return(Return),
     Break(Break),
+    Continue(Continue),
+    GlobalDeclaration(GlobalDeclaration),
     VariableInit(VariableInit),
+    // a variable of the global scope that cannot be assigned to
+    ConstantInit(VariableInit),
     VariableAssign(VariableAssign),
+    IndexAssign(IndexAssign),
 }
 
 impl Instruction {
-    #[inline]
-    pub fn exec(&self, exec_session: &mut ExecSession, parse_session: &ParseSession) -> Result {
-        match self {
-            Instruction::Expression(e) => e.exec(exec_session, parse_session),
-            Instruction::Branch(b) => b.exec(exec_session, parse_session),
-            Instruction::WhileLoop(wl) => wl.exec(exec_session, parse_session),
-            Instruction::ForLoop(fl) => fl.exec(exec_session, parse_session),
-            Instruction::Return(r) => r.exec(exec_session, parse_session),
-            Instruction::Break(_) => Ok(ReturnValue::Break),
-            Instruction::VariableInit(vi) => vi.exec(exec_session, parse_session),
-            Instruction::VariableAssign(va) => va.exec(exec_session, parse_session),
-        }
-    }
-
     #[inline]
     pub fn context(&self) -> Context {
         match self {
@@ -44,51 +34,72 @@ impl Instruction {
             Instruction::ForLoop(fl) => fl.context(),
             Instruction::Return(r) => r.context(),
             Instruction::Break(br) => br.context(),
-            Instruction::VariableInit(vi) => vi.context(),
+            Instruction::Continue(co) => co.context(),
+            Instruction::GlobalDeclaration(gd) => gd.context(),
+            Instruction::VariableInit(vi) | Instruction::ConstantInit(vi) => vi.context(),
             Instruction::VariableAssign(va) => va.context(),
+            Instruction::IndexAssign(ia) => ia.context(),
         }
     }
 }
 
-pub type Result = std::result::Result<ReturnValue, Error>;
+#[derive(Debug, Clone, Copy)]
+pub struct Break {
+    context: Context,
+}
+
+impl Break {
+    #[inline]
+    pub fn new(context: Context) -> Break {
+        Break { context }
+    }
+
+    #[inline]
+    pub fn context(&self) -> Context {
+        self.context
+    }
+}
 
 #[derive(Debug, Clone)]
-pub enum ReturnValue {
-    Value(Value),
-    Return(
-        (
-            Value,
-            usize, // global position of the token that produced tha value, for error reporting
-        ),
-    ),
-    Break,
+pub struct Continue {
+    context: Context,
 }
 
-impl ReturnValue {
+impl Continue {
     #[inline]
-    pub fn expect(self, msg: &str) -> Value {
-        match self {
-            ReturnValue::Value(v) | ReturnValue::Return((v, _)) => v,
-            _ => panic!("{msg}"),
-        }
+    pub fn new(context: Context) -> Continue {
+        Continue { context }
+    }
+
+    #[inline]
+    pub fn context(&self) -> Context {
+        self.context
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct Break {
+// Names that refer to global variables in the rest of a function
+
+#[derive(Debug, Clone)]
+pub struct GlobalDeclaration {
+    names: Vec<String>,
     context: Context,
 }
 
-impl Break {
+impl GlobalDeclaration {
     #[inline]
-    pub fn new(context: Context) -> Break {
-        Break { context }
+    pub fn new(names: Vec<String>, context: Context) -> Self {
+        GlobalDeclaration { names, context }
     }
 
     #[inline]
     pub fn context(&self) -> Context {
         self.context
     }
+
+    #[inline]
+    pub fn names(&self) -> &Vec<String> {
+        &self.names
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -121,39 +132,19 @@ impl VariableInit {
         self.context
     }
 
-    pub fn exec(&self, exec_session: &mut ExecSession, parse_session: &ParseSession) -> Result {
-        match self.expr.exec(exec_session, parse_session) {
-            Ok(output) => {
-                let value = output.expect("Expressions should always return a value on success");
-
-                for item in &self.identifiers {
-                    if let Some(typeid) = item.typeid() {
-                        if typeid == TYPEID_DYN {
-                            let var = Variable::new(value.clone(), true);
-                            exec_session.add_variable(&item.name(), var);
-                        } else if typeid == value.typeid() {
-                            let var = Variable::new(value.clone(), false);
-                            exec_session.add_variable(&item.name(), var);
-                        } else {
-                            return Err(Error::new(
-                                self.context,
-                                self.assign_pos,
-                                ErrorKind::InvalidAssignment(
-                                    parse_session.get_typename(typeid),
-                                    parse_session.get_typename(value.typeid()),
-                                ),
-                            ));
-                        }
-                    } else {
-                        let var = Variable::new(value.clone(), false);
-                        exec_session.add_variable(&item.name(), var);
-                    }
-                }
-
-                Ok(ReturnValue::Value(Value::None))
-            }
-            Err(e) => Err(e),
-        }
+    #[inline]
+    pub fn identifiers(&self) -> &Vec<OptionallyAnnotatedIdentifier> {
+        &self.identifiers
+    }
+
+    #[inline]
+    pub fn expr(&self) -> &Expression {
+        &self.expr
+    }
+
+    #[inline]
+    pub fn assign_pos(&self) -> usize {
+        self.assign_pos
     }
 }
 
@@ -163,6 +154,7 @@ pub struct VariableAssign {
     identifier: String,
     private_access_typeid: Option<usize>,
     expr: Expression,
+    compound_operator: Option<Operator>,
 
     context: Context,
     id_pos: usize,
@@ -185,84 +177,132 @@ impl VariableAssign {
             identifier,
             private_access_typeid,
             expr,
+            compound_operator: None,
             context,
             id_pos,
             assign_pos,
         }
     }
 
+    // Turns the assignment into a compound one, e.g. x += 1
+    #[inline]
+    pub fn set_compound_operator(&mut self, operator: Operator) {
+        self.compound_operator = Some(operator);
+    }
+
     #[inline]
     pub fn context(&self) -> Context {
         self.context
     }
 
-    pub fn exec(&self, exec_session: &mut ExecSession, parse_session: &ParseSession) -> Result {
-        let rhs = self
-            .expr
-            .exec(exec_session, parse_session)?
-            .expect("Expressions should always return a value on success");
-
-        if let Some((ref e, pos)) = self.source_expr {
-            let source = e
-                .exec(exec_session, parse_session)?
-                .expect("Expressions should always return a value on success");
-
-            match source {
-                Value::Class(c) => {
-                    let private_access: bool = if let Some(id) = self.private_access_typeid {
-                        if id == c.borrow().typeid() {
-                            true
-                        } else {
-                            false
-                        }
-                    } else {
-                        false
-                    };
-
-                    match c.borrow_mut().set_property(
-                        self.identifier.as_str(),
-                        private_access,
-                        rhs,
-                        parse_session,
-                    ) {
-                        Ok(()) => (),
-                        Err(e) => match e {
-                            ErrorKind::HasNoMember(_, _) => {
-                                return Err(Error::new(self.context, pos, e));
-                            }
-                            ErrorKind::MemberIsPrivate(_) => {
-                                return Err(Error::new(self.context, self.id_pos, e));
-                            }
-                            ErrorKind::InvalidAssignment(_, _) => {
-                                return Err(Error::new(self.context, self.assign_pos, e));
-                            }
-                            _ => unreachable!(),
-                        },
-                    }
-                }
-                _ => {
-                    return Err(Error::new(self.context, 0, ErrorKind::InvalidMemberAccess));
-                }
-            }
-        } else {
-            if let Some(lhs_var) = exec_session.get_variable_mut(&self.identifier) {
-                if lhs_var.is_dynamic() || lhs_var.typeid() == rhs.typeid() {
-                    lhs_var.set_value(rhs.clone());
-                } else {
-                    return Err(Error::new(
-                        self.context,
-                        self.assign_pos,
-                        ErrorKind::InvalidAssignment(
-                            parse_session.get_typename(lhs_var.typeid()),
-                            parse_session.get_typename(rhs.typeid()),
-                        ),
-                    ));
-                }
-            } else {
-                return Err(Error::new(self.context, 0, ErrorKind::IdentifierNotFound));
-            }
+    // The expression that produces the object whose property is assigned
+    #[inline]
+    pub fn source_expr(&self) -> Option<&Expression> {
+        self.source_expr.as_ref().map(|(e, _)| e)
+    }
+
+    // Position of the access operator
+    #[inline]
+    pub fn source_pos(&self) -> Option<usize> {
+        self.source_expr.as_ref().map(|(_, pos)| *pos)
+    }
+
+    #[inline]
+    pub fn private_access_typeid(&self) -> Option<usize> {
+        self.private_access_typeid
+    }
+
+    #[inline]
+    pub fn id_pos(&self) -> usize {
+        self.id_pos
+    }
+
+    #[inline]
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    #[inline]
+    pub fn expr(&self) -> &Expression {
+        &self.expr
+    }
+
+    #[inline]
+    pub fn compound_operator(&self) -> Option<Operator> {
+        self.compound_operator
+    }
+
+    #[inline]
+    pub fn assign_pos(&self) -> usize {
+        self.assign_pos
+    }
+}
+
+// Assignment to an element of a container, e.g. v[0] = 1
+
+#[derive(Debug, Clone)]
+pub struct IndexAssign {
+    container: Expression,
+    index: Expression,
+    expr: Expression,
+    compound_operator: Option<(Operator, /*operator position*/ usize)>,
+
+    context: Context,
+    index_pos: usize,
+}
+
+impl IndexAssign {
+    #[inline]
+    pub fn new(
+        container: Expression,
+        index: Expression,
+        expr: Expression,
+        context: Context,
+        index_pos: usize,
+    ) -> Self {
+        IndexAssign {
+            container,
+            index,
+            expr,
+            compound_operator: None,
+            context,
+            index_pos,
         }
+    }
+
+    // Turns the assignment into a compound one, e.g. v[0] += 1
+    #[inline]
+    pub fn set_compound_operator(&mut self, operator: Operator, pos: usize) {
+        self.compound_operator = Some((operator, pos));
+    }
+
+    #[inline]
+    pub fn context(&self) -> Context {
+        self.context
+    }
 
-        Ok(ReturnValue::Value(Value::None))
+    #[inline]
+    pub fn container(&self) -> &Expression {
+        &self.container
+    }
+
+    #[inline]
+    pub fn index(&self) -> &Expression {
+        &self.index
+    }
+
+    #[inline]
+    pub fn expr(&self) -> &Expression {
+        &self.expr
+    }
+
+    #[inline]
+    pub fn compound_operator(&self) -> Option<(Operator, usize)> {
+        self.compound_operator
+    }
+
+    #[inline]
+    pub fn index_pos(&self) -> usize {
+        self.index_pos
     }
 }
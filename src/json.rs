@@ -0,0 +1,450 @@
+use crate::map::MapKey;
+use crate::session::ParseSession;
+use crate::variable::Value;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+// Deeper documents are rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 512;
+
+// Objects become Maps with string keys, arrays become Vecs and
+// numbers become ints unless they have a fraction, an exponent
+// or are too large, in which case they become floats
+
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+        depth: 0,
+    };
+    parser.skip_whitespace();
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos < parser.chars.len() {
+        return Err(parser.unexpected());
+    }
+    Ok(value)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('{') => self.nested(Parser::object),
+            Some('[') => self.nested(Parser::array),
+            Some('"') => Ok(Value::new_string(self.string()?)),
+            Some('t') => self.literal("true", Value::Bool(true)),
+            Some('f') => self.literal("false", Value::Bool(false)),
+            Some('n') => self.literal("null", Value::None),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    #[inline]
+    fn nested(&mut self, f: fn(&mut Parser) -> Result<Value, String>) -> Result<Value, String> {
+        if self.depth == MAX_DEPTH {
+            return Err("Maximum nesting depth reached".to_string());
+        }
+        self.depth += 1;
+        let value = f(self)?;
+        self.depth -= 1;
+        Ok(value)
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        let mut map = BTreeMap::new();
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Value::new_map_instance_from(map));
+        }
+        loop {
+            if self.peek() != Some('"') {
+                return Err(self.unexpected());
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            let value = self.value()?;
+            map.insert(MapKey::Str(key), value);
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => self.skip_whitespace(),
+                Some('}') => return Ok(Value::new_map_instance_from(map)),
+                _ => return Err(self.unexpected_previous()),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        let mut vec = Vec::new();
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Value::new_vec_instance_from(vec));
+        }
+        loop {
+            vec.push(self.value()?);
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => self.skip_whitespace(),
+                Some(']') => return Ok(Value::new_vec_instance_from(vec)),
+                _ => return Err(self.unexpected_previous()),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let mut result = String::new();
+        self.pos += 1;
+        loop {
+            match self.next() {
+                Some('"') => return Ok(result),
+                Some('\\') => match self.next() {
+                    Some('"') => result.push('"'),
+                    Some('\\') => result.push('\\'),
+                    Some('/') => result.push('/'),
+                    Some('b') => result.push('\u{8}'),
+                    Some('f') => result.push('\u{c}'),
+                    Some('n') => result.push('\n'),
+                    Some('r') => result.push('\r'),
+                    Some('t') => result.push('\t'),
+                    Some('u') => result.push(self.unicode_escape()?),
+                    _ => return Err(self.unexpected_previous()),
+                },
+                Some(c) if (c as u32) < 0x20 => return Err(self.unexpected_previous()),
+                Some(c) => result.push(c),
+                None => return Err(self.unexpected()),
+            }
+        }
+    }
+
+    // Characters outside of the basic plane are written as surrogate pairs
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex_digits()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.invalid_escape());
+        }
+        if self.next() != Some('\\') || self.next() != Some('u') {
+            return Err(self.invalid_escape());
+        }
+        let low = self.hex_digits()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(self.invalid_escape());
+        }
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+            .ok_or_else(|| self.invalid_escape())
+    }
+
+    #[inline]
+    fn hex_digits(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            match self.next().and_then(|c| c.to_digit(16)) {
+                Some(digit) => code = code * 16 + digit,
+                None => return Err(self.unexpected_previous()),
+            }
+        }
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        let mut is_float = false;
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some('0') => self.pos += 1,
+            Some(c) if c.is_ascii_digit() => self.digits(),
+            _ => return Err(self.unexpected()),
+        }
+        if self.peek() == Some('.') {
+            is_float = true;
+            self.pos += 1;
+            self.required_digits()?;
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            is_float = true;
+            self.pos += 1;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.pos += 1;
+            }
+            self.required_digits()?;
+        }
+
+        let text: String = self.chars[start..self.pos].iter().collect();
+        if !is_float {
+            if let Ok(i) = text.parse::<isize>() {
+                return Ok(Value::Int(i));
+            }
+        }
+        match text.parse::<f64>() {
+            Ok(f) => Ok(Value::Float(f)),
+            Err(_) => Err(format!("Invalid number '{}'", text)),
+        }
+    }
+
+    #[inline]
+    fn digits(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+    }
+
+    #[inline]
+    fn required_digits(&mut self) -> Result<(), String> {
+        if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            return Err(self.unexpected());
+        }
+        self.digits();
+        Ok(())
+    }
+
+    #[inline]
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        for expected in word.chars() {
+            if self.next() != Some(expected) {
+                return Err(self.unexpected_previous());
+            }
+        }
+        Ok(value)
+    }
+
+    #[inline]
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(self.unexpected_previous()),
+        }
+    }
+
+    #[inline]
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.pos += 1;
+        }
+    }
+
+    #[inline]
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    #[inline]
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    // Used after next() has moved past the character that was not expected
+    #[inline]
+    fn unexpected_previous(&mut self) -> String {
+        self.pos -= 1;
+        self.unexpected()
+    }
+
+    fn unexpected(&self) -> String {
+        let (line, column) = self.line_col();
+        match self.peek() {
+            Some(c) => format!(
+                "Unexpected character '{}' at line {} column {}",
+                c.escape_default(),
+                line,
+                column
+            ),
+            None => "Unexpected end of input".to_string(),
+        }
+    }
+
+    fn invalid_escape(&self) -> String {
+        let (line, column) = self.line_col();
+        format!("Invalid unicode escape at line {} column {}", line, column)
+    }
+
+    #[inline]
+    fn line_col(&self) -> (usize, usize) {
+        let pos = self.pos.min(self.chars.len());
+        let line = self.chars[..pos].iter().filter(|c| **c == '\n').count() + 1;
+        let line_start = self.chars[..pos]
+            .iter()
+            .rposition(|c| *c == '\n')
+            .map_or(0, |i| i + 1);
+        (line, pos - line_start + 1)
+    }
+}
+
+// Class instances are written as objects of their public properties,
+// map keys that are not strings are converted to strings
+
+pub fn stringify(
+    value: &Value,
+    pretty: bool,
+    parse_session: &ParseSession,
+) -> Result<String, String> {
+    let mut writer = Writer {
+        output: String::new(),
+        pretty,
+        indent: 0,
+        visited: Vec::new(),
+        parse_session,
+    };
+    writer.value(value)?;
+    Ok(writer.output)
+}
+
+struct Writer<'a> {
+    output: String,
+    pretty: bool,
+    indent: usize,
+    // containers that are being written, to detect values that contain themselves
+    visited: Vec<*const ()>,
+    parse_session: &'a ParseSession,
+}
+
+impl Writer<'_> {
+    fn value(&mut self, value: &Value) -> Result<(), String> {
+        match value {
+            Value::None => self.output.push_str("null"),
+            Value::Int(i) => self.output.push_str(&i.to_string()),
+            Value::Float(f) => {
+                if !f.is_finite() {
+                    return Err(format!("Cannot convert float '{}' to JSON", f));
+                }
+                self.output.push_str(&format!("{:?}", f));
+            }
+            Value::Str(s) => self.string(&s.borrow()),
+            Value::Bool(b) => self.output.push_str(&b.to_string()),
+            Value::Vector(v) => {
+                self.enter(Rc::as_ptr(v) as *const ())?;
+                let items: Vec<Value> = v.borrow().clone();
+                self.sequence('[', ']', items.len(), |writer, i| writer.value(&items[i]))?;
+                self.visited.pop();
+            }
+            Value::Map(m) => {
+                self.enter(Rc::as_ptr(m) as *const ())?;
+                let entries: Vec<(String, Value)> = m
+                    .borrow()
+                    .iter()
+                    .map(|(key, value)| (key_string(key), value.clone()))
+                    .collect();
+                self.entries(&entries)?;
+                self.visited.pop();
+            }
+            Value::Class(c) => {
+                self.enter(Rc::as_ptr(c) as *const ())?;
+                let entries: Vec<(String, Value)> = c
+                    .borrow()
+                    .public_properties()
+                    .into_iter()
+                    .map(|(name, value)| (name.to_string(), value.clone()))
+                    .collect();
+                self.entries(&entries)?;
+                self.visited.pop();
+            }
+            Value::Function(_) | Value::File(_) => {
+                return Err(format!(
+                    "Cannot convert type '{}' to JSON",
+                    self.parse_session.get_typename(value.typeid())
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn entries(&mut self, entries: &[(String, Value)]) -> Result<(), String> {
+        self.sequence('{', '}', entries.len(), |writer, i| {
+            let (key, value) = &entries[i];
+            writer.string(key);
+            writer.output.push(':');
+            if writer.pretty {
+                writer.output.push(' ');
+            }
+            writer.value(value)
+        })
+    }
+
+    fn sequence<F>(
+        &mut self,
+        open: char,
+        close: char,
+        len: usize,
+        mut item: F,
+    ) -> Result<(), String>
+    where
+        F: FnMut(&mut Self, usize) -> Result<(), String>,
+    {
+        self.output.push(open);
+        if len == 0 {
+            self.output.push(close);
+            return Ok(());
+        }
+        self.indent += 1;
+        for i in 0..len {
+            if i > 0 {
+                self.output.push(',');
+            }
+            self.newline();
+            item(self, i)?;
+        }
+        self.indent -= 1;
+        self.newline();
+        self.output.push(close);
+        Ok(())
+    }
+
+    #[inline]
+    fn newline(&mut self) {
+        if self.pretty {
+            self.output.push('\n');
+            self.output.push_str(&"  ".repeat(self.indent));
+        }
+    }
+
+    #[inline]
+    fn enter(&mut self, ptr: *const ()) -> Result<(), String> {
+        if self.visited.contains(&ptr) {
+            return Err("Cannot convert a value that contains itself to JSON".to_string());
+        }
+        self.visited.push(ptr);
+        Ok(())
+    }
+
+    fn string(&mut self, s: &str) {
+        self.output.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => self.output.push_str("\\\""),
+                '\\' => self.output.push_str("\\\\"),
+                '\n' => self.output.push_str("\\n"),
+                '\r' => self.output.push_str("\\r"),
+                '\t' => self.output.push_str("\\t"),
+                '\u{8}' => self.output.push_str("\\b"),
+                '\u{c}' => self.output.push_str("\\f"),
+                c if (c as u32) < 0x20 => self.output.push_str(&format!("\\u{:04x}", c as u32)),
+                c => self.output.push(c),
+            }
+        }
+        self.output.push('"');
+    }
+}
+
+#[inline]
+fn key_string(key: &MapKey) -> String {
+    match key {
+        MapKey::Str(s) => s.clone(),
+        MapKey::Int(i) => i.to_string(),
+        MapKey::Bool(b) => b.to_string(),
+    }
+}
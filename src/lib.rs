@@ -1,106 +1,172 @@
 mod branch;
 mod builtin;
+mod checker;
 mod class;
+mod compiler;
 mod error;
 mod expression;
+mod file;
 mod for_loop;
 mod function;
+mod host;
 mod instruction;
+mod json;
+mod limits;
+mod literal;
+mod map;
 mod operation;
 mod parser;
 mod session;
+mod stream;
 mod token;
 mod variable;
+mod vm;
 mod while_loop;
 
 #[cfg(test)]
 mod tests;
 
-#[cfg(not(debug_assertions))]
-const FUNCTION_CALL_LIMIT: usize = 1000;
-#[cfg(debug_assertions)]
-const FUNCTION_CALL_LIMIT: usize = 100;
-
-pub use crate::session::Session;
-
-fn process(input: &str, session: &mut Session) {
-    let parse_result = parser::parse(&input, &mut session.parse_session);
-
-    match parse_result {
-        Ok(instructions) => {
-            for item in instructions {
-                match item.exec(&mut session.exec_session, &session.parse_session) {
-                    Ok(_) => (),
-                    Err(e) => {
-                        print_error_message(e, session);
-                        break;
-                    }
-                }
+use crate::error::Error;
+use crate::instruction::Instruction;
+use crate::variable::Value;
+
+pub use crate::error::{DustError, ErrorKind, SyntaxErrorDetails};
+pub use crate::host::{HostObject, HostValue, NativeClass, NativeResult};
+pub use crate::limits::{InterruptHandle, Limits};
+pub use crate::session::{BacktraceItem, Session};
+pub use crate::stream::{InputStream, MemoryBuffer, OutputStream};
+pub use crate::token::Operator;
+
+fn process(input: &str, session: &mut Session) -> bool {
+    match run(input, session) {
+        Ok(_) => true,
+        Err(errors) => {
+            for e in errors {
+                print_error_message(e, session);
             }
+            false
         }
+    }
+}
 
-        Err(e) => {
-            print_error_message(e, session);
-        }
+// Parses and executes the input, returning the value of the
+// last instruction if it was an expression. Nothing is executed
+// if the type checker finds errors, all of them are returned
+
+fn run(input: &str, session: &mut Session) -> Result<Value, Vec<Error>> {
+    let first_function = session.parse_session.user_functions().len();
+    let first_typeid = session.parse_session.get_next_typeid();
+    let instructions = parser::parse(input, &mut session.parse_session)?;
+
+    let errors = checker::check(
+        &instructions,
+        first_function,
+        first_typeid,
+        &session.parse_session,
+        &session.exec_session,
+        false,
+    );
+    if !errors.is_empty() {
+        return Err(errors);
     }
+
+    let program = compiler::compile_program(
+        &instructions,
+        session.exec_session.global_scope(),
+        &session.parse_session,
+    );
+    session
+        .exec_session
+        .set_global_layout(program.layout().clone(), program.slot_count());
+    session.exec_session.budget_mut().start();
+    session.exec_session.set_exit_code(None);
+
+    let mut result = Value::None;
+    for (item, chunk) in instructions.iter().zip(program.chunks()) {
+        let output = match vm::run(chunk, &mut session.exec_session, &session.parse_session) {
+            Ok(output) => output,
+            Err(e) => match e.kind() {
+                // the rest of the input does not run after 'exit'
+                ErrorKind::Exit(code) => {
+                    session.exec_session.set_exit_code(Some(*code));
+                    session.exec_session.clear_backtrace();
+                    return Ok(Value::None);
+                }
+                _ => return Err(vec![e]),
+            },
+        };
+        result = match item {
+            Instruction::Expression(_) => output,
+            _ => Value::None,
+        };
+    }
+
+    Ok(result)
+}
+
+// Parses and checks the input without executing it and prints
+// every error that was found, returns whether there were none
+
+fn check(input: &str, session: &mut Session) -> bool {
+    let first_function = session.parse_session.user_functions().len();
+    let first_typeid = session.parse_session.get_next_typeid();
+    // the statements that were built are checked even if others have errors
+    let (instructions, mut errors) = parser::parse_partial(input, &mut session.parse_session);
+    errors.extend(checker::check(
+        &instructions,
+        first_function,
+        first_typeid,
+        &session.parse_session,
+        &session.exec_session,
+        true,
+    ));
+    errors.sort_by_key(|e| e.position());
+
+    let is_valid = errors.is_empty();
+    for e in errors {
+        print_error_message(e, session);
+    }
+    is_valid
 }
 
 #[inline]
-fn print_error_message(e: crate::error::Error, session: &mut Session) {
-    let mut error_string = e.print_to_string(
+fn print_error_message(e: Error, session: &mut Session) {
+    let mut error_string = e.print_to_string_with_files(
         session.parse_session.get_source_code(),
+        session.parse_session.source_files(),
         session.exec_session.get_backtrace(),
     );
     session.exec_session.clear_backtrace();
-    if error_string.ends_with('\n') {
-        error_string.pop();
+    if !error_string.ends_with('\n') {
+        error_string.push('\n');
     }
-    println!("{error_string}");
+    // there is nowhere left to report a failure to write the message
+    _ = session.exec_session.output().write_str(&error_string);
 }
 
 #[cfg(test)]
 pub fn process_to_string(input: &str, session: &mut Session) -> String {
-    let parse_result = parser::parse(input, &mut session.parse_session);
-
-    match parse_result {
-        Ok(instructions) => {
-            for item in instructions {
-                match item.exec(&mut session.exec_session, &session.parse_session) {
-                    Ok(_) => (),
-                    Err(e) => {
-                        let error_string = e.print_to_string(
-                            session.parse_session.get_source_code(),
-                            session.exec_session.get_backtrace(),
-                        );
-                        session.exec_session.clear_backtrace();
-                        session
-                            .exec_session
-                            .output_stream
-                            .push_str(error_string.as_str());
-                        break;
-                    }
-                }
-            }
+    let buffer = MemoryBuffer::new();
+    session.set_output(OutputStream::new(buffer.clone()));
 
-            let result = session.exec_session.output_stream.clone();
-            session.exec_session.output_stream.clear();
-            result
-        }
-
-        Err(e) => {
-            let error_string = e.print_to_string(
-                session.parse_session.get_source_code(),
-                session.exec_session.get_backtrace(),
-            );
-            session.exec_session.clear_backtrace();
-            session
-                .exec_session
-                .output_stream
-                .push_str(error_string.as_str());
-
-            let result = session.exec_session.output_stream.clone();
-            session.exec_session.output_stream.clear();
-            result
+    let errors = run(input, session).err().unwrap_or_default();
+    for (i, e) in errors.iter().enumerate() {
+        let mut error_string = e.print_to_string_with_files(
+            session.parse_session.get_source_code(),
+            session.parse_session.source_files(),
+            session.exec_session.get_backtrace(),
+        );
+        session.exec_session.clear_backtrace();
+        // the errors are separated the same way as when they are printed
+        if i + 1 < errors.len() {
+            error_string.push('\n');
         }
+        session
+            .exec_session
+            .output()
+            .write_str(&error_string)
+            .unwrap();
     }
+
+    buffer.take()
 }
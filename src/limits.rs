@@ -0,0 +1,162 @@
+use crate::error::ErrorKind;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(not(debug_assertions))]
+const DEFAULT_CALL_DEPTH: usize = 1000;
+#[cfg(debug_assertions)]
+const DEFAULT_CALL_DEPTH: usize = 100;
+
+// how many steps are taken between looking at the clock and the interrupt flag
+const CHECK_INTERVAL: u64 = 1024;
+
+// Bounds on the work a single input may do, used to run scripts
+// that are not trusted. Only the call depth is limited by default.
+
+#[derive(Debug, Clone)]
+pub struct Limits {
+    max_call_depth: usize,
+    max_steps: Option<u64>,
+    max_loop_iterations: Option<u64>,
+    timeout: Option<Duration>,
+}
+
+impl Limits {
+    #[inline]
+    pub fn new() -> Limits {
+        Limits {
+            max_call_depth: DEFAULT_CALL_DEPTH,
+            max_steps: None,
+            max_loop_iterations: None,
+            timeout: None,
+        }
+    }
+
+    // Deep recursion can overflow the stack of the host
+    // before the limit is reached if it is set too high
+    #[inline]
+    pub fn max_call_depth(mut self, depth: usize) -> Limits {
+        self.max_call_depth = depth;
+        self
+    }
+
+    // The number of bytecode operations an input may execute
+    #[inline]
+    pub fn max_steps(mut self, steps: u64) -> Limits {
+        self.max_steps = Some(steps);
+        self
+    }
+
+    // The number of times the body of a single loop may run
+    #[inline]
+    pub fn max_loop_iterations(mut self, iterations: u64) -> Limits {
+        self.max_loop_iterations = Some(iterations);
+        self
+    }
+
+    // How long an input may run, measured from when its execution starts
+    #[inline]
+    pub fn timeout(mut self, timeout: Duration) -> Limits {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl Default for Limits {
+    #[inline]
+    fn default() -> Self {
+        Limits::new()
+    }
+}
+
+// Stops the script that is running in a session, it can be sent to other threads
+
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle {
+    flag: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    #[inline]
+    pub fn interrupt(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+}
+
+// The limits of a session and the work done by the input that is running
+
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    limits: Limits,
+    interrupt: InterruptHandle,
+    call_depth: usize,
+    steps: u64,
+    deadline: Option<Instant>,
+}
+
+impl Budget {
+    #[inline]
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    #[inline]
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    // Called before an input runs, an interrupt that was
+    // requested while nothing was running is discarded
+    #[inline]
+    pub fn start(&mut self) {
+        self.call_depth = 0;
+        self.steps = 0;
+        self.deadline = self.limits.timeout.map(|timeout| Instant::now() + timeout);
+        self.interrupt.flag.store(false, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn enter_call(&mut self) -> Result<(), ErrorKind> {
+        if self.call_depth >= self.limits.max_call_depth {
+            Err(ErrorKind::CallDepthLimitReached)
+        } else {
+            self.call_depth += 1;
+            Ok(())
+        }
+    }
+
+    #[inline]
+    pub fn leave_call(&mut self) {
+        self.call_depth -= 1;
+    }
+
+    #[inline]
+    pub fn step(&mut self) -> Result<(), ErrorKind> {
+        self.steps += 1;
+        if self.limits.max_steps.is_some_and(|max| self.steps > max) {
+            return Err(ErrorKind::StepLimitReached);
+        }
+        if self.steps.is_multiple_of(CHECK_INTERVAL) {
+            if self.interrupt.flag.load(Ordering::Relaxed) {
+                return Err(ErrorKind::Interrupted);
+            }
+            if self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                return Err(ErrorKind::TimeLimitReached);
+            }
+        }
+        Ok(())
+    }
+
+    // The number of times the body of a loop has started running
+    #[inline]
+    pub fn check_iterations(&self, iterations: u64) -> Result<(), ErrorKind> {
+        match self.limits.max_loop_iterations {
+            Some(max) if iterations > max => Err(ErrorKind::IterationLimitReached),
+            _ => Ok(()),
+        }
+    }
+}
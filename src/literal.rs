@@ -0,0 +1,68 @@
+use crate::expression::Expression;
+
+// Literals of container types contain expressions, so unlike
+// the literals of primitive types they are evaluated at runtime
+// and a new instance is created on every evaluation
+
+// A Vec literal such as [1, 2, 3]
+
+#[derive(Debug, Clone)]
+pub struct VecLiteral {
+    items: Vec<Expression>,
+}
+
+impl VecLiteral {
+    #[inline]
+    pub fn new(items: Vec<Expression>) -> VecLiteral {
+        VecLiteral { items }
+    }
+
+    #[inline]
+    pub fn items(&self) -> &Vec<Expression> {
+        &self.items
+    }
+}
+
+// A Map literal such as { "a": 1, "b": 2 }
+
+#[derive(Debug, Clone)]
+pub struct MapLiteral {
+    entries: Vec<(Expression, Expression)>,
+}
+
+impl MapLiteral {
+    #[inline]
+    pub fn new(entries: Vec<(Expression, Expression)>) -> MapLiteral {
+        MapLiteral { entries }
+    }
+
+    #[inline]
+    pub fn entries(&self) -> &Vec<(Expression, Expression)> {
+        &self.entries
+    }
+}
+
+// A string literal with embedded expressions such as "Hello {name}"
+
+#[derive(Debug, Clone)]
+pub enum InterpolationPart {
+    Text(String),
+    Expression(Expression),
+}
+
+#[derive(Debug, Clone)]
+pub struct StringInterpolation {
+    parts: Vec<InterpolationPart>,
+}
+
+impl StringInterpolation {
+    #[inline]
+    pub fn new(parts: Vec<InterpolationPart>) -> StringInterpolation {
+        StringInterpolation { parts }
+    }
+
+    #[inline]
+    pub fn parts(&self) -> &Vec<InterpolationPart> {
+        &self.parts
+    }
+}
@@ -7,35 +7,56 @@ use dust::Session;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() > 2 {
-        eprintln!("Usage: {} [file_path]", args[0]);
-        std::process::exit(1);
-    }
-    if args.len() == 2 {
-        if args[1] == "--help" {
+    match args.get(1).map(String::as_str) {
+        None => interpreter(),
+        Some("--help") if args.len() == 2 => {
             println!(
-                "Dust Scripting Language Interpreter v0.1\n\nUsage: {} [file_path]\n\n{}",
-                args[0], HELP_TEXT
+                "Dust Scripting Language Interpreter v0.1\n\nUsage: {} {}\n\n{}",
+                args[0], USAGE, HELP_TEXT
             );
-        } else {
-            process_file(&args[1]);
         }
-    } else {
-        interpreter();
+        Some("--check" | "check") if args.len() == 3 => check_file(&args[2]),
+        Some("--check" | "check" | "--help") => {
+            eprintln!("Usage: {} {}", args[0], USAGE);
+            std::process::exit(1);
+        }
+        // the arguments after the path are passed to the script
+        Some(file_path) => process_file(file_path, args[2..].to_vec()),
     }
 }
 
-fn process_file(file_path: &str) {
-    let input = match fs::read_to_string(file_path) {
+fn read_file(file_path: &str) -> String {
+    match fs::read_to_string(file_path) {
         Ok(text) => text,
         Err(e) => {
             eprintln!("Error reading file '{file_path}': {e}");
             std::process::exit(1);
         }
-    };
+    }
+}
 
+fn process_file(file_path: &str, args: Vec<String>) {
+    let input = read_file(file_path);
     let mut session = Session::new();
-    session.process(&input);
+    session.set_script_path(file_path);
+    session.set_args(args);
+    let success = session.process(&input);
+    if let Some(code) = session.exit_code() {
+        std::process::exit(code);
+    }
+    if !success {
+        std::process::exit(1);
+    }
+}
+
+// Reports every error in the file without running it
+fn check_file(file_path: &str) {
+    let input = read_file(file_path);
+    let mut session = Session::new();
+    session.set_script_path(file_path);
+    if !session.check(&input) {
+        std::process::exit(1);
+    }
 }
 
 fn readline(rl: &mut DefaultEditor, prompt: &str) -> Result<String, ReadlineError> {
@@ -55,6 +76,9 @@ fn interpreter() {
     let mut session = Session::new();
     let mut print_newline = false; // workaround for rustyline deleting the last line
 
+    // when the input is not typed in, an error makes the exit status non-zero
+    let mut status = 0;
+
     if stdin_is_terminal {
         println!("[Dust v0.1]");
     }
@@ -72,7 +96,7 @@ fn interpreter() {
             }
             let mut line = readline(&mut rl, ">>> ").unwrap_or_else(|e| {
                 match e {
-                    ReadlineError::Eof => (),
+                    ReadlineError::Eof => std::process::exit(status),
                     _ => eprintln!("{e}"),
                 }
                 std::process::exit(1);
@@ -87,7 +111,7 @@ fn interpreter() {
 
         match input.as_str() {
             "q" | "Q" | "exit" => {
-                break;
+                std::process::exit(status);
             }
             "clear" => {
                 print!("\x1Bc");
@@ -101,14 +125,25 @@ fn interpreter() {
                 print_newline = false;
             }
             _ => {
-                session.process(&input);
+                if !session.process(&input) && !stdin_is_terminal {
+                    status = 1;
+                }
+                if let Some(code) = session.exit_code() {
+                    std::process::exit(code);
+                }
                 print_newline = true;
             }
         }
     }
 }
 
-const HELP_TEXT: &str = "While in interpreter mode:
+const USAGE: &str = "[--check] [file_path] [args...]";
+
+const HELP_TEXT: &str =
+    "Use '--check' or 'check' before the file path to report its errors without running it.
+Arguments after the file path are passed to the script, which can read them with 'args()'.
+
+While in interpreter mode:
 Use '\\' at the end of a line for multiline input.
 Input 'clear' to clear the screen.
 Input 'reset' to delete all classes, functions and variables.";
@@ -0,0 +1,56 @@
+use crate::error::ErrorKind;
+use crate::session::ParseSession;
+use crate::variable::Value;
+use std::fmt;
+
+// Only values that can be compared and ordered reliably
+// are allowed as keys, which rules out floats and references
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MapKey {
+    Bool(bool),
+    Int(isize),
+    Str(String),
+}
+
+impl MapKey {
+    #[inline]
+    pub fn from_value(value: &Value) -> Option<MapKey> {
+        match value {
+            Value::Bool(b) => Some(MapKey::Bool(*b)),
+            Value::Int(i) => Some(MapKey::Int(*i)),
+            Value::Str(s) => Some(MapKey::Str(s.borrow().clone())),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn to_value(&self) -> Value {
+        match self {
+            MapKey::Bool(b) => Value::Bool(*b),
+            MapKey::Int(i) => Value::Int(*i),
+            MapKey::Str(s) => Value::new_string(s.clone()),
+        }
+    }
+}
+
+impl fmt::Display for MapKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapKey::Bool(b) => write!(f, "{}", b),
+            MapKey::Int(i) => write!(f, "{}", i),
+            MapKey::Str(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+// Convert a value used to access a map into a key
+#[inline]
+pub fn get_key(value: &Value, parse_session: &ParseSession) -> Result<MapKey, ErrorKind> {
+    match MapKey::from_value(value) {
+        Some(key) => Ok(key),
+        None => Err(ErrorKind::InvalidKeyType(
+            parse_session.get_typename(value.typeid()),
+        )),
+    }
+}
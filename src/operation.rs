@@ -1,69 +1,63 @@
-use crate::error::{Context, Error, ErrorKind};
-use crate::function::FunctionCall;
-use crate::instruction::ReturnValue;
-use crate::session::{ExecSession, ParseSession};
+use crate::builtin::TYPEID_RESULT;
+use crate::error::ErrorKind;
+use crate::map;
+use crate::session::ParseSession;
 use crate::token::Operator;
 use crate::variable::Value;
-
-#[derive(Debug)]
-pub enum Operand<'a> {
-    Value(Value),
-    Identifier(&'a str, usize),
-    FunctionCall(&'a FunctionCall, usize),
-}
-
-impl<'a> Operand<'a> {
-    #[inline]
-    pub fn get_value(self, args: &mut OperationArgs) -> Result<Value, Error> {
-        match self {
-            Operand::Value(val) => Ok(val),
-            Operand::Identifier(id, pos) => match args.exec_session.get_variable(id) {
-                Some(var) => Ok(var.get_value().clone()),
-                None => {
-                    return Err(Error::new(args.context, pos, ErrorKind::IdentifierNotFound));
-                }
-            },
-            Operand::FunctionCall(f, pos) => {
-                match f.call(
-                    args.parse_session,
-                    args.exec_session,
-                    None,
-                    args.context,
-                    pos,
-                ) {
-                    Ok(output) => match output {
-                        ReturnValue::Value(value) => Ok(value),
-                        _ => unreachable!("Function calls should not return Break or Return types"),
-                    },
-                    Err(e) => return Err(e),
-                }
-            }
-        }
+use std::rc::Rc;
+
+// Applies a binary operator to values that were already evaluated,
+// used by the virtual machine, compound assignments and the type checker
+pub fn apply_binary(
+    operator: Operator,
+    lhs: Value,
+    rhs: Value,
+    parse_session: &ParseSession,
+) -> Result<Value, ErrorKind> {
+    match operator {
+        Operator::Add => add(lhs, rhs, parse_session),
+        Operator::Sub => subtract(lhs, rhs, parse_session),
+        Operator::Mult => multiply(lhs, rhs, parse_session),
+        Operator::Div => divide(lhs, rhs, parse_session),
+        Operator::Mod => modulo(lhs, rhs, parse_session),
+        Operator::Pow => power(lhs, rhs, parse_session),
+        Operator::And => and(lhs, rhs, parse_session),
+        Operator::Or => or(lhs, rhs, parse_session),
+        Operator::LessThan => less_than(lhs, rhs, parse_session),
+        Operator::GreaterThan => greater_than(lhs, rhs, parse_session),
+        Operator::LessEqual => less_equal(lhs, rhs, parse_session),
+        Operator::GreaterEqual => greater_equal(lhs, rhs, parse_session),
+        Operator::Equal => equal(lhs, rhs, parse_session),
+        Operator::NotEqual => not_equal(lhs, rhs, parse_session),
+        Operator::Is => is(lhs, rhs),
+        Operator::Index => index(lhs, rhs, parse_session),
+        _ => unreachable!("Operator is not a binary operator on values"),
     }
 }
 
-#[derive(Debug)]
-pub struct OperationArgs<'a> {
-    pub stack: &'a mut Vec<Operand<'a>>,
-    pub exec_session: &'a mut ExecSession,
-    pub parse_session: &'a ParseSession,
-    pub private_access_typeid: Option<usize>,
-    pub context: Context,
+pub fn apply_unary(
+    operator: Operator,
+    operand: Value,
+    parse_session: &ParseSession,
+) -> Result<Value, ErrorKind> {
+    match operator {
+        Operator::Neg => negate(operand, parse_session),
+        Operator::Not => not(operand, parse_session),
+        Operator::Typeof => Ok(gettype(operand, parse_session)),
+        _ => unreachable!("Operator is not a unary operator on values"),
+    }
 }
 
 #[inline]
-pub fn add(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
-    let rhs = args.stack.pop().unwrap().get_value(args)?;
-    let lhs = args.stack.pop().unwrap().get_value(args)?;
-
-    let result = match lhs {
+pub fn add(lhs: Value, rhs: Value, parse_session: &ParseSession) -> Result<Value, ErrorKind> {
+    match lhs {
         Value::Int(l) => match rhs {
             Value::Int(r) => Ok(Value::Int(l + r)),
             Value::Float(r) => Ok(Value::Float(l as f64 + r)),
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::Add,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         Value::Float(l) => match rhs {
@@ -71,8 +65,8 @@ pub fn add(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
             Value::Int(r) => Ok(Value::Float(l + r as f64)),
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::Add,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         Value::Str(ref l) => match rhs {
@@ -84,39 +78,28 @@ pub fn add(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
             }
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::Add,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         _ => Err(ErrorKind::InvalidOperationForTypes(
             Operator::Add,
-            args.parse_session.get_typename(lhs.typeid()),
-            args.parse_session.get_typename(rhs.typeid()),
+            parse_session.get_typename(lhs.typeid()),
+            parse_session.get_typename(rhs.typeid()),
         )),
-    };
-
-    match result {
-        Ok(val) => {
-            args.stack.push(Operand::Value(val));
-            Ok(())
-        }
-        Err(e) => Err(Error::new(args.context, pos, e)),
     }
 }
 
 #[inline]
-pub fn subtract(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
-    let rhs = args.stack.pop().unwrap().get_value(args)?;
-    let lhs = args.stack.pop().unwrap().get_value(args)?;
-
-    let result = match lhs {
+pub fn subtract(lhs: Value, rhs: Value, parse_session: &ParseSession) -> Result<Value, ErrorKind> {
+    match lhs {
         Value::Int(l) => match rhs {
             Value::Int(r) => Ok(Value::Int(l - r)),
             Value::Float(r) => Ok(Value::Float(l as f64 - r)),
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::Add,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         Value::Float(l) => match rhs {
@@ -124,40 +107,29 @@ pub fn subtract(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
             Value::Int(r) => Ok(Value::Float(l - r as f64)),
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::Add,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         _ => Err(ErrorKind::InvalidOperationForTypes(
             Operator::Sub,
-            args.parse_session.get_typename(lhs.typeid()),
-            args.parse_session.get_typename(rhs.typeid()),
+            parse_session.get_typename(lhs.typeid()),
+            parse_session.get_typename(rhs.typeid()),
         )),
-    };
-
-    match result {
-        Ok(val) => {
-            args.stack.push(Operand::Value(val));
-            Ok(())
-        }
-        Err(e) => Err(Error::new(args.context, pos, e)),
     }
 }
 
 #[inline]
-pub fn multiply(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
-    let rhs = args.stack.pop().unwrap().get_value(args)?;
-    let lhs = args.stack.pop().unwrap().get_value(args)?;
-
-    let result = match lhs {
+pub fn multiply(lhs: Value, rhs: Value, parse_session: &ParseSession) -> Result<Value, ErrorKind> {
+    match lhs {
         Value::Int(l) => match rhs {
             Value::Int(r) => Ok(Value::Int(l * r)),
             Value::Float(r) => Ok(Value::Float(l as f64 * r)),
             Value::Str(ref r) => Ok(Value::new_string(r.borrow().repeat(l.abs() as usize))),
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::Mult,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         Value::Float(l) => match rhs {
@@ -165,40 +137,29 @@ pub fn multiply(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
             Value::Int(r) => Ok(Value::Float(l * r as f64)),
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::Mult,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         Value::Str(ref l) => match rhs {
             Value::Int(r) => Ok(Value::new_string(l.borrow().repeat(r.abs() as usize))),
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::Mult,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         _ => Err(ErrorKind::InvalidOperationForTypes(
             Operator::Mult,
-            args.parse_session.get_typename(lhs.typeid()),
-            args.parse_session.get_typename(rhs.typeid()),
+            parse_session.get_typename(lhs.typeid()),
+            parse_session.get_typename(rhs.typeid()),
         )),
-    };
-
-    match result {
-        Ok(val) => {
-            args.stack.push(Operand::Value(val));
-            Ok(())
-        }
-        Err(e) => Err(Error::new(args.context, pos, e)),
     }
 }
 
 #[inline]
-pub fn divide(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
-    let rhs = args.stack.pop().unwrap().get_value(args)?;
-    let lhs = args.stack.pop().unwrap().get_value(args)?;
-
-    let result = match lhs {
+pub fn divide(lhs: Value, rhs: Value, parse_session: &ParseSession) -> Result<Value, ErrorKind> {
+    match lhs {
         Value::Int(l) => match rhs {
             Value::Int(r) => {
                 if r != 0 {
@@ -216,8 +177,8 @@ pub fn divide(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
             }
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::Div,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         Value::Float(l) => match rhs {
@@ -237,39 +198,28 @@ pub fn divide(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
             }
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::Div,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         _ => Err(ErrorKind::InvalidOperationForTypes(
             Operator::Div,
-            args.parse_session.get_typename(lhs.typeid()),
-            args.parse_session.get_typename(rhs.typeid()),
+            parse_session.get_typename(lhs.typeid()),
+            parse_session.get_typename(rhs.typeid()),
         )),
-    };
-
-    match result {
-        Ok(val) => {
-            args.stack.push(Operand::Value(val));
-            Ok(())
-        }
-        Err(e) => Err(Error::new(args.context, pos, e)),
     }
 }
 
 #[inline]
-pub fn modulo(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
-    let rhs = args.stack.pop().unwrap().get_value(args)?;
-    let lhs = args.stack.pop().unwrap().get_value(args)?;
-
-    let result = match lhs {
+pub fn modulo(lhs: Value, rhs: Value, parse_session: &ParseSession) -> Result<Value, ErrorKind> {
+    match lhs {
         Value::Int(l) => match rhs {
             Value::Int(r) => Ok(Value::Int(l % r)),
             Value::Float(r) => Ok(Value::Float(l as f64 % r)),
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::Mod,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         Value::Float(l) => match rhs {
@@ -277,39 +227,28 @@ pub fn modulo(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
             Value::Int(r) => Ok(Value::Float(l % r as f64)),
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::Mod,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         _ => Err(ErrorKind::InvalidOperationForTypes(
             Operator::Mod,
-            args.parse_session.get_typename(lhs.typeid()),
-            args.parse_session.get_typename(rhs.typeid()),
+            parse_session.get_typename(lhs.typeid()),
+            parse_session.get_typename(rhs.typeid()),
         )),
-    };
-
-    match result {
-        Ok(val) => {
-            args.stack.push(Operand::Value(val));
-            Ok(())
-        }
-        Err(e) => Err(Error::new(args.context, pos, e)),
     }
 }
 
 #[inline]
-pub fn power(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
-    let rhs = args.stack.pop().unwrap().get_value(args)?;
-    let lhs = args.stack.pop().unwrap().get_value(args)?;
-
-    let result = match lhs {
+pub fn power(lhs: Value, rhs: Value, parse_session: &ParseSession) -> Result<Value, ErrorKind> {
+    match lhs {
         Value::Int(l) => match rhs {
             Value::Int(r) => Ok(Value::Float((l as f64).powf(r as f64))),
             Value::Float(r) => Ok(Value::Float((l as f64).powf(r))),
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::Pow,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         Value::Float(l) => match rhs {
@@ -317,32 +256,21 @@ pub fn power(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
             Value::Int(r) => Ok(Value::Float(l.powf(r as f64))),
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::Pow,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         _ => Err(ErrorKind::InvalidOperationForTypes(
             Operator::Pow,
-            args.parse_session.get_typename(lhs.typeid()),
-            args.parse_session.get_typename(rhs.typeid()),
+            parse_session.get_typename(lhs.typeid()),
+            parse_session.get_typename(rhs.typeid()),
         )),
-    };
-
-    match result {
-        Ok(val) => {
-            args.stack.push(Operand::Value(val));
-            Ok(())
-        }
-        Err(e) => Err(Error::new(args.context, pos, e)),
     }
 }
 
 #[inline]
-pub fn and(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
-    let rhs = args.stack.pop().unwrap().get_value(args)?;
-    let lhs = args.stack.pop().unwrap().get_value(args)?;
-
-    let result = match lhs {
+pub fn and(lhs: Value, rhs: Value, parse_session: &ParseSession) -> Result<Value, ErrorKind> {
+    match lhs {
         Value::Bool(l) => match rhs {
             Value::Bool(r) => {
                 if l != false && r != false {
@@ -353,32 +281,21 @@ pub fn and(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
             }
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::And,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         _ => Err(ErrorKind::InvalidOperationForTypes(
             Operator::And,
-            args.parse_session.get_typename(lhs.typeid()),
-            args.parse_session.get_typename(rhs.typeid()),
+            parse_session.get_typename(lhs.typeid()),
+            parse_session.get_typename(rhs.typeid()),
         )),
-    };
-
-    match result {
-        Ok(val) => {
-            args.stack.push(Operand::Value(val));
-            Ok(())
-        }
-        Err(e) => Err(Error::new(args.context, pos, e)),
     }
 }
 
 #[inline]
-pub fn or(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
-    let rhs = args.stack.pop().unwrap().get_value(args)?;
-    let lhs = args.stack.pop().unwrap().get_value(args)?;
-
-    let result = match lhs {
+pub fn or(lhs: Value, rhs: Value, parse_session: &ParseSession) -> Result<Value, ErrorKind> {
+    match lhs {
         Value::Bool(l) => match rhs {
             Value::Bool(r) => {
                 if l != false || r != false {
@@ -389,32 +306,21 @@ pub fn or(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
             }
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::Or,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         _ => Err(ErrorKind::InvalidOperationForTypes(
             Operator::Or,
-            args.parse_session.get_typename(lhs.typeid()),
-            args.parse_session.get_typename(rhs.typeid()),
+            parse_session.get_typename(lhs.typeid()),
+            parse_session.get_typename(rhs.typeid()),
         )),
-    };
-
-    match result {
-        Ok(val) => {
-            args.stack.push(Operand::Value(val));
-            Ok(())
-        }
-        Err(e) => Err(Error::new(args.context, pos, e)),
     }
 }
 
 #[inline]
-pub fn less_than(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
-    let rhs = args.stack.pop().unwrap().get_value(args)?;
-    let lhs = args.stack.pop().unwrap().get_value(args)?;
-
-    let result = match lhs {
+pub fn less_than(lhs: Value, rhs: Value, parse_session: &ParseSession) -> Result<Value, ErrorKind> {
+    match lhs {
         Value::Int(l) => match rhs {
             Value::Int(r) => {
                 if l < r {
@@ -432,8 +338,8 @@ pub fn less_than(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
             }
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::LessThan,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         Value::Float(l) => match rhs {
@@ -453,40 +359,33 @@ pub fn less_than(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
             }
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::LessThan,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         Value::Str(ref l) => match rhs {
             Value::Str(ref r) => Ok(Value::Bool(l.borrow().as_str() < r.borrow().as_str())),
             _ => Err(ErrorKind::InvalidOperationForTypes(
                 Operator::LessThan,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         _ => Err(ErrorKind::InvalidOperationForTypes(
             Operator::LessThan,
-            args.parse_session.get_typename(lhs.typeid()),
-            args.parse_session.get_typename(rhs.typeid()),
+            parse_session.get_typename(lhs.typeid()),
+            parse_session.get_typename(rhs.typeid()),
         )),
-    };
-
-    match result {
-        Ok(val) => {
-            args.stack.push(Operand::Value(val));
-            Ok(())
-        }
-        Err(e) => Err(Error::new(args.context, pos, e)),
     }
 }
 
 #[inline]
-pub fn greater_than(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
-    let rhs = args.stack.pop().unwrap().get_value(args)?;
-    let lhs = args.stack.pop().unwrap().get_value(args)?;
-
-    let result = match lhs {
+pub fn greater_than(
+    lhs: Value,
+    rhs: Value,
+    parse_session: &ParseSession,
+) -> Result<Value, ErrorKind> {
+    match lhs {
         Value::Int(l) => match rhs {
             Value::Int(r) => {
                 if l > r {
@@ -503,9 +402,9 @@ pub fn greater_than(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
                 }
             }
             _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::LessThan,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                Operator::GreaterThan,
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         Value::Float(l) => match rhs {
@@ -524,296 +423,336 @@ pub fn greater_than(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
                 }
             }
             _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::LessThan,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                Operator::GreaterThan,
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         Value::Str(ref l) => match rhs {
             Value::Str(ref r) => Ok(Value::Bool(l.borrow().as_str() > r.borrow().as_str())),
             _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::LessThan,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                Operator::GreaterThan,
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         _ => Err(ErrorKind::InvalidOperationForTypes(
             Operator::GreaterThan,
-            args.parse_session.get_typename(lhs.typeid()),
-            args.parse_session.get_typename(rhs.typeid()),
+            parse_session.get_typename(lhs.typeid()),
+            parse_session.get_typename(rhs.typeid()),
         )),
-    };
-
-    match result {
-        Ok(val) => {
-            args.stack.push(Operand::Value(val));
-            Ok(())
-        }
-        Err(e) => Err(Error::new(args.context, pos, e)),
     }
 }
 
 #[inline]
-pub fn equal(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
-    let rhs = args.stack.pop().unwrap().get_value(args)?;
-    let lhs = args.stack.pop().unwrap().get_value(args)?;
-
-    let result = match lhs {
+pub fn less_equal(
+    lhs: Value,
+    rhs: Value,
+    parse_session: &ParseSession,
+) -> Result<Value, ErrorKind> {
+    match lhs {
         Value::Int(l) => match rhs {
             Value::Int(r) => {
-                if l == r {
+                if l <= r {
                     Ok(Value::Bool(true))
                 } else {
                     Ok(Value::Bool(false))
                 }
             }
             Value::Float(r) => {
-                if (l as f64) == r {
+                if (l as f64) <= r {
                     Ok(Value::Bool(true))
                 } else {
                     Ok(Value::Bool(false))
                 }
             }
-            Value::None => Ok(Value::Bool(false)),
             _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::Equal,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                Operator::LessEqual,
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         Value::Float(l) => match rhs {
             Value::Float(r) => {
-                if l == r {
+                if l <= r {
                     Ok(Value::Bool(true))
                 } else {
                     Ok(Value::Bool(false))
                 }
             }
             Value::Int(r) => {
-                if l == (r as f64) {
+                if l <= (r as f64) {
                     Ok(Value::Bool(true))
                 } else {
                     Ok(Value::Bool(false))
                 }
             }
-            Value::None => Ok(Value::Bool(false)),
             _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::Equal,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                Operator::LessEqual,
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         Value::Str(ref l) => match rhs {
-            Value::Str(ref r) => {
-                if *l.borrow() == *r.borrow() {
-                    Ok(Value::Bool(true))
-                } else {
-                    Ok(Value::Bool(false))
-                }
-            }
-            Value::None => Ok(Value::Bool(false)),
+            Value::Str(ref r) => Ok(Value::Bool(l.borrow().as_str() <= r.borrow().as_str())),
             _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::Equal,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                Operator::LessEqual,
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
-        Value::Bool(l) => match rhs {
-            Value::Bool(r) => {
-                if l == r {
-                    Ok(Value::Bool(true))
-                } else {
-                    Ok(Value::Bool(false))
-                }
-            }
-            Value::None => Ok(Value::Bool(false)),
-            _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::Equal,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
-            )),
-        },
-        Value::None => match rhs {
-            Value::None => Ok(Value::Bool(true)),
-            _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::Equal,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
-            )),
-        },
-        Value::Class(ref l) => match rhs {
-            Value::Class(ref r) => Ok(Value::Bool(std::rc::Rc::ptr_eq(l, r))),
-            Value::None => Ok(Value::Bool(false)),
-            _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::Equal,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
-            )),
-        },
-        Value::Vector(ref l) => match rhs {
-            Value::Vector(ref r) => Ok(Value::Bool(std::rc::Rc::ptr_eq(l, r))),
-            Value::None => Ok(Value::Bool(false)),
-            _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::Equal,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
-            )),
-        },
-    };
-
-    match result {
-        Ok(val) => {
-            args.stack.push(Operand::Value(val));
-            Ok(())
-        }
-        Err(e) => Err(Error::new(args.context, pos, e)),
+        _ => Err(ErrorKind::InvalidOperationForTypes(
+            Operator::LessEqual,
+            parse_session.get_typename(lhs.typeid()),
+            parse_session.get_typename(rhs.typeid()),
+        )),
     }
 }
 
 #[inline]
-pub fn not_equal(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
-    let rhs = args.stack.pop().unwrap().get_value(args)?;
-    let lhs = args.stack.pop().unwrap().get_value(args)?;
-
-    let result = match lhs {
+pub fn greater_equal(
+    lhs: Value,
+    rhs: Value,
+    parse_session: &ParseSession,
+) -> Result<Value, ErrorKind> {
+    match lhs {
         Value::Int(l) => match rhs {
             Value::Int(r) => {
-                if l != r {
+                if l >= r {
                     Ok(Value::Bool(true))
                 } else {
                     Ok(Value::Bool(false))
                 }
             }
             Value::Float(r) => {
-                if (l as f64) != r {
+                if (l as f64) >= r {
                     Ok(Value::Bool(true))
                 } else {
                     Ok(Value::Bool(false))
                 }
             }
-            Value::None => Ok(Value::Bool(true)),
             _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::NotEqual,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                Operator::GreaterEqual,
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         Value::Float(l) => match rhs {
             Value::Float(r) => {
-                if l != r {
+                if l >= r {
                     Ok(Value::Bool(true))
                 } else {
                     Ok(Value::Bool(false))
                 }
             }
             Value::Int(r) => {
-                if l != (r as f64) {
+                if l >= (r as f64) {
                     Ok(Value::Bool(true))
                 } else {
                     Ok(Value::Bool(false))
                 }
             }
-            Value::None => Ok(Value::Bool(true)),
             _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::NotEqual,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                Operator::GreaterEqual,
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
         Value::Str(ref l) => match rhs {
-            Value::Str(ref r) => {
-                if *l.borrow() != *r.borrow() {
-                    Ok(Value::Bool(true))
-                } else {
-                    Ok(Value::Bool(false))
-                }
-            }
-            Value::None => Ok(Value::Bool(true)),
+            Value::Str(ref r) => Ok(Value::Bool(l.borrow().as_str() >= r.borrow().as_str())),
             _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::NotEqual,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
+                Operator::GreaterEqual,
+                parse_session.get_typename(lhs.typeid()),
+                parse_session.get_typename(rhs.typeid()),
             )),
         },
-        Value::Bool(l) => match rhs {
-            Value::Bool(r) => {
-                if l != r {
-                    Ok(Value::Bool(true))
-                } else {
-                    Ok(Value::Bool(false))
+        _ => Err(ErrorKind::InvalidOperationForTypes(
+            Operator::GreaterEqual,
+            parse_session.get_typename(lhs.typeid()),
+            parse_session.get_typename(rhs.typeid()),
+        )),
+    }
+}
+
+#[inline]
+pub fn equal(lhs: Value, rhs: Value, parse_session: &ParseSession) -> Result<Value, ErrorKind> {
+    check_comparable(&lhs, &rhs, Operator::Equal, parse_session)?;
+    let result: Result<bool, ErrorKind> = values_equal(&lhs, &rhs, &mut |_, _| Ok(None));
+    Ok(Value::Bool(result?))
+}
+
+#[inline]
+pub fn not_equal(lhs: Value, rhs: Value, parse_session: &ParseSession) -> Result<Value, ErrorKind> {
+    check_comparable(&lhs, &rhs, Operator::NotEqual, parse_session)?;
+    let result: Result<bool, ErrorKind> = values_equal(&lhs, &rhs, &mut |_, _| Ok(None));
+    Ok(Value::Bool(!result?))
+}
+
+// Values of different types can only be compared if one of them is a number
+// and the other one is too, or if the right hand side is none
+pub fn check_comparable(
+    lhs: &Value,
+    rhs: &Value,
+    operator: Operator,
+    parse_session: &ParseSession,
+) -> Result<(), ErrorKind> {
+    let is_comparable = match (lhs, rhs) {
+        (Value::None, rhs) => matches!(rhs, Value::None),
+        (_, Value::None) => true,
+        (Value::Int(_) | Value::Float(_), Value::Int(_) | Value::Float(_)) => true,
+        (lhs, rhs) => std::mem::discriminant(lhs) == std::mem::discriminant(rhs),
+    };
+    if is_comparable {
+        Ok(())
+    } else {
+        Err(ErrorKind::InvalidOperationForTypes(
+            operator,
+            parse_session.get_typename(lhs.typeid()),
+            parse_session.get_typename(rhs.typeid()),
+        ))
+    }
+}
+
+// Vecs are equal if their items are, Maps if they have the same keys with
+// equal values and class instances if they are of the same type and their
+// properties are equal, values of different types inside of them are not equal.
+// `class_eq` is asked first about every pair of class instances and returns
+// None if the class does not define a comparison.
+pub fn values_equal<E, F>(lhs: &Value, rhs: &Value, class_eq: &mut F) -> Result<bool, E>
+where
+    F: FnMut(&Value, &Value) -> Result<Option<bool>, E>,
+{
+    let mut visited = Vec::new();
+    compare(lhs, rhs, class_eq, &mut visited)
+}
+
+fn compare<E, F>(
+    lhs: &Value,
+    rhs: &Value,
+    class_eq: &mut F,
+    // pairs of containers that are being compared, a pair that is reached
+    // again inside of itself does not make its containers different
+    visited: &mut Vec<(*const (), *const ())>,
+) -> Result<bool, E>
+where
+    F: FnMut(&Value, &Value) -> Result<Option<bool>, E>,
+{
+    let (pair, items) = match (lhs, rhs) {
+        (Value::None, Value::None) => return Ok(true),
+        (Value::Int(l), Value::Int(r)) => return Ok(l == r),
+        (Value::Int(l), Value::Float(r)) => return Ok((*l as f64) == *r),
+        (Value::Float(l), Value::Int(r)) => return Ok(*l == (*r as f64)),
+        (Value::Float(l), Value::Float(r)) => return Ok(l == r),
+        (Value::Str(l), Value::Str(r)) => return Ok(*l.borrow() == *r.borrow()),
+        (Value::Bool(l), Value::Bool(r)) => return Ok(l == r),
+        (Value::Function(l), Value::Function(r)) => return Ok(l.ptr_eq(r)),
+        (Value::File(l), Value::File(r)) => return Ok(Rc::ptr_eq(l, r)),
+        (Value::Vector(l), Value::Vector(r)) => {
+            if l.borrow().len() != r.borrow().len() {
+                return Ok(false);
+            }
+            // the contents are copied so a comparison is free to modify them
+            let items: Vec<(Value, Value)> = l
+                .borrow()
+                .iter()
+                .cloned()
+                .zip(r.borrow().iter().cloned())
+                .collect();
+            (pointers(l, r), items)
+        }
+        (Value::Map(l), Value::Map(r)) => {
+            if l.borrow().len() != r.borrow().len() {
+                return Ok(false);
+            }
+            // both Maps keep their keys in the same order
+            let mut items = Vec::new();
+            for ((l_key, l_value), (r_key, r_value)) in l.borrow().iter().zip(r.borrow().iter()) {
+                if l_key != r_key {
+                    return Ok(false);
                 }
+                items.push((l_value.clone(), r_value.clone()));
             }
-            Value::None => Ok(Value::Bool(true)),
-            _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::NotEqual,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
-            )),
-        },
-        Value::None => match rhs {
-            Value::None => Ok(Value::Bool(false)),
-            _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::NotEqual,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
-            )),
-        },
-        Value::Class(ref l) => match rhs {
-            Value::Class(ref r) => Ok(Value::Bool(!std::rc::Rc::ptr_eq(l, r))),
-            Value::None => Ok(Value::Bool(true)),
-            _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::NotEqual,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
-            )),
-        },
-        Value::Vector(ref l) => match rhs {
-            Value::Vector(ref r) => Ok(Value::Bool(!std::rc::Rc::ptr_eq(l, r))),
-            Value::None => Ok(Value::Bool(true)),
-            _ => Err(ErrorKind::InvalidOperationForTypes(
-                Operator::NotEqual,
-                args.parse_session.get_typename(lhs.typeid()),
-                args.parse_session.get_typename(rhs.typeid()),
-            )),
-        },
+            (pointers(l, r), items)
+        }
+        (Value::Class(l), Value::Class(r)) => {
+            if let Some(result) = class_eq(lhs, rhs)? {
+                return Ok(result);
+            }
+            let (l_instance, r_instance) = (l.borrow(), r.borrow());
+            if l_instance.typeid() != r_instance.typeid() {
+                return Ok(false);
+            }
+            // whether a Result was checked does not change the value it holds
+            let is_result = l_instance.typeid() == TYPEID_RESULT;
+            let mut items = Vec::new();
+            for (name, value) in l_instance.properties() {
+                if is_result && name == "is_checked" {
+                    continue;
+                }
+                match r_instance.property_value(name) {
+                    Some(other) => items.push((value.clone(), other.clone())),
+                    None => return Ok(false),
+                }
+            }
+            (pointers(l, r), items)
+        }
+        _ => return Ok(false),
     };
 
-    match result {
-        Ok(val) => {
-            args.stack.push(Operand::Value(val));
-            Ok(())
+    if pair.0 == pair.1 || visited.contains(&pair) {
+        return Ok(true);
+    }
+    visited.push(pair);
+    for (l, r) in items.iter() {
+        if !compare(l, r, class_eq, visited)? {
+            visited.pop();
+            return Ok(false);
         }
-        Err(e) => Err(Error::new(args.context, pos, e)),
     }
+    visited.pop();
+    Ok(true)
+}
+
+#[inline]
+fn pointers<T>(lhs: &Rc<T>, rhs: &Rc<T>) -> (*const (), *const ()) {
+    (Rc::as_ptr(lhs) as *const (), Rc::as_ptr(rhs) as *const ())
 }
 
+// Whether both sides are the same object, values that are
+// not shared by reference are identical if they are equal
 #[inline]
-pub fn negate(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
-    let operand = args.stack.pop().unwrap().get_value(args)?;
+pub fn is(lhs: Value, rhs: Value) -> Result<Value, ErrorKind> {
+    let result = match (&lhs, &rhs) {
+        (Value::Vector(l), Value::Vector(r)) => Rc::ptr_eq(l, r),
+        (Value::Map(l), Value::Map(r)) => Rc::ptr_eq(l, r),
+        (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+        (Value::File(l), Value::File(r)) => Rc::ptr_eq(l, r),
+        (Value::Function(l), Value::Function(r)) => l.ptr_eq(r),
+        (Value::None, Value::None) => true,
+        (Value::Int(l), Value::Int(r)) => l == r,
+        (Value::Float(l), Value::Float(r)) => l == r,
+        (Value::Str(l), Value::Str(r)) => *l.borrow() == *r.borrow(),
+        (Value::Bool(l), Value::Bool(r)) => l == r,
+        _ => false,
+    };
+    Ok(Value::Bool(result))
+}
 
-    let result = match operand {
+#[inline]
+pub fn negate(operand: Value, parse_session: &ParseSession) -> Result<Value, ErrorKind> {
+    match operand {
         Value::Int(n) => Ok(Value::Int(n * (-1))),
         Value::Float(n) => Ok(Value::Float(n * (-1.0_f64))),
         _ => Err(ErrorKind::InvalidOperationForType(
             Operator::Neg,
-            args.parse_session.get_typename(operand.typeid()),
+            parse_session.get_typename(operand.typeid()),
         )),
-    };
-
-    match result {
-        Ok(val) => {
-            args.stack.push(Operand::Value(val));
-            Ok(())
-        }
-        Err(e) => Err(Error::new(args.context, pos, e)),
     }
 }
 
 #[inline]
-pub fn not(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
-    let operand = args.stack.pop().unwrap().get_value(args)?;
-
-    let result = match operand {
+pub fn not(operand: Value, parse_session: &ParseSession) -> Result<Value, ErrorKind> {
+    match operand {
         Value::Bool(b) => {
             if b == false {
                 Ok(Value::Bool(true))
@@ -823,99 +762,87 @@ pub fn not(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
         }
         _ => Err(ErrorKind::InvalidOperationForType(
             Operator::Not,
-            args.parse_session.get_typename(operand.typeid()),
+            parse_session.get_typename(operand.typeid()),
         )),
-    };
-
-    match result {
-        Ok(val) => {
-            args.stack.push(Operand::Value(val));
-            Ok(())
-        }
-        Err(e) => Err(Error::new(args.context, pos, e)),
     }
 }
 
 #[inline]
-pub fn member_access(args: &mut OperationArgs, pos: usize) -> Result<(), Error> {
-    let rhs_operand = args.stack.pop().unwrap();
-    let lhs_operand = args.stack.pop().unwrap();
+pub fn index(lhs: Value, rhs: Value, parse_session: &ParseSession) -> Result<Value, ErrorKind> {
+    get_item(&lhs, &rhs, parse_session)
+}
 
-    let lhs = lhs_operand.get_value(args)?;
-    let private_access = if let Some(id) = args.private_access_typeid {
-        if id == lhs.typeid() {
-            true
-        } else {
-            false
+// Shared by the index operator and index assignment
+
+pub fn get_item(
+    container: &Value,
+    index: &Value,
+    parse_session: &ParseSession,
+) -> Result<Value, ErrorKind> {
+    match (container, index) {
+        (Value::Vector(v), Value::Int(i)) => {
+            let vec = v.borrow();
+            if *i < 0 || *i >= vec.len() as isize {
+                Err(ErrorKind::IndexOutOfRange(*i, vec.len()))
+            } else {
+                Ok(vec[*i as usize].clone())
+            }
         }
-    } else {
-        false
-    };
-
-    let value = match rhs_operand {
-        Operand::Identifier(name, idpos) => match lhs {
-            Value::Class(c) => {
-                match c
-                    .borrow()
-                    .get_property(name, private_access, args.parse_session)
-                {
-                    Ok(var) => var.get_value_clone(),
-                    Err(errorkind) => {
-                        return Err(Error::new(args.context, idpos, errorkind));
-                    }
-                }
+        (Value::Str(s), Value::Int(i)) => {
+            let string = s.borrow();
+            let len = string.chars().count();
+            if *i < 0 || *i >= len as isize {
+                Err(ErrorKind::IndexOutOfRange(*i, len))
+            } else {
+                let ch = string.chars().nth(*i as usize).unwrap();
+                Ok(Value::new_string(ch.to_string()))
             }
-            _ => {
-                return Err(Error::new(
-                    args.context,
-                    pos,
-                    ErrorKind::InvalidMemberAccess,
-                ));
+        }
+        (Value::Map(m), _) => {
+            let key = map::get_key(index, parse_session)?;
+            match m.borrow().get(&key) {
+                Some(value) => Ok(value.clone()),
+                None => Err(ErrorKind::KeyNotFound(key.to_string())),
             }
-        },
-        Operand::FunctionCall(f, _pos) => {
-            if let Some(_) = f.associated_typeid() {
-                return Err(Error::new(
-                    f.context(),
-                    f.scope_res_pos().unwrap(),
-                    ErrorKind::InvalidScopeAccess,
-                ));
+        }
+        _ => Err(ErrorKind::InvalidOperationForTypes(
+            Operator::Index,
+            parse_session.get_typename(container.typeid()),
+            parse_session.get_typename(index.typeid()),
+        )),
+    }
+}
+
+pub fn set_item(
+    container: &Value,
+    index: &Value,
+    value: Value,
+    parse_session: &ParseSession,
+) -> Result<(), ErrorKind> {
+    match (container, index) {
+        (Value::Vector(v), Value::Int(i)) => {
+            let mut vec = v.borrow_mut();
+            if *i < 0 || *i >= vec.len() as isize {
+                Err(ErrorKind::IndexOutOfRange(*i, vec.len()))
             } else {
-                match f.call(
-                    args.parse_session,
-                    args.exec_session,
-                    Some(lhs.clone()),
-                    args.context,
-                    f.context().start - args.context.start,
-                ) {
-                    Ok(output) => match output {
-                        ReturnValue::Value(value) => value,
-                        _ => unreachable!("Function calls should not return Break or Return types"),
-                    },
-                    Err(e) => return Err(e),
-                }
+                vec[*i as usize] = value;
+                Ok(())
             }
         }
-        Operand::Value(_) => {
-            return Err(Error::new(
-                args.context,
-                pos,
-                ErrorKind::InvalidMemberAccess,
-            ));
+        (Value::Map(m), _) => {
+            let key = map::get_key(index, parse_session)?;
+            m.borrow_mut().insert(key, value);
+            Ok(())
         }
-    };
-
-    args.stack.push(Operand::Value(value));
-    Ok(())
+        _ => Err(ErrorKind::InvalidOperationForTypes(
+            Operator::Index,
+            parse_session.get_typename(container.typeid()),
+            parse_session.get_typename(index.typeid()),
+        )),
+    }
 }
 
 #[inline]
-pub fn gettype(args: &mut OperationArgs) -> Result<(), Error> {
-    let operand = args.stack.pop().unwrap().get_value(args)?;
-
-    args.stack.push(Operand::Value(Value::new_string(
-        args.parse_session.get_typename(operand.typeid()),
-    )));
-
-    Ok(())
+pub fn gettype(operand: Value, parse_session: &ParseSession) -> Value {
+    Value::new_string(parse_session.get_typename(operand.typeid()))
 }
@@ -1,17 +1,21 @@
 use crate::branch::{Branch, BranchBody};
 use crate::builtin::{DYN_KEYWORD, TYPEID_DYN, TYPEID_NONE};
-use crate::class::{ClassDefinition, ClassFunction, PropertyDefinition};
-use crate::error::{Context, Error, ErrorKind};
+use crate::class::{ClassDefinition, ClassFunction, MethodSignature, PropertyDefinition};
+use crate::error::{Context, Error, ErrorKind, SyntaxErrorDetails};
 use crate::expression::Expression;
 use crate::for_loop::ForLoop;
-use crate::function::{Function, FunctionCall, Return, UserFunction};
-use crate::instruction::{Break, Instruction, VariableAssign, VariableInit};
+use crate::function::{CallArguments, Function, FunctionCall, Return, UserFunction};
+use crate::instruction::{
+    Break, Continue, GlobalDeclaration, IndexAssign, Instruction, VariableAssign, VariableInit,
+};
+use crate::literal::{InterpolationPart, MapLiteral, StringInterpolation, VecLiteral};
 use crate::session::ParseSession;
 use crate::token::{Operator, Parenthesis, Token, TokenKind};
 use crate::variable::{AnnotatedIdentifier, OptionallyAnnotatedIdentifier};
 use crate::while_loop::WhileLoop;
 
 use std::collections::{HashMap, HashSet};
+use std::fs;
 
 use pest::{iterators::Pair, iterators::Pairs, Parser};
 use pest_derive::Parser;
@@ -20,77 +24,675 @@ use pest_derive::Parser;
 #[grammar = "grammar.pest"]
 struct DustParser;
 
-pub fn parse(input: &str, session: &mut ParseSession) -> Result<Vec<Instruction>, Error> {
+pub fn parse(input: &str, session: &mut ParseSession) -> Result<Vec<Instruction>, Vec<Error>> {
+    into_result(parse_source(input, session, None, false))
+}
+
+// Returns the statements that were built along with every error, the definitions
+// among them are kept even if there are syntax errors so the rest can be checked
+pub fn parse_partial(input: &str, session: &mut ParseSession) -> (Vec<Instruction>, Vec<Error>) {
+    parse_source(input, session, None, true)
+}
+
+// Parse the contents of an imported file, its functions
+// and classes are placed into the given namespace
+fn parse_module(
+    input: &str,
+    session: &mut ParseSession,
+    file_name: &str,
+    namespace: &str,
+) -> Result<Vec<Instruction>, Vec<Error>> {
+    into_result(parse_source(
+        input,
+        session,
+        Some((file_name, namespace)),
+        false,
+    ))
+}
+
+#[inline]
+fn into_result(parsed: (Vec<Instruction>, Vec<Error>)) -> Result<Vec<Instruction>, Vec<Error>> {
+    match parsed {
+        (instructions, errors) if errors.is_empty() => Ok(instructions),
+        (_, errors) => Err(errors),
+    }
+}
+
+fn parse_source(
+    input: &str,
+    session: &mut ParseSession,
+    module: Option<(&str, &str)>,
+    keep_partial: bool,
+) -> (Vec<Instruction>, Vec<Error>) {
     let input = input.to_string();
     session.append_source_code(&input);
-    let parse_result = DustParser::parse(Rule::start_symbol, &input);
-    let parse_content;
+    if let Some((file_name, _)) = module {
+        let start = session.get_source_code_offset();
+        let end = session.get_source_code_len();
+        session.add_source_file(file_name, start, end);
+    }
+
+    match DustParser::parse(Rule::start_symbol, &input) {
+        Ok(mut content) => build_source(content.next().unwrap(), &input, session, module),
+        Err(_) => recover(&input, session, module, keep_partial),
+    }
+}
 
-    match parse_result {
-        Ok(mut content) => {
-            parse_content = content.next().unwrap();
+fn build_source(
+    parse_content: Pair<Rule>,
+    input: &str,
+    session: &mut ParseSession,
+    module: Option<(&str, &str)>,
+) -> (Vec<Instruction>, Vec<Error>) {
+    let offset = session.get_source_code_offset();
+    match module {
+        Some((_, namespace)) => {
+            // lines of an imported file are counted from its own beginning
+            let mut builder = Builder::new(offset, input, offset, session);
+            builder.namespace = Some(namespace.to_string());
+            builder.build(parse_content, session)
+        }
+        None => {
+            let full_text = session.get_source_code().to_string() + input;
+            let mut builder = Builder::new(offset, &full_text, 0, session);
+            builder.build(parse_content, session)
         }
-        Err(e) => {
-            let (lines, column) = match e.line_col {
-                pest::error::LineColLocation::Pos((line, column)) => (line, column),
-                pest::error::LineColLocation::Span((line, _), (column, _)) => (line, column),
+    }
+}
+
+// Finds every syntax error in the input. After an error the statement it is
+// in is skipped and parsing continues from the next one. The statements
+// without syntax errors are built as well to find the rest of the errors,
+// but nothing is added to the session unless the partial result is kept
+fn recover(
+    input: &str,
+    session: &mut ParseSession,
+    module: Option<(&str, &str)>,
+    keep_partial: bool,
+) -> (Vec<Instruction>, Vec<Error>) {
+    let offset = session.get_source_code_offset();
+    let mut errors = Vec::new();
+
+    // the parser only records what it expected when this is enabled,
+    // it is left on because other threads may be recovering as well
+    pest::set_error_detail(true);
+
+    // statements with syntax errors are replaced with a placeholder expression,
+    // so the whitespace is not counted as the end of the statement before them
+    let mut valid = input.to_string();
+    let mut pos = 0;
+
+    while pos < input.len() {
+        // the text before the next statement is blanked out to keep the positions
+        let remaining = " ".repeat(pos) + &input[pos..];
+        let mut error = match DustParser::parse(Rule::start_symbol, &remaining) {
+            Ok(_) => break,
+            Err(e) => e,
+        };
+        let (mut error_pos, mut attempts) = inspect(&error, 0);
+        error_pos = error_pos.min(input.len());
+
+        // a statement that starts with a keyword may have been read as something else,
+        // in which case the error is found earlier by parsing the statement on its own
+        let start = find_statement_start(input, pos, error_pos);
+        for statement_start in find_inner_statements(input, start, error_pos) {
+            if let Some(statement_error) = parse_keyword_statement(input, statement_start) {
+                let (statement_pos, statement_attempts) =
+                    inspect(&statement_error, statement_start);
+                if statement_pos < error_pos {
+                    (error, error_pos, attempts) =
+                        (statement_error, statement_pos, statement_attempts);
+                }
+            }
+        }
+        errors.push(syntax_error(input, error_pos, &error, attempts, offset));
+
+        let end = find_statement_end(input, start, error_pos);
+        if start < end {
+            valid.replace_range(start..end, &placeholder(&input[start..end]));
+        }
+        pos = end;
+    }
+
+    let snapshot = session.clone();
+    let mut instructions = Vec::new();
+    if let Ok(mut content) = DustParser::parse(Rule::start_symbol, &valid) {
+        let (built, e) = build_source(content.next().unwrap(), &valid, session, module);
+        instructions = built;
+        errors.extend(e);
+    }
+    if !keep_partial {
+        *session = snapshot;
+        instructions.clear();
+    }
+
+    errors.sort_by_key(|e| e.position());
+    (instructions, errors)
+}
+
+// Position of the error in the input that starts at the given position,
+// along with what the parser expected there
+fn inspect(error: &pest::error::Error<Rule>, start: usize) -> (usize, Option<Attempts>) {
+    let attempts = parse_attempts(error);
+    let pos = match attempts {
+        Some(ref attempts) => attempts.position,
+        None => match error.location {
+            pest::error::InputLocation::Pos(pos) => pos,
+            pest::error::InputLocation::Span((span_start, _)) => span_start,
+        },
+    };
+    (start + pos, attempts)
+}
+
+// Keywords parse as identifiers when the statement they start is malformed,
+// so the statement is parsed again on its own to find out what is wrong with it
+fn parse_keyword_statement(input: &str, pos: usize) -> Option<pest::error::Error<Rule>> {
+    let keyword = input[pos..]
+        .split(|ch: char| !ch.is_alphanumeric() && ch != '_')
+        .next()
+        .unwrap_or_default();
+    let rule = match keyword {
+        "if" => Rule::branch,
+        "while" => Rule::while_loop,
+        "for" => Rule::for_loop,
+        "fn" => Rule::function_definition,
+        "class" => Rule::class_definition,
+        "interface" => Rule::interface_definition,
+        "import" => Rule::import_statement,
+        "const" => Rule::const_definition,
+        "global" => Rule::global_declaration,
+        _ => return None,
+    };
+
+    pest::set_error_detail(true);
+    DustParser::parse(rule, &input[pos..]).err()
+}
+
+// What the parser tried to match at the furthest position it reached
+struct Attempts {
+    position: usize,
+    tokens: Vec<String>,
+    // the rules that failed to match
+    deepest: Vec<Rule>,
+    // the rules that contain them
+    parents: Vec<Rule>,
+}
+
+fn parse_attempts(error: &pest::error::Error<Rule>) -> Option<Attempts> {
+    let attempts = error.parse_attempts()?;
+    let tokens: Vec<String> = attempts
+        .expected_tokens()
+        .iter()
+        .map(|token| token.to_string())
+        .filter(|token| !IGNORED_TOKENS.contains(&token.as_str()))
+        .collect();
+
+    let mut deepest = Vec::new();
+    let mut parents = Vec::new();
+    for stack in attempts.call_stacks() {
+        if let Some(rule) = stack.deepest.get_rule() {
+            if !deepest.contains(rule) {
+                deepest.push(*rule);
+            }
+        }
+        if let Some(rule) = stack.parent {
+            if !parents.contains(&rule) {
+                parents.push(rule);
+            }
+        }
+    }
+
+    if tokens.is_empty() && deepest.is_empty() {
+        return None;
+    }
+    Some(Attempts {
+        position: attempts.max_position,
+        tokens,
+        deepest,
+        parents,
+    })
+}
+
+// whitespace, comments and semicolons are allowed almost anywhere
+const IGNORED_TOKENS: [&str; 8] = [" ", "\t", "\n", "\r", "\r\n", "//", "/*", ";"];
+
+const STATEMENT_TOKENS: [&str; 12] = [
+    "let",
+    "const",
+    "global",
+    "if",
+    "while",
+    "for",
+    "return",
+    "break",
+    "continue",
+    "class",
+    "interface",
+    "import",
+];
+
+// tokens that can only start an expression
+const EXPRESSION_TOKENS: [&str; 6] = ["\"", "true", "false", "none", "not", "typeof"];
+
+// tokens that start an expression, but can come after an operand as well
+const OPERAND_TOKENS: [&str; 5] = ["(", "[", "{", "-", "fn"];
+
+// tokens that can come after an operand, the ones that can start an expression
+// or belong to an assignment are at the end
+const OPERATOR_TOKENS: [&str; 24] = [
+    "+", "*", "/", "%", "^", "<", ">", "<=", ">=", "==", "!=", "and", "or", "is", ".", "-", "(",
+    "[", "=", "+=", "-=", "*=", "/=", "::",
+];
+
+const NAME_TOKENS: [&str; 3] = ["a..z", "A..Z", "_"];
+
+// Reports the line of the input that the error is in
+fn syntax_error(
+    input: &str,
+    pos: usize,
+    error: &pest::error::Error<Rule>,
+    attempts: Option<Attempts>,
+    offset: usize,
+) -> Error {
+    let start = input[..pos].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let end = input[pos..]
+        .find('\n')
+        .map(|i| i + pos)
+        .unwrap_or(input.len());
+
+    // without details only the names of the expected rules are known
+    let attempts = attempts.unwrap_or_else(|| Attempts {
+        position: pos,
+        tokens: Vec::new(),
+        deepest: match &error.variant {
+            pest::error::ErrorVariant::ParsingError { positives, .. } => positives.clone(),
+            pest::error::ErrorVariant::CustomError { .. } => Vec::new(),
+        },
+        parents: Vec::new(),
+    });
+
+    Error::new(
+        Context {
+            start: start + offset,
+            end: end + offset,
+        },
+        pos - start,
+        ErrorKind::UnexpectedToken(Box::new(describe(input, pos, &attempts))),
+    )
+}
+
+fn describe(input: &str, pos: usize, attempts: &Attempts) -> SyntaxErrorDetails {
+    let has_token = |tokens: &[&str]| attempts.tokens.iter().any(|t| tokens.contains(&t.as_str()));
+    let has_rule = |rules: &[Rule]| attempts.deepest.iter().any(|r| rules.contains(r));
+
+    let found = found_token(input, pos);
+    let construct = (attempts.deepest.iter())
+        .chain(attempts.parents.iter())
+        .find_map(|rule| construct_name(*rule));
+
+    // only the closing quote or the contents of a string can be expected
+    if has_token(&["\""]) && has_token(&["\\"]) {
+        // a quote inside of an interpolation starts a new string
+        if in_interpolation(input, pos) {
+            return SyntaxErrorDetails {
+                expected: vec!["'}'".to_string()],
+                found,
+                construct: Some("string".to_string()),
+                hint: Some(
+                    "'{' starts an interpolation in a string, use '\\{' for a literal brace"
+                        .to_string(),
+                ),
             };
+        }
+        return SyntaxErrorDetails {
+            expected: vec!["'\"'".to_string()],
+            found,
+            construct: Some("string".to_string()),
+            hint: Some("strings have to be closed on the line they start on".to_string()),
+        };
+    }
 
-            let mut chars = input.chars();
-            let mut line_count = 1;
-            let mut char_count = 0;
+    let file_name = has_rule(&[Rule::import_path]);
+    let statement = has_token(&STATEMENT_TOKENS);
+    let operator = has_token(&OPERATOR_TOKENS[..15]);
+    // a failed expression after an operand means the operand could have continued
+    let expression = !file_name
+        && (statement
+            || has_token(&EXPRESSION_TOKENS)
+            || (has_rule(&[Rule::expression]) && !operator));
+    let type_name = has_rule(&[Rule::type_name]);
+    let name = !expression
+        && !type_name
+        && (has_token(&NAME_TOKENS) || has_rule(&[Rule::identifier, Rule::var_id]));
+
+    // tokens that are not part of a larger category are listed one by one
+    let mut expected: Vec<String> = Vec::new();
+    for token in &attempts.tokens {
+        let token = token.as_str();
+        let covered = (expression
+            && (EXPRESSION_TOKENS.contains(&token)
+                || OPERAND_TOKENS.contains(&token)
+                || STATEMENT_TOKENS.contains(&token)))
+            || (operator && OPERATOR_TOKENS.contains(&token))
+            || (file_name && token == "\"")
+            || (statement && token == ":")
+            || NAME_TOKENS.contains(&token)
+            || token == "0..9";
+        let quoted = format!("'{}'", token);
+        if !covered && !expected.contains(&quoted) {
+            expected.push(quoted);
+        }
+    }
+    let categories = [
+        (statement, "a statement"),
+        (expression && !statement, "an expression"),
+        (operator, "an operator"),
+        (type_name, "a type name"),
+        (name, "a name"),
+        (file_name, "a file name in quotes"),
+    ];
+    for (present, category) in categories {
+        if present {
+            expected.push(category.to_string());
+        }
+    }
+    if expected.is_empty() && has_token(&["0..9"]) {
+        expected.push("a number".to_string());
+    }
 
-            while line_count < lines {
-                if chars.next().unwrap() == '\n' {
-                    line_count += 1;
-                }
-                char_count += 1;
+    let hint = if found == "'='"
+        && (operator || matches!(construct, Some("if statement" | "while loop")))
+    {
+        Some("use '==' to compare values".to_string())
+    } else if expected.contains(&"'{'".to_string())
+        && matches!(construct, Some("if statement" | "while loop" | "for loop"))
+    {
+        Some(format!(
+            "the body of the {} has to be in braces",
+            construct.unwrap()
+        ))
+    } else {
+        None
+    };
+
+    SyntaxErrorDetails {
+        expected,
+        found,
+        construct: construct.map(String::from),
+        hint,
+    }
+}
+
+// Human friendly names for the rules that make up a statement
+fn construct_name(rule: Rule) -> Option<&'static str> {
+    match rule {
+        Rule::branch | Rule::branch_else_if | Rule::branch_else => Some("if statement"),
+        Rule::while_loop => Some("while loop"),
+        Rule::for_loop => Some("for loop"),
+        Rule::function_signature => Some("function signature"),
+        Rule::function_definition => Some("function definition"),
+        Rule::lambda | Rule::lambda_signature => Some("anonymous function"),
+        Rule::class_definition => Some("class definition"),
+        Rule::constructor_parameters => Some("constructor parameters"),
+        Rule::property_definition => Some("property definition"),
+        Rule::class_function_definition => Some("method definition"),
+        Rule::interface_definition => Some("interface definition"),
+        Rule::var_init => Some("variable declaration"),
+        Rule::const_definition => Some("constant declaration"),
+        Rule::global_declaration => Some("global declaration"),
+        Rule::var_assign | Rule::index_assign => Some("assignment"),
+        Rule::import_statement | Rule::import_path => Some("import statement"),
+        Rule::function_call | Rule::call => Some("function call"),
+        Rule::vec_literal => Some("Vec literal"),
+        Rule::map_literal | Rule::map_entry => Some("Map literal"),
+        Rule::text | Rule::interpolation => Some("string"),
+        _ => None,
+    }
+}
+
+// The word, operator or character at the position of the error
+fn found_token(input: &str, pos: usize) -> String {
+    let rest = &input[pos..];
+    let Some(first) = rest.chars().next() else {
+        return "end of input".to_string();
+    };
+    if first == '\n' || rest.starts_with("\r\n") {
+        return "end of line".to_string();
+    }
+
+    let is_word = |ch: char| ch.is_alphanumeric() || ch == '_';
+    let token = if is_word(first) {
+        let end = rest.find(|ch| !is_word(ch)).unwrap_or(rest.len());
+        &rest[..end]
+    } else if ["==", "!=", "<=", ">=", "->", "::", "+=", "-=", "*=", "/="]
+        .iter()
+        .any(|op| rest.starts_with(op))
+    {
+        &rest[..2]
+    } else {
+        &rest[..first.len_utf8()]
+    };
+    format!("'{}'", token)
+}
+
+// Strings end on the line they start on, so scanning the line up to `pos`
+// is enough to find the interpolations that are still open there
+fn in_interpolation(input: &str, pos: usize) -> bool {
+    let line_start = input[..pos].rfind('\n').map_or(0, |i| i + 1);
+    // '"' for a string, 'i' for an interpolation and '{' for other braces
+    let mut open: Vec<char> = Vec::new();
+    let mut chars = input[line_start..pos].chars();
+    while let Some(ch) = chars.next() {
+        match (open.last(), ch) {
+            (Some('"'), '\\') => {
+                chars.next();
+            }
+            (Some('"'), '{') => open.push('i'),
+            (Some('"'), '"') | (Some('i' | '{'), '}') => {
+                open.pop();
             }
+            (Some('"'), _) => (),
+            (_, '"') | (_, '{') => open.push(ch),
+            _ => (),
+        }
+    }
+    open.contains(&'i')
+}
 
-            let start = char_count;
+// Statements end at a line break, a semicolon or a closing brace
+// that is not inside of any brackets opened by the statement
+#[inline]
+fn is_statement_boundary(ch: char, depth: usize) -> bool {
+    depth == 0 && matches!(ch, '\n' | ';' | '}')
+}
+
+// Start of the statement that contains the error, the search
+// begins at the end of the previous statement with an error
+fn find_statement_start(input: &str, from: usize, error_pos: usize) -> usize {
+    let mut start = from;
+    scan_code(input, from, |pos, ch, depth| {
+        if pos >= error_pos {
+            return false;
+        }
+        if is_statement_boundary(ch, depth) {
+            start = pos + 1;
+        }
+        true
+    });
+    skip_whitespace(input, start).min(error_pos)
+}
+
+// An expression with the same length and line breaks as the given statement
+fn placeholder(statement: &str) -> String {
+    let blank = statement
+        .chars()
+        .map(|ch| match ch {
+            '\n' => "\n".to_string(),
+            _ => " ".repeat(ch.len_utf8()),
+        })
+        .collect::<String>();
+    format!("0{}", &blank[1..])
+}
+
+// Start of the statement and the statements nested in it before the error
+fn find_inner_statements(input: &str, start: usize, error_pos: usize) -> Vec<usize> {
+    let mut starts = vec![start];
+    scan_code(input, start, |pos, ch, _| {
+        if pos >= error_pos {
+            return false;
+        }
+        if matches!(ch, '\n' | ';' | '{' | '}') {
+            starts.push(skip_whitespace(input, pos + 1));
+        }
+        true
+    });
+    starts.retain(|pos| *pos < error_pos);
+    starts
+}
+
+// Position after the end of the statement that contains the error,
+// if a bracket is never closed it ends at the line of the error
+fn find_statement_end(input: &str, start: usize, error_pos: usize) -> usize {
+    let mut end = None;
+    let mut line_end = None;
+    scan_code(input, start, |pos, ch, depth| {
+        if pos < error_pos {
+            return true;
+        }
+        if ch == '\n' && line_end.is_none() {
+            line_end = Some(pos + 1);
+        }
+        if is_statement_boundary(ch, depth) {
+            end = Some(pos + 1);
+            return false;
+        }
+        true
+    });
+    end.or(line_end).unwrap_or(input.len())
+}
 
-            while let Some(ch) = chars.next() {
-                if ch == '\n' {
-                    break;
+// Calls the function with the position of every character after the given
+// position that is not part of a string or a comment and the depth of the
+// brackets around it, until the function returns false
+fn scan_code(input: &str, start: usize, mut f: impl FnMut(usize, char, usize) -> bool) {
+    let mut open: Vec<char> = Vec::new();
+    let mut chars = input[start..].char_indices().peekable();
+
+    while let Some((i, ch)) = chars.next() {
+        match ch {
+            '"' => {
+                // strings end at the line they start on, even if they are not closed
+                while let Some((_, ch)) = chars.next_if(|(_, ch)| *ch != '\n') {
+                    match ch {
+                        '\\' => {
+                            chars.next_if(|(_, ch)| *ch != '\n');
+                        }
+                        '"' => break,
+                        _ => (),
+                    }
                 }
-                char_count += 1;
+                continue;
             }
+            '/' if matches!(chars.peek(), Some((_, '/'))) => {
+                while chars.next_if(|(_, ch)| *ch != '\n').is_some() {}
+                continue;
+            }
+            '/' if matches!(chars.peek(), Some((_, '*'))) => {
+                chars.next();
+                let mut previous = ' ';
+                for (_, ch) in chars.by_ref() {
+                    if previous == '*' && ch == '/' {
+                        break;
+                    }
+                    previous = ch;
+                }
+                continue;
+            }
+            '{' | '(' | '[' => open.push(ch),
+            // a closing bracket also closes the unclosed brackets inside of its pair,
+            // one that does not close anything is ignored
+            '}' | ')' | ']' => {
+                let pair = match ch {
+                    '}' => '{',
+                    ')' => '(',
+                    _ => '[',
+                };
+                if let Some(index) = open.iter().rposition(|bracket| *bracket == pair) {
+                    open.truncate(index);
+                }
+            }
+            _ => (),
+        }
+        if !f(start + i, ch, open.len()) {
+            return;
+        }
+    }
+}
 
-            let end = char_count;
-
-            let offset = session.get_source_code_offset();
-            let err = Error::new(
-                Context {
-                    start: start + offset,
-                    end: end + offset,
-                },
-                column.saturating_sub(1),
-                ErrorKind::SyntaxError,
-            );
-            return Err(err);
+// Position of the first character that is not whitespace or part of a comment
+fn skip_whitespace(input: &str, mut pos: usize) -> usize {
+    loop {
+        let rest = &input[pos..];
+        let trimmed = rest.trim_start();
+        pos += rest.len() - trimmed.len();
+        if trimmed.starts_with("//") {
+            pos += trimmed.find('\n').unwrap_or(trimmed.len());
+        } else if trimmed.starts_with("/*") {
+            match trimmed.find("*/") {
+                Some(i) => pos += i + 2,
+                None => return pos,
+            }
+        } else {
+            return pos;
         }
     }
+}
 
-    let offset = session.get_source_code_offset();
-    let full_text = session.get_source_code().to_string() + &input;
-    let mut builder = Builder::new(offset, &full_text, session.create_typemap());
-    builder.build(parse_content, session)
+#[inline]
+fn outside_loop(rule: Rule) -> ErrorKind {
+    match rule {
+        Rule::loop_break => ErrorKind::OutsideLoop("break".to_string()),
+        _ => ErrorKind::OutsideLoop("continue".to_string()),
+    }
 }
 
 #[derive(Debug, Clone)]
 struct Builder<'a> {
     offset: usize,
     text: &'a str,
+    text_offset: usize,
     type_map: HashMap<String, usize>,
+    namespaces: HashSet<String>,
+
+    // set when building an imported file
+    namespace: Option<String>,
+    local_functions: HashSet<String>,
+
+    // parent of the class that is being built
+    current_parent: Option<usize>,
 }
 
 impl<'a> Builder<'a> {
     #[inline]
-    fn new(offset: usize, text: &'a str, type_map: HashMap<String, usize>) -> Self {
+    fn new(offset: usize, text: &'a str, text_offset: usize, session: &ParseSession) -> Self {
         Builder {
             offset,
             text,
-            type_map,
+            text_offset,
+            type_map: session.create_typemap(),
+            namespaces: session.namespaces().cloned().collect(),
+            namespace: None,
+            local_functions: HashSet::new(),
+            current_parent: None,
+        }
+    }
+
+    // Names defined in an imported file are prefixed with its namespace
+    #[inline]
+    fn qualify(&self, name: &str) -> String {
+        match self.namespace {
+            Some(ref namespace) => format!("{}::{}", namespace, name),
+            None => name.to_string(),
         }
     }
 
@@ -108,60 +710,208 @@ impl<'a> Builder<'a> {
         &mut self,
         parse_result: Pair<Rule>,
         session: &mut ParseSession,
-    ) -> Result<Vec<Instruction>, Error> {
+    ) -> (Vec<Instruction>, Vec<Error>) {
         let mut result: Vec<Instruction> = Vec::new();
+        let mut errors: Vec<Error> = Vec::new();
+
+        // functions of an imported file may be called before they are defined,
+        // so calls to them have to be recognized up front
+        if self.namespace.is_some() {
+            for pair in parse_result.clone().into_inner() {
+                if pair.as_rule() == Rule::function_definition {
+                    let signature = pair.into_inner().next().unwrap();
+                    let name = signature.into_inner().next().unwrap().as_str();
+                    self.local_functions.insert(name.to_string());
+                }
+            }
+        }
 
+        // an error only stops the statement it is in,
+        // the rest of the statements are still built
+        let input = parse_result.as_span().get_input();
+        let mut explained_until = 0;
         for pair in parse_result.into_inner() {
-            match pair.as_rule() {
-                Rule::class_definition => {
-                    let typeid = session.get_next_typeid();
-                    let (name, class_definition) = self.build_class_definition(pair, typeid)?;
-                    session.add_class_definition(name.clone(), class_definition);
-                }
-                Rule::function_definition => {
-                    let (name, name_pos, _uses_self, function) =
-                        self.build_function_definition(pair, false, None)?;
-                    let context = function.context();
-                    if !session.add_function(name, Function::UserFunction(function)) {
-                        return Err(Error::new(
-                            context,
-                            name_pos,
-                            ErrorKind::FunctionAlreadyDefined,
-                        ));
+            let built = match pair.as_rule() {
+                Rule::import_statement => self.build_import(pair, session),
+                _ => self
+                    .build_statement(pair, session)
+                    .map(|instruction| instruction.into_iter().collect())
+                    .map_err(|e| vec![e]),
+            };
+            match built {
+                Ok(instructions) => result.extend(instructions),
+                Err(e) => {
+                    for error in e {
+                        // the rest of the line of a malformed statement is not reported again
+                        if error.position() < explained_until {
+                            continue;
+                        }
+                        let error = self.explain_keyword(error, input);
+                        if matches!(error.kind(), ErrorKind::UnexpectedToken(_)) {
+                            let pos = error.position() - self.offset;
+                            let line_end = input[pos..].find('\n').map(|i| i + pos);
+                            explained_until = line_end.unwrap_or(input.len()) + self.offset;
+                        }
+                        errors.push(error);
                     }
                 }
-                Rule::branch => result.push(Instruction::Branch(self.build_branch(pair, None)?)),
-                Rule::while_loop => {
-                    result.push(Instruction::WhileLoop(self.build_while_loop(pair, None)?))
-                }
-                Rule::for_loop => {
-                    result.push(Instruction::ForLoop(self.build_for_loop(pair, None)?))
-                }
-                Rule::loop_break | Rule::function_return => {
-                    let context = self.get_context(&pair);
-                    return Err(Error::new(context, 0, ErrorKind::SyntaxError));
-                }
-                Rule::var_init => result.push(Instruction::VariableInit(
-                    self.build_variable_init(pair, None)?,
-                )),
-                Rule::var_assign => result.push(Instruction::VariableAssign(
-                    self.build_variable_assign(pair, None)?,
-                )),
-                Rule::expression => {
-                    result.push(Instruction::Expression(self.build_expression(pair, None)?))
+            }
+        }
+
+        (result, errors)
+    }
+
+    fn build_statement(
+        &mut self,
+        pair: Pair<Rule>,
+        session: &mut ParseSession,
+    ) -> Result<Option<Instruction>, Error> {
+        let instruction = match pair.as_rule() {
+            Rule::class_definition => {
+                let typeid = session.get_next_typeid();
+                let (name, class_definition) =
+                    self.build_class_definition(pair, typeid, session)?;
+                session.add_class_definition(self.qualify(&name), class_definition);
+                return Ok(None);
+            }
+            Rule::interface_definition => {
+                let typeid = session.get_next_typeid();
+                let (name, interface_definition) = self.build_interface_definition(pair, typeid)?;
+                session.add_class_definition(self.qualify(&name), interface_definition);
+                return Ok(None);
+            }
+            Rule::function_definition => {
+                let (name, name_pos, _uses_self, function) =
+                    self.build_function_definition(pair, false, None)?;
+                let context = function.context();
+                let name = self.qualify(&name);
+                if !session.add_function(name, Function::UserFunction(function)) {
+                    return Err(Error::new(
+                        context,
+                        name_pos,
+                        ErrorKind::FunctionAlreadyDefined,
+                    ));
                 }
-                Rule::EOI => (),
-                _ => unreachable!(),
+                return Ok(None);
             }
+            Rule::branch => Instruction::Branch(self.build_branch(pair, None)?),
+            Rule::while_loop => Instruction::WhileLoop(self.build_while_loop(pair, None)?),
+            Rule::for_loop => Instruction::ForLoop(self.build_for_loop(pair, None)?),
+            Rule::loop_break | Rule::loop_continue => {
+                let context = self.get_context(&pair);
+                return Err(Error::new(context, 0, outside_loop(pair.as_rule())));
+            }
+            Rule::function_return => {
+                let context = self.get_context(&pair);
+                return Err(Error::new(context, 0, ErrorKind::SyntaxError));
+            }
+            Rule::var_init | Rule::const_definition if self.namespace.is_some() => {
+                // they would replace the variables of the importing file
+                let context = self.get_context(&pair);
+                return Err(Error::new(context, 0, ErrorKind::VariableInImport));
+            }
+            Rule::const_definition => {
+                Instruction::ConstantInit(self.build_variable_init(pair, None)?)
+            }
+            Rule::global_declaration => {
+                Instruction::GlobalDeclaration(self.build_global_declaration(pair)?)
+            }
+            Rule::var_init => Instruction::VariableInit(self.build_variable_init(pair, None)?),
+            Rule::var_assign => {
+                Instruction::VariableAssign(self.build_variable_assign(pair, None)?)
+            }
+            Rule::index_assign => Instruction::IndexAssign(self.build_index_assign(pair, None)?),
+            Rule::expression => Instruction::Expression(self.build_expression(pair, None)?),
+            Rule::EOI => return Ok(None),
+            _ => unreachable!(),
+        };
+
+        Ok(Some(instruction))
+    }
+
+    // Parse an imported file into the session, top level
+    // instructions of the file run in place of the import
+    fn build_import(
+        &mut self,
+        import: Pair<Rule>,
+        session: &mut ParseSession,
+    ) -> Result<Vec<Instruction>, Vec<Error>> {
+        let context = self.get_context(&import);
+        let path_pair = import.into_inner().next().unwrap();
+        let path_pos = self.offset + path_pair.as_span().start() - context.start;
+        let file_name = &path_pair.as_str()[1..path_pair.as_str().len() - 1];
+        let import_error = |reason: String| {
+            vec![Error::new(
+                context,
+                path_pos,
+                ErrorKind::ImportFailed(file_name.to_string(), reason),
+            )]
+        };
+
+        let path = match session.resolve_import_path(file_name).canonicalize() {
+            Ok(path) => path,
+            Err(e) => return Err(import_error(e.to_string())),
+        };
+
+        if session.is_being_imported(&path) {
+            return Err(vec![Error::new(
+                context,
+                path_pos,
+                ErrorKind::CircularImport(file_name.to_string()),
+            )]);
         }
 
-        Ok(result)
+        // importing the same file again has no effect
+        if let Some(namespace) = session.get_import_namespace(&path) {
+            self.namespaces.insert(namespace.clone());
+            return Ok(Vec::new());
+        }
+
+        let namespace = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let is_identifier = namespace.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && namespace
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_identifier {
+            return Err(import_error(format!(
+                "'{}' is not a valid namespace name",
+                namespace
+            )));
+        }
+        if session.has_namespace(&namespace) || self.get_typeid(&namespace).is_some() {
+            return Err(import_error(format!(
+                "the name '{}' is already in use",
+                namespace
+            )));
+        }
+
+        let input = match fs::read_to_string(&path) {
+            Ok(input) => input,
+            Err(e) => return Err(import_error(e.to_string())),
+        };
+
+        session.begin_import(path);
+        let result = parse_module(&input, session, file_name, &namespace);
+        session.end_import(result.is_ok().then(|| namespace.clone()));
+        let instructions = result?;
+
+        // make the types of the imported file visible under their namespace
+        for (name, typeid) in session.create_typemap() {
+            self.type_map.entry(name).or_insert(typeid);
+        }
+        self.namespaces.insert(namespace);
+
+        Ok(instructions)
     }
 
     fn build_class_definition(
         &mut self,
         class: Pair<Rule>,
         typeid: usize,
+        session: &ParseSession,
     ) -> Result<(String, ClassDefinition), Error> {
         let context = self.get_context(&class);
         let mut class = class.into_inner();
@@ -173,13 +923,74 @@ impl<'a> Builder<'a> {
         )?;
         let name = name_pair.as_str().to_string();
         self.register_type(name.clone(), typeid);
+        if self.namespace.is_some() {
+            self.register_type(self.qualify(&name), typeid);
+        }
         let mut constructor_parameters: Vec<AnnotatedIdentifier> = Vec::new();
         let mut property_definitions: Vec<PropertyDefinition> = Vec::new();
         let mut property_names: HashSet<String> = HashSet::new();
         let mut function_definitions: HashMap<String, ClassFunction> = HashMap::new();
+        let mut interfaces: Vec<(usize, usize)> = Vec::new();
+        let mut parent_pos = 0;
+        self.current_parent = None;
 
         for pair in class {
             match pair.as_rule() {
+                Rule::parent_class => {
+                    let type_pair = pair.into_inner().next().unwrap();
+                    let pos = self.offset + type_pair.as_span().start() - context.start;
+                    let typename = type_pair.as_str();
+                    let parent = match self.get_typeid(typename) {
+                        Some(id) => id,
+                        None => {
+                            return Err(Error::new(
+                                context,
+                                pos,
+                                ErrorKind::UnknownType(typename.to_string()),
+                            ));
+                        }
+                    };
+                    // only classes defined in a script can be inherited from
+                    match session.get_class_definition(parent) {
+                        Some(c) if !c.is_builtin() && !c.is_interface() && parent != typeid => (),
+                        _ => {
+                            return Err(Error::new(
+                                context,
+                                pos,
+                                ErrorKind::InvalidParentClass(typename.to_string()),
+                            ));
+                        }
+                    }
+                    self.current_parent = Some(parent);
+                    parent_pos = pos;
+                }
+                Rule::implemented_interfaces => {
+                    for type_pair in pair.into_inner() {
+                        let pos = self.offset + type_pair.as_span().start() - context.start;
+                        let typename = type_pair.as_str();
+                        let interface = match self.get_typeid(typename) {
+                            Some(id) => id,
+                            None => {
+                                return Err(Error::new(
+                                    context,
+                                    pos,
+                                    ErrorKind::UnknownType(typename.to_string()),
+                                ));
+                            }
+                        };
+                        match session.get_class_definition(interface) {
+                            Some(c) if c.is_interface() => (),
+                            _ => {
+                                return Err(Error::new(
+                                    context,
+                                    pos,
+                                    ErrorKind::NotAnInterface(typename.to_string()),
+                                ));
+                            }
+                        }
+                        interfaces.push((interface, pos));
+                    }
+                }
                 Rule::constructor_parameters => {
                     let params = pair.into_inner();
                     for pair in params {
@@ -265,24 +1076,190 @@ impl<'a> Builder<'a> {
                             ErrorKind::FunctionAlreadyDefined,
                         ));
                     }
-                    function_definitions.insert(
-                        name,
-                        ClassFunction::new(Function::UserFunction(function), uses_self, is_public),
-                    );
+                    let function_context = function.context();
+                    let classfunc =
+                        ClassFunction::new(Function::UserFunction(function), uses_self, is_public);
+
+                    // a method that overrides one of a parent class is called in its place,
+                    // so it has to take the same arguments and return the same type
+                    let overridden = self.current_parent.and_then(|parent| {
+                        session
+                            .get_class_definition(parent)
+                            .and_then(|c| c.find_class_function(&name, session))
+                    });
+                    if let Some((owner, overridden)) = overridden {
+                        let compatible = match MethodSignature::of(&name, overridden) {
+                            Some(signature) if overridden.is_public() => {
+                                signature.matches(&classfunc)
+                            }
+                            Some(signature) => signature.matches_private(&classfunc),
+                            None => true,
+                        };
+                        if !compatible {
+                            return Err(Error::new(
+                                function_context,
+                                name_pos,
+                                ErrorKind::OverrideMismatch(session.get_typename(owner), name),
+                            ));
+                        }
+                    }
+                    function_definitions.insert(name, classfunc);
                 }
                 _ => unreachable!(),
             }
         }
 
+        // the properties of the parent classes are initialized with the arguments
+        // of the constructor, so it has to take every parameter of the parent's
+        if let Some(parent) = self.current_parent {
+            let parent_definition = session.get_class_definition(parent).unwrap();
+            let constructor = parent_definition.get_class_function("new").unwrap();
+            for expected in constructor.function().arguments() {
+                let compatible = constructor_parameters.iter().any(|parameter| {
+                    parameter.name() == expected.name()
+                        && (parameter.typeid() == TYPEID_DYN
+                            || expected.typeid() == TYPEID_DYN
+                            || session.is_subtype(parameter.typeid(), expected.typeid()))
+                });
+                if !compatible {
+                    return Err(Error::new(
+                        context,
+                        parent_pos,
+                        ErrorKind::MissingParentParameter(
+                            session.get_typename(parent),
+                            expected.name().to_string(),
+                            session.get_typename(expected.typeid()),
+                        ),
+                    ));
+                }
+            }
+        }
+
+        let mut class_definition = ClassDefinition::new(
+            constructor_parameters,
+            property_definitions,
+            function_definitions,
+            typeid,
+            context,
+        );
+        if let Some(parent) = self.current_parent.take() {
+            class_definition.set_parent(parent);
+        }
+
+        // every method of an implemented interface has to be defined
+        // by the class or one of its parents with a matching signature
+        for (interface, pos) in &interfaces {
+            let interface_definition = session.get_class_definition(*interface).unwrap();
+            for signature in interface_definition.method_signatures().unwrap() {
+                let mut current = Some(&class_definition);
+                let mut classfunc = None;
+                while let Some(c) = current {
+                    classfunc = c.get_class_function(signature.name());
+                    if classfunc.is_some() {
+                        break;
+                    }
+                    current = c.parent().and_then(|id| session.get_class_definition(id));
+                }
+                let interface_name = session.get_typename(*interface);
+                match classfunc {
+                    Some(f) if signature.matches(f) => (),
+                    Some(_) => {
+                        return Err(Error::new(
+                            context,
+                            *pos,
+                            ErrorKind::InterfaceMethodMismatch(
+                                interface_name,
+                                signature.name().to_string(),
+                            ),
+                        ));
+                    }
+                    None => {
+                        return Err(Error::new(
+                            context,
+                            *pos,
+                            ErrorKind::InterfaceNotImplemented(
+                                interface_name,
+                                signature.name().to_string(),
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+        class_definition.set_interfaces(interfaces.into_iter().map(|(id, _)| id).collect());
+
+        Ok((name, class_definition))
+    }
+
+    fn build_interface_definition(
+        &mut self,
+        interface: Pair<Rule>,
+        typeid: usize,
+    ) -> Result<(String, ClassDefinition), Error> {
+        let context = self.get_context(&interface);
+        let mut interface = interface.into_inner();
+        let name_pair = interface.next().unwrap();
+        self.validate_identifier(
+            name_pair.as_str(),
+            context,
+            self.offset + name_pair.as_span().start() - context.start,
+        )?;
+        let name = name_pair.as_str().to_string();
+        self.register_type(name.clone(), typeid);
+        if self.namespace.is_some() {
+            self.register_type(self.qualify(&name), typeid);
+        }
+
+        let mut method_signatures: Vec<MethodSignature> = Vec::new();
+
+        for signature in interface {
+            let context = self.get_context(&signature);
+            let mut signature = signature.into_inner();
+            let name_pair = signature.next().unwrap();
+            let name_pos = self.offset + name_pair.as_span().start() - context.start;
+            self.validate_identifier(name_pair.as_str(), context, name_pos)?;
+            if method_signatures
+                .iter()
+                .any(|m| m.name() == name_pair.as_str())
+            {
+                return Err(Error::new(
+                    context,
+                    name_pos,
+                    ErrorKind::FunctionAlreadyDefined,
+                ));
+            }
+
+            let mut arguments: Vec<AnnotatedIdentifier> = Vec::new();
+            let mut return_typeid: Option<usize> = Some(TYPEID_NONE);
+            let mut uses_self = false;
+
+            for pair in signature {
+                match pair.as_rule() {
+                    Rule::self_keyword => uses_self = true,
+                    Rule::var_id => {
+                        let pos = self.offset + pair.as_span().start() - context.start;
+                        arguments.push(self.build_annotated_identifier(pair, context, pos)?);
+                    }
+                    Rule::return_type_annotation => {
+                        return_typeid = self
+                            .build_return_type(pair, context)?
+                            .map(|(typeid, _)| typeid);
+                    }
+                    _ => unreachable!(),
+                }
+            }
+
+            method_signatures.push(MethodSignature::new(
+                name_pair.as_str().to_string(),
+                arguments,
+                return_typeid,
+                uses_self,
+            ));
+        }
+
         Ok((
             name,
-            ClassDefinition::new(
-                constructor_parameters,
-                property_definitions,
-                function_definitions,
-                typeid,
-                context,
-            ),
+            ClassDefinition::new_interface(method_signatures, typeid, context),
         ))
     }
 
@@ -324,24 +1301,7 @@ impl<'a> Builder<'a> {
                     arguments.push(argument);
                 }
                 Rule::return_type_annotation => {
-                    let token = pair.into_inner().next().unwrap();
-                    let typename = token.as_str();
-                    return_typeid = match self.get_typeid(typename) {
-                        Some(id) => {
-                            if id == TYPEID_DYN {
-                                None
-                            } else {
-                                Some((id, self.offset + token.as_span().start()))
-                            }
-                        }
-                        None => {
-                            return Err(Error::new(
-                                context,
-                                self.offset + token.as_span().start() - context.start,
-                                ErrorKind::UnknownType(typename.to_string()),
-                            ));
-                        }
-                    };
+                    return_typeid = self.build_return_type(pair, context)?;
                 }
                 _ => {
                     unreachable!()
@@ -349,9 +1309,42 @@ impl<'a> Builder<'a> {
             }
         }
 
+        let body = self.build_function_body(function, private_access_typeid)?;
+
+        Ok((
+            name,
+            name_pos,
+            uses_self,
+            UserFunction::new(arguments, return_typeid, body, context),
+        ))
+    }
+
+    fn build_return_type(
+        &self,
+        annotation: Pair<Rule>,
+        context: Context,
+    ) -> Result<Option<(usize, usize)>, Error> {
+        let token = annotation.into_inner().next().unwrap();
+        let typename = token.as_str();
+        match self.get_typeid(typename) {
+            Some(TYPEID_DYN) => Ok(None),
+            Some(id) => Ok(Some((id, self.offset + token.as_span().start()))),
+            None => Err(Error::new(
+                context,
+                self.offset + token.as_span().start() - context.start,
+                ErrorKind::UnknownType(typename.to_string()),
+            )),
+        }
+    }
+
+    fn build_function_body(
+        &self,
+        pairs: Pairs<Rule>,
+        private_access_typeid: Option<usize>,
+    ) -> Result<Vec<Instruction>, Error> {
         let mut body: Vec<Instruction> = Vec::new();
 
-        for pair in function {
+        for pair in pairs {
             match pair.as_rule() {
                 Rule::expression => body.push(Instruction::Expression(
                     self.build_expression(pair, private_access_typeid)?,
@@ -368,15 +1361,21 @@ impl<'a> Builder<'a> {
                 Rule::function_return => body.push(Instruction::Return(
                     self.build_function_return(pair, private_access_typeid)?,
                 )),
+                Rule::global_declaration => body.push(Instruction::GlobalDeclaration(
+                    self.build_global_declaration(pair)?,
+                )),
                 Rule::var_init => body.push(Instruction::VariableInit(
                     self.build_variable_init(pair, private_access_typeid)?,
                 )),
                 Rule::var_assign => body.push(Instruction::VariableAssign(
                     self.build_variable_assign(pair, private_access_typeid)?,
                 )),
-                Rule::loop_break => {
+                Rule::index_assign => body.push(Instruction::IndexAssign(
+                    self.build_index_assign(pair, private_access_typeid)?,
+                )),
+                Rule::loop_break | Rule::loop_continue => {
                     let context = self.get_context(&pair);
-                    return Err(Error::new(context, 0, ErrorKind::SyntaxError));
+                    return Err(Error::new(context, 0, outside_loop(pair.as_rule())));
                 }
                 _ => {
                     unreachable!();
@@ -384,12 +1383,43 @@ impl<'a> Builder<'a> {
             }
         }
 
-        Ok((
-            name,
-            name_pos,
-            uses_self,
-            UserFunction::new(arguments, return_typeid, body, context),
-        ))
+        Ok(body)
+    }
+
+    fn build_lambda(
+        &self,
+        lambda: Pair<Rule>,
+        private_access_typeid: Option<usize>,
+    ) -> Result<Function, Error> {
+        let context = self.get_context(&lambda);
+        let mut lambda = lambda.into_inner();
+        let signature = lambda.next().unwrap();
+
+        let mut arguments: Vec<AnnotatedIdentifier> = Vec::new();
+        let mut return_typeid: Option<(usize, usize)> = Some((TYPEID_NONE, 0));
+
+        for pair in signature.into_inner() {
+            match pair.as_rule() {
+                Rule::var_id => {
+                    let pos = self.offset + pair.as_span().start() - context.start;
+                    let argument = self.build_annotated_identifier(pair, context, pos)?;
+                    arguments.push(argument);
+                }
+                Rule::return_type_annotation => {
+                    return_typeid = self.build_return_type(pair, context)?;
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        let body = self.build_function_body(lambda, private_access_typeid)?;
+
+        Ok(Function::UserFunction(UserFunction::new(
+            arguments,
+            return_typeid,
+            body,
+            context,
+        )))
     }
 
     fn build_function_return(
@@ -443,16 +1473,25 @@ impl<'a> Builder<'a> {
                 Rule::for_loop => main_body.push(Instruction::ForLoop(
                     self.build_for_loop(pair, private_access_typeid)?,
                 )),
-                Rule::loop_break => main_body.push(Instruction::Break(Break::new(context))),
+                Rule::loop_break => main_body.push(Instruction::Break(self.build_loop_break(pair))),
+                Rule::loop_continue => {
+                    main_body.push(Instruction::Continue(self.build_loop_continue(pair)))
+                }
                 Rule::function_return => main_body.push(Instruction::Return(
                     self.build_function_return(pair, private_access_typeid)?,
                 )),
+                Rule::global_declaration => main_body.push(Instruction::GlobalDeclaration(
+                    self.build_global_declaration(pair)?,
+                )),
                 Rule::var_init => main_body.push(Instruction::VariableInit(
                     self.build_variable_init(pair, private_access_typeid)?,
                 )),
                 Rule::var_assign => main_body.push(Instruction::VariableAssign(
                     self.build_variable_assign(pair, private_access_typeid)?,
                 )),
+                Rule::index_assign => main_body.push(Instruction::IndexAssign(
+                    self.build_index_assign(pair, private_access_typeid)?,
+                )),
                 Rule::expression => main_body.push(Instruction::Expression(
                     self.build_expression(pair, private_access_typeid)?,
                 )),
@@ -509,6 +1548,11 @@ impl<'a> Builder<'a> {
         Break::new(context)
     }
 
+    fn build_loop_continue(&self, loop_continue: Pair<Rule>) -> Continue {
+        let context = self.get_context(&loop_continue);
+        Continue::new(context)
+    }
+
     fn build_body(
         &self,
         pairs: Pairs<Rule>,
@@ -527,15 +1571,24 @@ impl<'a> Builder<'a> {
                     self.build_for_loop(pair, private_access_typeid)?,
                 )),
                 Rule::loop_break => output.push(Instruction::Break(self.build_loop_break(pair))),
+                Rule::loop_continue => {
+                    output.push(Instruction::Continue(self.build_loop_continue(pair)))
+                }
                 Rule::function_return => output.push(Instruction::Return(
                     self.build_function_return(pair, private_access_typeid)?,
                 )),
+                Rule::global_declaration => output.push(Instruction::GlobalDeclaration(
+                    self.build_global_declaration(pair)?,
+                )),
                 Rule::var_init => output.push(Instruction::VariableInit(
                     self.build_variable_init(pair, private_access_typeid)?,
                 )),
                 Rule::var_assign => output.push(Instruction::VariableAssign(
                     self.build_variable_assign(pair, private_access_typeid)?,
                 )),
+                Rule::index_assign => output.push(Instruction::IndexAssign(
+                    self.build_index_assign(pair, private_access_typeid)?,
+                )),
                 Rule::expression => output.push(Instruction::Expression(
                     self.build_expression(pair, private_access_typeid)?,
                 )),
@@ -565,6 +1618,7 @@ impl<'a> Builder<'a> {
                 }
                 Rule::assign => assign_pos = self.offset + pair.as_span().start() - context.start,
                 Rule::expression => expr = Some(pair),
+                Rule::const_keyword => (),
                 _ => unreachable!(),
             }
         }
@@ -574,6 +1628,22 @@ impl<'a> Builder<'a> {
         Ok(VariableInit::new(identifiers, assign_pos, expr, context))
     }
 
+    fn build_global_declaration(
+        &self,
+        declaration: Pair<Rule>,
+    ) -> Result<GlobalDeclaration, Error> {
+        let context = self.get_context(&declaration);
+        let mut names = Vec::new();
+        for pair in declaration.into_inner() {
+            if pair.as_rule() == Rule::identifier {
+                let pos = self.offset + pair.as_span().start() - context.start;
+                self.validate_identifier(pair.as_str(), context, pos)?;
+                names.push(pair.as_str().to_string());
+            }
+        }
+        Ok(GlobalDeclaration::new(names, context))
+    }
+
     fn build_variable_assign(
         &self,
         var_assign: Pair<Rule>,
@@ -586,6 +1656,7 @@ impl<'a> Builder<'a> {
         let mut identifier = String::new();
         let mut id_pos = 0;
         let mut assign_pos: usize = 0;
+        let mut compound_operator = None;
         let mut expr = None;
 
         for pair in var_assign {
@@ -612,6 +1683,10 @@ impl<'a> Builder<'a> {
                     identifier = pair.as_str().to_string();
                 }
                 Rule::assign => assign_pos = self.offset + pair.as_span().start() - context.start,
+                Rule::add_assign | Rule::sub_assign | Rule::mul_assign | Rule::div_assign => {
+                    assign_pos = self.offset + pair.as_span().start() - context.start;
+                    compound_operator = Some(Builder::get_compound_operator(&pair));
+                }
                 Rule::expression => expr = Some(pair),
                 _ => unreachable!(),
             }
@@ -619,7 +1694,7 @@ impl<'a> Builder<'a> {
 
         let expr = expr.expect("A var_assign rule should always contain an expression");
         let expr = self.build_expression(expr, private_access_typeid)?;
-        Ok(VariableAssign::new(
+        let mut var_assign = VariableAssign::new(
             source_expr,
             identifier,
             private_access_typeid,
@@ -627,7 +1702,64 @@ impl<'a> Builder<'a> {
             context,
             id_pos,
             assign_pos,
-        ))
+        );
+        if let Some(operator) = compound_operator {
+            var_assign.set_compound_operator(operator);
+        }
+
+        Ok(var_assign)
+    }
+
+    fn build_index_assign(
+        &self,
+        index_assign: Pair<Rule>,
+        private_access_typeid: Option<usize>,
+    ) -> Result<IndexAssign, Error> {
+        let context = self.get_context(&index_assign);
+
+        let mut index_assign = index_assign.into_inner();
+
+        let source = index_assign.next().unwrap();
+        let source_context = self.get_context(&source);
+        let local_offset = source.as_span().start();
+        let mut output = Vec::new();
+        self.tokenize_expression(
+            source,
+            source_context,
+            local_offset,
+            &mut output,
+            private_access_typeid,
+        )?;
+        let container = Expression::compile(output, private_access_typeid, source_context);
+
+        let index_pair = index_assign.next().unwrap();
+        let index_pos = self.offset + index_pair.as_span().start() - context.start;
+        let index = self.build_expression(
+            index_pair.into_inner().next().unwrap(),
+            private_access_typeid,
+        )?;
+
+        let assign = index_assign.next().unwrap();
+        let assign_pos = self.offset + assign.as_span().start() - context.start;
+        let expr = self.build_expression(index_assign.next().unwrap(), private_access_typeid)?;
+
+        let mut index_assign = IndexAssign::new(container, index, expr, context, index_pos);
+        if assign.as_rule() != Rule::assign {
+            index_assign.set_compound_operator(Builder::get_compound_operator(&assign), assign_pos);
+        }
+
+        Ok(index_assign)
+    }
+
+    #[inline]
+    fn get_compound_operator(pair: &Pair<Rule>) -> Operator {
+        match pair.as_rule() {
+            Rule::add_assign => Operator::Add,
+            Rule::sub_assign => Operator::Sub,
+            Rule::mul_assign => Operator::Mult,
+            Rule::div_assign => Operator::Div,
+            _ => unreachable!(),
+        }
     }
 
     fn build_annotated_identifier(
@@ -716,7 +1848,8 @@ impl<'a> Builder<'a> {
         output: &mut Vec<Token>,
         private_access_typeid: Option<usize>,
     ) -> Result<(), Error> {
-        for pair in expression.into_inner() {
+        let mut pairs = expression.into_inner();
+        while let Some(pair) = pairs.next() {
             let pos = pair.as_span().start() - local_offset;
             match pair.as_rule() {
                 Rule::number => match pair.as_str().parse::<isize>() {
@@ -725,15 +1858,42 @@ impl<'a> Builder<'a> {
                 },
                 Rule::bool_true => output.push(Token::new_bool(pos, true)),
                 Rule::bool_false => output.push(Token::new_bool(pos, false)),
-                Rule::text => {
-                    let result = pair.as_str()[1..pair.as_str().len() - 1].to_string();
-                    let result = result.replace(r#"\""#, r#"""#);
-                    let result = result.replace("\\n", "\n");
-                    let result = result.replace("\\t", "\t");
-                    let result = result.replace(r"\\", r"\");
-                    output.push(Token::new_str(pos, result));
-                }
+                Rule::text => output.push(self.build_text(
+                    pair,
+                    context,
+                    local_offset,
+                    private_access_typeid,
+                    pos,
+                )?),
                 Rule::none => output.push(Token::new_none(pos)),
+                Rule::identifier if pair.as_str() == "super" => {
+                    // super.method() calls the method of the parent class on self
+                    let (dot, call) = (pairs.next(), pairs.next());
+                    let parent = match (self.current_parent, &dot, &call) {
+                        (Some(parent), Some(dot), Some(call))
+                            if dot.as_rule() == Rule::dot
+                                && call.as_rule() == Rule::function_call =>
+                        {
+                            parent
+                        }
+                        _ => return Err(Error::new(context, pos, ErrorKind::InvalidSuper)),
+                    };
+                    let (dot, call) = (dot.unwrap(), call.unwrap());
+                    let call_pos = call.as_span().start() - local_offset;
+                    let mut function_call =
+                        self.build_function_call(call, private_access_typeid)?;
+                    if function_call.associated_typeid().is_some() {
+                        return Err(Error::new(context, pos, ErrorKind::InvalidSuper));
+                    }
+                    function_call.set_super_typeid(parent);
+
+                    output.push(Token::new_identifier(pos, "self".to_string()));
+                    output.push(Token::new_operator(
+                        dot.as_span().start() - local_offset,
+                        Operator::Dot,
+                    ));
+                    output.push(Token::new_function_call(call_pos, function_call));
+                }
                 Rule::identifier => {
                     if pair.as_str() != "self" {
                         self.validate_identifier(pair.as_str(), context, pos)?;
@@ -750,8 +1910,13 @@ impl<'a> Builder<'a> {
                 Rule::or => output.push(Token::new_operator(pos, Operator::Or)),
                 Rule::less_than => output.push(Token::new_operator(pos, Operator::LessThan)),
                 Rule::greater_than => output.push(Token::new_operator(pos, Operator::GreaterThan)),
+                Rule::less_equal => output.push(Token::new_operator(pos, Operator::LessEqual)),
+                Rule::greater_equal => {
+                    output.push(Token::new_operator(pos, Operator::GreaterEqual))
+                }
                 Rule::equal => output.push(Token::new_operator(pos, Operator::Equal)),
                 Rule::not_equal => output.push(Token::new_operator(pos, Operator::NotEqual)),
+                Rule::is => output.push(Token::new_operator(pos, Operator::Is)),
                 Rule::neg => output.push(Token::new_operator(pos, Operator::Neg)),
                 Rule::not => output.push(Token::new_operator(pos, Operator::Not)),
                 Rule::dot => output.push(Token::new_operator(pos, Operator::Dot)),
@@ -766,9 +1931,55 @@ impl<'a> Builder<'a> {
                     output,
                     private_access_typeid,
                 )?,
-                Rule::function_call => {
-                    output.push(self.build_function_call(pair, private_access_typeid, pos)?)
+                Rule::function_call => output.push(Token::new_function_call(
+                    pos,
+                    self.build_function_call(pair, private_access_typeid)?,
+                )),
+                Rule::vec_literal => {
+                    output.push(self.build_vec_literal(pair, private_access_typeid, pos)?)
+                }
+                Rule::map_literal => {
+                    output.push(self.build_map_literal(pair, private_access_typeid, pos)?)
+                }
+                Rule::index => {
+                    // the index expression is treated as the parenthesized
+                    // right hand side operand of the index operator
+                    output.push(Token::new_operator(pos, Operator::Index));
+                    output.push(Token::new_parenthesis(pos, Parenthesis::Left));
+                    self.tokenize_expression(
+                        pair,
+                        context,
+                        local_offset,
+                        output,
+                        private_access_typeid,
+                    )?;
+                    output.push(Token::new_parenthesis(pos, Parenthesis::Right));
                 }
+                Rule::call => {
+                    // the arguments are the right hand side operand of the call operator
+                    let global_pos = pair.as_span().start() + self.offset;
+                    let mut arguments: Vec<Expression> = Vec::new();
+                    for item in pair.into_inner() {
+                        arguments.push(self.build_expression(item, private_access_typeid)?);
+                    }
+                    let line_col = get_line_column(global_pos - self.text_offset, self.text);
+                    output.push(Token::new_operator(pos, Operator::Call));
+                    output.push(Token::new_arguments(
+                        pos,
+                        CallArguments::new(arguments, line_col),
+                    ));
+                }
+                Rule::lambda => output.push(Token::new_lambda(
+                    pos,
+                    self.build_lambda(pair, private_access_typeid)?,
+                )),
+                Rule::source_chain => self.tokenize_expression(
+                    pair,
+                    context,
+                    local_offset,
+                    output,
+                    private_access_typeid,
+                )?,
                 Rule::EOI => (),
                 _ => unreachable!(),
             }
@@ -781,8 +1992,7 @@ impl<'a> Builder<'a> {
         &self,
         function_call: Pair<Rule>,
         private_access_typeid: Option<usize>,
-        pos: usize,
-    ) -> Result<Token, Error> {
+    ) -> Result<FunctionCall, Error> {
         let context = self.get_context(&function_call);
         let global_pos = function_call.as_span().start() + self.offset;
 
@@ -791,34 +2001,48 @@ impl<'a> Builder<'a> {
         let name: String;
         let name_pos;
         let mut associated_type: Option<(usize, usize)> = None;
+        let mut qualified_name: Option<String> = None;
 
         let first_pair = function_call.next().unwrap();
         match first_pair.as_rule() {
             Rule::identifier => {
                 name = first_pair.as_str().to_string();
                 name_pos = 0;
+                if self.local_functions.contains(&name) {
+                    qualified_name = Some(self.qualify(&name));
+                }
             }
             Rule::scope => {
-                let mut scope = first_pair.into_inner();
-                let name_pair = scope.next().unwrap();
-                let start_pos = name_pair.as_span().start();
-                let typename = name_pair.as_str();
-                let operator_pos =
-                    self.offset + scope.next().unwrap().as_span().start() - context.start;
-                let typeid = match self.get_typeid(typename) {
-                    Some(id) => id,
-                    None => {
-                        return Err(Error::new(
-                            context,
-                            self.offset + start_pos - context.start,
-                            ErrorKind::UnknownType(typename.to_string()),
-                        ));
+                // the scope is either a type, possibly inside a namespace,
+                // or the namespace of a function
+                let start_pos = first_pair.as_span().start();
+                let mut path: Vec<&str> = Vec::new();
+                let mut operator_pos = 0;
+                for pair in first_pair.into_inner() {
+                    match pair.as_rule() {
+                        Rule::identifier => path.push(pair.as_str()),
+                        Rule::scope_res => {
+                            operator_pos = self.offset + pair.as_span().start() - context.start
+                        }
+                        _ => unreachable!(),
                     }
-                };
+                }
+                let typename = path.join("::");
                 let name_pair = function_call.next().unwrap();
-                name = name_pair.as_str().to_string();
                 name_pos = self.offset + name_pair.as_span().start() - context.start;
-                associated_type = Some((typeid, operator_pos));
+
+                if let Some(typeid) = self.get_typeid(&typename) {
+                    name = name_pair.as_str().to_string();
+                    associated_type = Some((typeid, operator_pos));
+                } else if self.namespaces.contains(&typename) {
+                    name = format!("{}::{}", typename, name_pair.as_str());
+                } else {
+                    return Err(Error::new(
+                        context,
+                        self.offset + start_pos - context.start,
+                        ErrorKind::UnknownType(typename),
+                    ));
+                }
             }
             _ => unreachable!(),
         }
@@ -835,20 +2059,143 @@ impl<'a> Builder<'a> {
             }
         }
 
-        Ok(Token::new_function_call(
+        let mut function_call = FunctionCall::new(
+            associated_type,
+            name,
+            arguments,
+            private_access_typeid,
+            context,
+            name_pos,
+            get_line_column(global_pos - self.text_offset, self.text),
+        );
+        if let Some(qualified_name) = qualified_name {
+            function_call.set_qualified_name(qualified_name);
+        }
+
+        Ok(function_call)
+    }
+
+    #[inline]
+    fn build_vec_literal(
+        &self,
+        vec_literal: Pair<Rule>,
+        private_access_typeid: Option<usize>,
+        pos: usize,
+    ) -> Result<Token, Error> {
+        let mut items = Vec::new();
+        for item in vec_literal.into_inner() {
+            items.push(self.build_expression(item, private_access_typeid)?);
+        }
+
+        Ok(Token::new_vec_literal(pos, VecLiteral::new(items)))
+    }
+
+    fn build_text(
+        &self,
+        text: Pair<Rule>,
+        context: Context,
+        local_offset: usize,
+        private_access_typeid: Option<usize>,
+        pos: usize,
+    ) -> Result<Token, Error> {
+        let mut parts = Vec::new();
+        let mut current = String::new();
+
+        for pair in text.into_inner() {
+            match pair.as_rule() {
+                Rule::text_chars => current.push_str(pair.as_str()),
+                Rule::escape_sequence => match Builder::unescape(pair.as_str()) {
+                    Some(c) => current.push(c),
+                    None => {
+                        return Err(Error::new(
+                            context,
+                            pair.as_span().start() - local_offset,
+                            ErrorKind::InvalidEscapeSequence(pair.as_str().to_string()),
+                        ));
+                    }
+                },
+                Rule::interpolation => {
+                    if !current.is_empty() {
+                        parts.push(InterpolationPart::Text(std::mem::take(&mut current)));
+                    }
+                    let expr = pair.into_inner().next().unwrap();
+                    let expr = self.build_expression(expr, private_access_typeid)?;
+                    parts.push(InterpolationPart::Expression(expr));
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        // strings without embedded expressions are constant
+        if parts.is_empty() {
+            return Ok(Token::new_str(pos, current));
+        }
+
+        if !current.is_empty() {
+            parts.push(InterpolationPart::Text(current));
+        }
+        Ok(Token::new_interpolation(
             pos,
-            FunctionCall::new(
-                associated_type,
-                name,
-                arguments,
-                private_access_typeid,
-                context,
-                name_pos,
-                get_line_column(global_pos, self.text),
-            ),
+            StringInterpolation::new(parts),
         ))
     }
 
+    fn unescape(sequence: &str) -> Option<char> {
+        match sequence {
+            r"\n" => Some('\n'),
+            r"\t" => Some('\t'),
+            r"\r" => Some('\r'),
+            r"\0" => Some('\0'),
+            r"\\" => Some('\\'),
+            r#"\""# => Some('"'),
+            r"\'" => Some('\''),
+            r"\{" => Some('{'),
+            r"\}" => Some('}'),
+            _ => {
+                // unicode code point, e.g. \u{1F600}
+                let hex = sequence.strip_prefix(r"\u{")?.strip_suffix('}')?;
+                if hex.is_empty() || hex.len() > 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return None;
+                }
+                char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+            }
+        }
+    }
+
+    #[inline]
+    fn build_map_literal(
+        &self,
+        map_literal: Pair<Rule>,
+        private_access_typeid: Option<usize>,
+        pos: usize,
+    ) -> Result<Token, Error> {
+        let mut entries = Vec::new();
+        for entry in map_literal.into_inner() {
+            let mut entry = entry.into_inner();
+            let key = self.build_expression(entry.next().unwrap(), private_access_typeid)?;
+            let value = self.build_expression(entry.next().unwrap(), private_access_typeid)?;
+            entries.push((key, value));
+        }
+
+        Ok(Token::new_map_literal(pos, MapLiteral::new(entries)))
+    }
+
+    // A keyword in place of an identifier usually means
+    // that the statement it starts is malformed
+    fn explain_keyword(&self, error: Error, input: &str) -> Error {
+        if !matches!(error.kind(), ErrorKind::IdentifierIsKeyword) {
+            return error;
+        }
+        let pos = error.position() - self.offset;
+        match parse_keyword_statement(input, pos) {
+            Some(e) => {
+                let (error_pos, attempts) = inspect(&e, pos);
+                syntax_error(input, error_pos, &e, attempts, self.offset)
+            }
+            None => error,
+        }
+    }
+
     #[inline]
     fn get_context(&self, pair: &Pair<Rule>) -> Context {
         Context {
@@ -876,16 +2223,23 @@ impl<'a> Builder<'a> {
     }
 }
 
-const KEYWORDS: [&'static str; 18] = [
+const KEYWORDS: [&'static str; 26] = [
     DYN_KEYWORD,
     "and",
     "break",
     "class",
+    "const",
+    "continue",
     "else",
     "fn",
     "for",
+    "global",
     "if",
+    "impl",
+    "import",
     "in",
+    "interface",
+    "is",
     "let",
     "new",
     "none",
@@ -893,6 +2247,7 @@ const KEYWORDS: [&'static str; 18] = [
     "or",
     "return",
     "self",
+    "super",
     "typeof",
     "while",
 ];
@@ -1,9 +1,18 @@
 use crate::builtin::{self, DYN_KEYWORD};
-use crate::class::ClassDefinition;
-use crate::error::{Context, Error, ErrorKind};
+use crate::class::{ClassDefinition, ClassFunction};
+use crate::error::{Context, DustError, Error, ErrorKind};
 use crate::function::Function;
+use crate::host::{self, HostValue, NativeClass, NativeResult};
+use crate::limits::{Budget, InterruptHandle, Limits};
+use crate::stream::{InputStream, OutputStream};
 use crate::variable::Variable;
-use std::collections::HashMap;
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::{BTreeMap, HashMap};
+use std::env::{self, VarError};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
 
 // Two structs are needed in order to be able to
 // assign different mutability modifiers to them
@@ -29,10 +38,121 @@ impl Session {
         self.parse_session.clear();
     }
 
+    // Runs the input and prints its errors, returns whether there were none
     #[inline]
-    pub fn process(&mut self, input: &str) {
+    pub fn process(&mut self, input: &str) -> bool {
         crate::process(input, self)
     }
+
+    // Validates the input without running it, every error is printed
+    // to the output. Classes and functions that it defines are kept
+    #[inline]
+    pub fn check(&mut self, input: &str) -> bool {
+        crate::check(input, self)
+    }
+
+    // Set the path of the script being run, imports in it
+    // are resolved relative to the directory that contains it
+    #[inline]
+    pub fn set_script_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.parse_session.set_script_path(path.as_ref());
+    }
+
+    // Redirect the output of 'print' and 'println' as well as error messages,
+    // the stream is kept when the session is reset
+    #[inline]
+    pub fn set_output(&mut self, output: OutputStream) {
+        self.exec_session.set_output(output);
+    }
+
+    // Change where 'input' reads its lines from
+    #[inline]
+    pub fn set_input(&mut self, input: InputStream) {
+        self.exec_session.set_input(input);
+    }
+
+    // Limit the work each input may do, the limits are kept when the session is reset
+    #[inline]
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.exec_session.budget_mut().set_limits(limits);
+    }
+
+    // The arguments returned by 'args', they are kept when the session is reset
+    #[inline]
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.exec_session.set_args(args);
+    }
+
+    // The code the last input passed to 'exit', None if it did not call it
+    #[inline]
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exec_session.exit_code()
+    }
+
+    // A handle that stops the input that is running from another thread
+    #[inline]
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.exec_session.budget().interrupt_handle()
+    }
+
+    // Runs the input and returns the value of the final expression,
+    // errors are returned instead of being printed
+    pub fn eval(&mut self, input: &str) -> Result<HostValue, DustError> {
+        match crate::run(input, self) {
+            Ok(value) => Ok(HostValue::from_value(&value, &self.parse_session)),
+            Err(mut errors) => {
+                let e = errors.swap_remove(0);
+                let backtrace = self.exec_session.get_backtrace().clone();
+                self.exec_session.clear_backtrace();
+                Err(DustError::new(
+                    e,
+                    self.parse_session.get_source_code(),
+                    self.parse_session.source_files(),
+                    backtrace,
+                ))
+            }
+        }
+    }
+
+    // Make a Rust closure callable from scripts as a global function.
+    // The signature lists the parameter names and type names, for example
+    // &[("a", "int"), ("b", "dyn")]. An Err(message) returned from the closure
+    // is reported as a runtime error at the call site.
+    pub fn register_function<F>(
+        &mut self,
+        name: &str,
+        signature: &[(&str, &str)],
+        body: F,
+    ) -> Result<(), ErrorKind>
+    where
+        F: Fn(&[HostValue]) -> NativeResult + 'static,
+    {
+        let arguments = host::resolve_signature(signature, &self.parse_session)?;
+        let function = host::make_native_function(arguments, Rc::new(body));
+        if self
+            .parse_session
+            .add_native_function(name.to_string(), function)
+        {
+            Ok(())
+        } else {
+            Err(ErrorKind::FunctionAlreadyDefined)
+        }
+    }
+
+    // Register a class whose associated functions are implemented by the host
+    pub fn register_class(&mut self, name: &str, class: NativeClass) -> Result<(), ErrorKind> {
+        if self.parse_session.create_typemap().contains_key(name) {
+            return Err(ErrorKind::IdentifierIsTypename);
+        }
+        let functions = class
+            .build(&self.parse_session)?
+            .into_iter()
+            .map(|(name, function)| (name, ClassFunction::new(function, false, true)))
+            .collect();
+        self.parse_session
+            .add_native_class(name.to_string(), functions);
+        Ok(())
+    }
 }
 
 // Contains data that cannot be modified after parsing
@@ -40,25 +160,35 @@ impl Session {
 
 #[derive(Debug, Clone)]
 pub struct ParseSession {
-    function_store: HashMap<String, Function>,
+    function_store: Vec<Function>,
+    function_indices: HashMap<String, usize>,
+    user_functions: Vec<String>,
     class_definitions: Vec<ClassDefinition>,
-    default_type_names: [&'static str; 11],
+    default_type_names: [&'static str; 17],
     type_names: Vec<String>,
+    native_functions: Vec<(String, Function)>,
+    native_classes: Vec<(String, HashMap<String, ClassFunction>)>,
     source_code: String,
     offset: usize,
+
+    // state of the module system
+    script_path: Option<PathBuf>,
+    import_stack: Vec<PathBuf>,
+    imported_files: HashMap<PathBuf, String>,
+    source_files: Vec<SourceFile>,
 }
 
 impl ParseSession {
     #[inline]
     pub fn new() -> Self {
-        let mut function_store = HashMap::new();
-        builtin::load_builtin_functions(&mut function_store);
         let mut class_definitions = Vec::new();
         builtin::load_builtin_class_definitions(&mut class_definitions);
         let type_names = Vec::new();
 
-        ParseSession {
-            function_store,
+        let mut parse_session = ParseSession {
+            function_store: Vec::new(),
+            function_indices: HashMap::new(),
+            user_functions: Vec::new(),
             class_definitions,
             default_type_names: [
                 DYN_KEYWORD,
@@ -72,11 +202,25 @@ impl ParseSession {
                 "File",
                 "Fs",
                 "Math",
+                "Map",
+                "Function",
+                "Env",
+                "Process",
+                "FileHandle",
+                "Json",
             ],
             type_names,
+            native_functions: Vec::new(),
+            native_classes: Vec::new(),
             source_code: String::new(),
             offset: 0,
-        }
+            script_path: None,
+            import_stack: Vec::new(),
+            imported_files: HashMap::new(),
+            source_files: Vec::new(),
+        };
+        parse_session.load_builtin_functions();
+        parse_session
     }
 
     const CLASSLESS_TYPES_COUNT: usize = 2; // dyn and none
@@ -84,12 +228,25 @@ impl ParseSession {
     #[inline]
     pub fn clear(&mut self) {
         self.function_store.clear();
-        builtin::load_builtin_functions(&mut self.function_store);
+        self.function_indices.clear();
+        self.user_functions.clear();
+        self.load_builtin_functions();
         self.class_definitions.clear();
         builtin::load_builtin_class_definitions(&mut self.class_definitions);
         self.type_names.clear();
         self.source_code.clear();
         self.offset = 0;
+        self.import_stack.clear();
+        self.imported_files.clear();
+        self.source_files.clear();
+
+        // functions and classes registered by the host outlive a reset
+        for (name, function) in self.native_functions.clone() {
+            self.insert_function(name, function);
+        }
+        for (name, functions) in std::mem::take(&mut self.native_classes) {
+            self.add_native_class(name, functions);
+        }
     }
 
     #[inline]
@@ -123,14 +280,51 @@ impl ParseSession {
 
     #[inline]
     pub fn add_function(&mut self, name: String, value: Function) -> bool {
-        if self.function_store.contains_key(&name) {
+        if self.function_indices.contains_key(&name) {
             false
         } else {
-            self.function_store.insert(name, value);
+            if let Function::UserFunction(_) = value {
+                self.user_functions.push(name.clone());
+            }
+            self.insert_function(name, value);
+            true
+        }
+    }
+
+    #[inline]
+    fn insert_function(&mut self, name: String, value: Function) {
+        self.function_indices
+            .insert(name, self.function_store.len());
+        self.function_store.push(value);
+    }
+
+    #[inline]
+    fn load_builtin_functions(&mut self) {
+        let mut builtin_functions = HashMap::new();
+        builtin::load_builtin_functions(&mut builtin_functions);
+        for (name, function) in builtin_functions {
+            self.insert_function(name, function);
+        }
+    }
+
+    #[inline]
+    pub fn add_native_function(&mut self, name: String, value: Function) -> bool {
+        if self.add_function(name.clone(), value.clone()) {
+            self.native_functions.push((name, value));
             true
+        } else {
+            false
         }
     }
 
+    #[inline]
+    pub fn add_native_class(&mut self, name: String, functions: HashMap<String, ClassFunction>) {
+        let class_definition =
+            ClassDefinition::new_without_constructor(functions.clone(), self.get_next_typeid());
+        self.add_class_definition(name.clone(), class_definition);
+        self.native_classes.push((name, functions));
+    }
+
     #[inline]
     pub fn get_function(
         &self,
@@ -141,18 +335,36 @@ impl ParseSession {
             Some(options) => {
                 if options.associated_typeid >= Self::CLASSLESS_TYPES_COUNT {
                     self.class_definitions[options.associated_typeid - Self::CLASSLESS_TYPES_COUNT]
-                        .get_function(name, options.member_only, options.private_access)
+                        .get_function(name, options.member_only, options.private_access, self)
                 } else {
                     Err(ErrorKind::FunctionNotFound)
                 }
             }
-            None => match self.function_store.get(name) {
-                Some(f) => Ok(f),
+            None => match self.function_indices.get(name) {
+                Some(index) => Ok(&self.function_store[*index]),
                 None => Err(ErrorKind::FunctionNotFound),
             },
         }
     }
 
+    // Functions that are not associated with a type can be
+    // resolved to an index once and then called through it
+    #[inline]
+    pub fn function_index(&self, name: &str) -> Option<usize> {
+        self.function_indices.get(name).copied()
+    }
+
+    #[inline]
+    pub fn function_at(&self, index: usize) -> &Function {
+        &self.function_store[index]
+    }
+
+    // Names of the functions defined by scripts in the order they were defined
+    #[inline]
+    pub fn user_functions(&self) -> &Vec<String> {
+        &self.user_functions
+    }
+
     #[inline]
     pub fn get_next_typeid(&self) -> usize {
         self.default_type_names.len() + self.type_names.len()
@@ -173,6 +385,31 @@ impl ParseSession {
             .get(typeid - Self::CLASSLESS_TYPES_COUNT)
     }
 
+    // Whether a value of the first type can be used where the second one
+    // is expected, a class is a subtype of its parents and its interfaces
+    pub fn is_subtype(&self, typeid: usize, expected_typeid: usize) -> bool {
+        let mut current = typeid;
+        loop {
+            if current == expected_typeid {
+                return true;
+            }
+            if current < Self::CLASSLESS_TYPES_COUNT {
+                return false;
+            }
+            let class_definition = match self.get_class_definition(current) {
+                Some(c) => c,
+                None => return false,
+            };
+            if class_definition.interfaces().contains(&expected_typeid) {
+                return true;
+            }
+            match class_definition.parent() {
+                Some(parent) => current = parent,
+                None => return false,
+            }
+        }
+    }
+
     #[inline]
     pub fn append_source_code(&mut self, input: &str) {
         if !self.source_code.is_empty() {
@@ -199,6 +436,82 @@ impl ParseSession {
     pub fn get_source_code_len(&self) -> usize {
         self.source_code.len()
     }
+
+    #[inline]
+    pub fn set_script_path(&mut self, path: &Path) {
+        self.script_path = Some(path.canonicalize().unwrap_or(path.to_path_buf()));
+    }
+
+    // Imports are relative to the file that is currently being parsed
+    #[inline]
+    pub fn resolve_import_path(&self, path: &str) -> PathBuf {
+        let current_file = self.import_stack.last().or(self.script_path.as_ref());
+        match current_file.and_then(|file| file.parent()) {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        }
+    }
+
+    // A file that is still being parsed cannot be imported again
+    #[inline]
+    pub fn is_being_imported(&self, path: &Path) -> bool {
+        self.import_stack.iter().any(|file| file == path)
+            || self.script_path.as_deref() == Some(path)
+    }
+
+    #[inline]
+    pub fn get_import_namespace(&self, path: &Path) -> Option<&String> {
+        self.imported_files.get(path)
+    }
+
+    #[inline]
+    pub fn has_namespace(&self, namespace: &str) -> bool {
+        self.imported_files.values().any(|name| name == namespace)
+    }
+
+    #[inline]
+    pub fn namespaces(&self) -> impl Iterator<Item = &String> {
+        self.imported_files.values()
+    }
+
+    #[inline]
+    pub fn begin_import(&mut self, path: PathBuf) {
+        self.import_stack.push(path);
+    }
+
+    #[inline]
+    pub fn end_import(&mut self, namespace: Option<String>) {
+        let path = self
+            .import_stack
+            .pop()
+            .expect("end_import should only be called after begin_import");
+        if let Some(namespace) = namespace {
+            self.imported_files.insert(path, namespace);
+        }
+    }
+
+    #[inline]
+    pub fn add_source_file(&mut self, name: &str, start: usize, end: usize) {
+        self.source_files.push(SourceFile {
+            name: name.to_string(),
+            start,
+            end,
+        });
+    }
+
+    #[inline]
+    pub fn source_files(&self) -> &[SourceFile] {
+        &self.source_files
+    }
+}
+
+// The region of the source code that was read from an imported file
+
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
 }
 
 // Contains data that can be modified even after
@@ -206,12 +519,16 @@ impl ParseSession {
 
 #[derive(Debug, Clone)]
 pub struct ExecSession {
-    #[cfg(test)]
-    pub output_stream: String,
-
-    global_scope: HashMap<String, Variable>,
-    local_scopes: Vec<HashMap<String, Variable>>,
-    call_count: usize,
+    output: OutputStream,
+    input: InputStream,
+    global_scope: Scope,
+    local_scopes: Vec<Scope>,
+    budget: Budget,
+    args: Vec<String>,
+    // environment variables set or removed by the script, they are seen by
+    // the script and the processes it starts, but not by the rest of the host
+    env_changes: HashMap<String, Option<String>>,
+    exit_code: Option<i32>,
     backtrace: Vec<BacktraceItem>,
 }
 
@@ -219,65 +536,115 @@ impl ExecSession {
     #[inline]
     pub fn new() -> Self {
         ExecSession {
-            #[cfg(test)]
-            output_stream: String::new(),
-
-            global_scope: HashMap::new(),
+            output: OutputStream::stdout(),
+            input: InputStream::stdin(),
+            global_scope: Scope::default(),
             local_scopes: Vec::new(),
-            call_count: 0,
+            budget: Budget::default(),
+            args: Vec::new(),
+            env_changes: HashMap::new(),
+            exit_code: None,
             backtrace: Vec::new(),
         }
     }
 
     #[inline]
     pub fn clear(&mut self) {
-        #[cfg(test)]
-        self.output_stream.clear();
-
-        self.global_scope.clear();
+        self.global_scope = Scope::default();
         self.local_scopes.clear();
-        self.call_count = 0;
+        self.budget.start();
+        self.env_changes.clear();
+        self.exit_code = None;
         self.backtrace.clear();
     }
 
     #[inline]
-    pub fn get_variable(&self, var_name: &str) -> Option<&Variable> {
-        let len = self.local_scopes.len();
-        if len > 0 {
-            self.local_scopes[len - 1].get(var_name)
-        } else {
-            self.global_scope.get(var_name)
+    pub fn output(&self) -> &OutputStream {
+        &self.output
+    }
+
+    #[inline]
+    pub fn set_output(&mut self, output: OutputStream) {
+        self.output = output;
+    }
+
+    #[inline]
+    pub fn input(&self) -> &InputStream {
+        &self.input
+    }
+
+    #[inline]
+    pub fn set_input(&mut self, input: InputStream) {
+        self.input = input;
+    }
+
+    #[inline]
+    pub fn global_variables(&self) -> impl Iterator<Item = (&String, VariableRef<'_>)> {
+        self.global_scope.variables()
+    }
+
+    // The global variables and their slots, a program
+    // is compiled against it and may add variables of its own
+    #[inline]
+    pub fn global_scope(&self) -> &Scope {
+        &self.global_scope
+    }
+
+    #[inline]
+    pub fn set_global_layout(&mut self, layout: Rc<HashMap<String, usize>>, slot_count: usize) {
+        self.global_scope.set_layout(layout, slot_count);
+    }
+
+    #[inline]
+    pub fn global_scope_mut(&mut self) -> &mut Scope {
+        &mut self.global_scope
+    }
+
+    // Constants are visible in every scope
+    #[inline]
+    pub fn get_constant(&self, name: &str) -> Option<VariableRef<'_>> {
+        self.global_scope.get(name).filter(|var| var.is_constant())
+    }
+
+    // The scope of the function that is running, or the global one
+    #[inline]
+    pub fn scope(&self) -> &Scope {
+        self.local_scopes.last().unwrap_or(&self.global_scope)
+    }
+
+    #[inline]
+    pub fn scope_mut(&mut self) -> &mut Scope {
+        match self.local_scopes.last_mut() {
+            Some(scope) => scope,
+            None => &mut self.global_scope,
         }
     }
 
+    // The variables of built-in functions, such as their arguments, are never shared
+    #[inline]
+    pub fn get_variable(&self, var_name: &str) -> Option<&Variable> {
+        self.scope().get_local(var_name)
+    }
+
     #[inline]
     pub fn get_variable_mut(&mut self, var_name: &str) -> Option<&mut Variable> {
-        let len = self.local_scopes.len();
-        if len > 0 {
-            self.local_scopes[len - 1].get_mut(var_name)
-        } else {
-            self.global_scope.get_mut(var_name)
-        }
+        self.scope_mut().get_local_mut(var_name)
     }
 
     #[inline]
     pub fn add_variable(&mut self, var_name: &str, var: Variable) {
-        let len = self.local_scopes.len();
-        if len > 0 {
-            self.local_scopes[len - 1].insert(var_name.to_string(), var);
-        } else {
-            self.global_scope.insert(var_name.to_string(), var);
-        }
+        self.scope_mut().insert(var_name, var);
+    }
+
+    // The variables in the current scope, shared with anonymous functions
+    #[inline]
+    pub fn capture_scope(&mut self) -> HashMap<String, SharedVariable> {
+        self.scope_mut().share_variables()
     }
 
     #[inline]
     pub fn variable_exists(&self, var_name: &str) -> bool {
-        let len = self.local_scopes.len();
-        if len > 0 {
-            self.local_scopes[len - 1].contains_key(var_name)
-        } else {
-            self.global_scope.contains_key(var_name)
-        }
+        self.scope().get(var_name).is_some()
     }
 
     #[inline]
@@ -296,7 +663,7 @@ impl ExecSession {
     }
 
     #[inline]
-    pub fn add_scope(&mut self, scope: HashMap<String, Variable>) {
+    pub fn add_scope(&mut self, scope: Scope) {
         self.local_scopes.push(scope);
     }
 
@@ -306,22 +673,322 @@ impl ExecSession {
     }
 
     #[inline]
-    pub fn increment_call_count(&mut self) -> Result<(), Error> {
-        if self.call_count >= crate::FUNCTION_CALL_LIMIT {
-            Err(Error::new(
-                Context { start: 0, end: 0 },
-                0,
-                ErrorKind::IterationLimitReached,
-            ))
-        } else {
-            self.call_count += 1;
-            Ok(())
+    pub fn args(&self) -> &Vec<String> {
+        &self.args
+    }
+
+    #[inline]
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    pub fn env_var(&self, name: &str) -> Result<String, VarError> {
+        match self.env_changes.get(name) {
+            Some(Some(value)) => Ok(value.clone()),
+            Some(None) => Err(VarError::NotPresent),
+            None => env::var(name),
+        }
+    }
+
+    #[inline]
+    pub fn set_env_var(&mut self, name: String, value: String) {
+        self.env_changes.insert(name, Some(value));
+    }
+
+    #[inline]
+    pub fn remove_env_var(&mut self, name: String) {
+        self.env_changes.insert(name, None);
+    }
+
+    // The environment variables of the process with the changes made by the script
+    pub fn env_vars(&self) -> BTreeMap<String, String> {
+        let mut vars: BTreeMap<String, String> = env::vars_os()
+            .map(|(name, value)| {
+                (
+                    name.to_string_lossy().to_string(),
+                    value.to_string_lossy().to_string(),
+                )
+            })
+            .collect();
+        for (name, value) in self.env_changes.iter() {
+            match value {
+                Some(value) => vars.insert(name.clone(), value.clone()),
+                None => vars.remove(name),
+            };
+        }
+        vars
+    }
+
+    // Passes the changes made by the script on to a process that it starts
+    pub fn apply_env(&self, command: &mut Command) {
+        for (name, value) in self.env_changes.iter() {
+            match value {
+                Some(value) => command.env(name, value),
+                None => command.env_remove(name),
+            };
         }
     }
 
+    #[inline]
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    #[inline]
+    pub fn set_exit_code(&mut self, exit_code: Option<i32>) {
+        self.exit_code = exit_code;
+    }
+
+    #[inline]
+    pub fn budget(&self) -> &Budget {
+        &self.budget
+    }
+
+    #[inline]
+    pub fn budget_mut(&mut self) -> &mut Budget {
+        &mut self.budget
+    }
+
+    #[inline]
+    pub fn increment_call_count(&mut self) -> Result<(), Error> {
+        self.budget
+            .enter_call()
+            .map_err(|kind| Error::new(Context { start: 0, end: 0 }, 0, kind))
+    }
+
     #[inline]
     pub fn decrement_call_count(&mut self) {
-        self.call_count -= 1;
+        self.budget.leave_call();
+    }
+}
+
+// The variables of a function call or of the global scope. Compiled code
+// refers to variables by their slot, names that were not known when
+// the code was compiled, such as the variables captured by anonymous
+// functions, are given a new slot when they are added
+
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    layout: Rc<HashMap<String, usize>>,
+    slots: Vec<Slot>,
+}
+
+// A variable that anonymous functions captured, it is shared between
+// them and the scope it was declared in. It is empty while an anonymous
+// function that refers to itself is created, e.g. let f = fn() { f() }
+pub type SharedVariable = Rc<RefCell<Option<Variable>>>;
+
+#[derive(Debug, Clone, Default)]
+enum Slot {
+    #[default]
+    Empty,
+    Local(Variable),
+    Shared(SharedVariable),
+}
+
+// A variable of a scope, borrowed from its cell if it is shared
+
+pub enum VariableRef<'a> {
+    Local(&'a Variable),
+    Shared(Ref<'a, Variable>),
+}
+
+impl Deref for VariableRef<'_> {
+    type Target = Variable;
+
+    #[inline]
+    fn deref(&self) -> &Variable {
+        match self {
+            VariableRef::Local(var) => var,
+            VariableRef::Shared(var) => var,
+        }
+    }
+}
+
+pub enum VariableRefMut<'a> {
+    Local(&'a mut Variable),
+    Shared(RefMut<'a, Variable>),
+}
+
+impl Deref for VariableRefMut<'_> {
+    type Target = Variable;
+
+    #[inline]
+    fn deref(&self) -> &Variable {
+        match self {
+            VariableRefMut::Local(var) => var,
+            VariableRefMut::Shared(var) => var,
+        }
+    }
+}
+
+impl DerefMut for VariableRefMut<'_> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Variable {
+        match self {
+            VariableRefMut::Local(var) => var,
+            VariableRefMut::Shared(var) => var,
+        }
+    }
+}
+
+impl Scope {
+    #[inline]
+    pub fn new(layout: Rc<HashMap<String, usize>>, slot_count: usize) -> Self {
+        let slots = vec![Slot::Empty; slot_count];
+        Scope { layout, slots }
+    }
+
+    #[inline]
+    pub fn layout(&self) -> &Rc<HashMap<String, usize>> {
+        &self.layout
+    }
+
+    // The new layout has to keep the slots of the names in the current one
+    #[inline]
+    pub fn set_layout(&mut self, layout: Rc<HashMap<String, usize>>, slot_count: usize) {
+        self.slots.resize(slot_count, Slot::Empty);
+        self.layout = layout;
+    }
+
+    // The number of slots, including the ones of variables
+    // in blocks that are not part of the layout
+    #[inline]
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    #[inline]
+    pub fn slot(&self, slot: usize) -> Option<VariableRef<'_>> {
+        match &self.slots[slot] {
+            Slot::Empty => None,
+            Slot::Local(var) => Some(VariableRef::Local(var)),
+            Slot::Shared(cell) => Ref::filter_map(cell.borrow(), Option::as_ref)
+                .ok()
+                .map(VariableRef::Shared),
+        }
+    }
+
+    #[inline]
+    pub fn slot_mut(&mut self, slot: usize) -> Option<VariableRefMut<'_>> {
+        match &mut self.slots[slot] {
+            Slot::Empty => None,
+            Slot::Local(var) => Some(VariableRefMut::Local(var)),
+            Slot::Shared(cell) => RefMut::filter_map(cell.borrow_mut(), Option::as_mut)
+                .ok()
+                .map(VariableRefMut::Shared),
+        }
+    }
+
+    // A new variable in the slot, anonymous functions that
+    // captured the one it replaces keep the old one
+    #[inline]
+    pub fn set_slot(&mut self, slot: usize, var: Variable) {
+        self.slots[slot] = Slot::Local(var);
+    }
+
+    // The value of a declared variable, an anonymous function that
+    // refers to the variable it is assigned to already shares it
+    #[inline]
+    pub fn init_slot(&mut self, slot: usize, var: Variable) {
+        match &self.slots[slot] {
+            Slot::Shared(cell) if cell.borrow().is_none() => *cell.borrow_mut() = Some(var),
+            _ => self.set_slot(slot, var),
+        }
+    }
+
+    // The variable in the slot is shared from now on
+    #[inline]
+    pub fn share_slot(&mut self, slot: usize) -> Option<SharedVariable> {
+        let cell = match std::mem::take(&mut self.slots[slot]) {
+            Slot::Empty => return None,
+            Slot::Local(var) => Rc::new(RefCell::new(Some(var))),
+            Slot::Shared(cell) => cell,
+        };
+        self.slots[slot] = Slot::Shared(Rc::clone(&cell));
+        Some(cell)
+    }
+
+    // A shared variable without a value yet, see init_slot
+    #[inline]
+    pub fn declare_shared(&mut self, slot: usize) -> SharedVariable {
+        let cell = Rc::new(RefCell::new(None));
+        self.slots[slot] = Slot::Shared(Rc::clone(&cell));
+        cell
+    }
+
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<VariableRef<'_>> {
+        self.layout.get(name).and_then(|slot| self.slot(*slot))
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, name: &str) -> Option<VariableRefMut<'_>> {
+        match self.layout.get(name) {
+            Some(slot) => self.slot_mut(*slot),
+            None => None,
+        }
+    }
+
+    // A variable that is not shared with anonymous functions
+    #[inline]
+    pub fn get_local(&self, name: &str) -> Option<&Variable> {
+        match self.layout.get(name).map(|slot| &self.slots[*slot]) {
+            Some(Slot::Local(var)) => Some(var),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn get_local_mut(&mut self, name: &str) -> Option<&mut Variable> {
+        match self.layout.get(name).map(|slot| &mut self.slots[*slot]) {
+            Some(Slot::Local(var)) => Some(var),
+            _ => None,
+        }
+    }
+
+    // The slot of the name, a new one is added if it has none
+    #[inline]
+    pub fn slot_of(&mut self, name: &str) -> usize {
+        match self.layout.get(name) {
+            Some(slot) => *slot,
+            None => {
+                let slot = self.slots.len();
+                Rc::make_mut(&mut self.layout).insert(name.to_string(), slot);
+                self.slots.push(Slot::Empty);
+                slot
+            }
+        }
+    }
+
+    #[inline]
+    pub fn insert(&mut self, name: &str, var: Variable) {
+        let slot = self.slot_of(name);
+        self.set_slot(slot, var);
+    }
+
+    #[inline]
+    pub fn insert_shared(&mut self, name: &str, cell: SharedVariable) {
+        let slot = self.slot_of(name);
+        self.slots[slot] = Slot::Shared(cell);
+    }
+
+    // The variables that were assigned a value
+    #[inline]
+    pub fn variables(&self) -> impl Iterator<Item = (&String, VariableRef<'_>)> {
+        self.layout
+            .iter()
+            .filter_map(|(name, slot)| self.slot(*slot).map(|var| (name, var)))
+    }
+
+    // The variables of the scope, they are shared from now on
+    #[inline]
+    pub fn share_variables(&mut self) -> HashMap<String, SharedVariable> {
+        let layout = Rc::clone(&self.layout);
+        layout
+            .iter()
+            .filter_map(|(name, slot)| self.share_slot(*slot).map(|cell| (name.clone(), cell)))
+            .collect()
     }
 }
 
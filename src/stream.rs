@@ -0,0 +1,160 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+// Where the output of 'print' and 'println' goes. Cloning an
+// OutputStream shares the underlying writer instead of copying it.
+
+#[derive(Clone)]
+pub struct OutputStream {
+    writer: Rc<RefCell<Box<dyn Write>>>,
+}
+
+impl OutputStream {
+    #[inline]
+    pub fn new<W: Write + 'static>(writer: W) -> OutputStream {
+        OutputStream {
+            writer: Rc::new(RefCell::new(Box::new(writer))),
+        }
+    }
+
+    #[inline]
+    pub fn stdout() -> OutputStream {
+        OutputStream::new(io::stdout())
+    }
+
+    // Every piece of text written by a script is passed to the callback
+    #[inline]
+    pub fn from_callback<F: FnMut(&str) + 'static>(callback: F) -> OutputStream {
+        OutputStream::new(CallbackWriter { callback })
+    }
+
+    pub fn write_str(&self, text: &str) -> io::Result<()> {
+        let mut writer = self.writer.borrow_mut();
+        writer.write_all(text.as_bytes())?;
+        writer.flush()
+    }
+}
+
+impl fmt::Debug for OutputStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OutputStream").finish_non_exhaustive()
+    }
+}
+
+// Where 'input' reads its lines from
+
+#[derive(Clone)]
+pub struct InputStream {
+    reader: Rc<RefCell<Reader>>,
+}
+
+// Standard input is locked only for the duration of a read so that
+// it stays usable for the rest of the application, e.g. the REPL
+
+enum Reader {
+    Stdin,
+    Custom(Box<dyn BufRead>),
+}
+
+impl InputStream {
+    #[inline]
+    pub fn new<R: BufRead + 'static>(reader: R) -> InputStream {
+        InputStream {
+            reader: Rc::new(RefCell::new(Reader::Custom(Box::new(reader)))),
+        }
+    }
+
+    #[inline]
+    pub fn stdin() -> InputStream {
+        InputStream {
+            reader: Rc::new(RefCell::new(Reader::Stdin)),
+        }
+    }
+
+    // Lines of the string are handed out one by one on each read
+    #[inline]
+    pub fn from_string(text: &str) -> InputStream {
+        InputStream::new(io::Cursor::new(text.to_string().into_bytes()))
+    }
+
+    // Reads a single line without the line terminator,
+    // an empty string is returned at the end of the input
+    pub fn read_line(&self) -> io::Result<String> {
+        let mut line = String::new();
+        match &mut *self.reader.borrow_mut() {
+            Reader::Stdin => io::stdin().read_line(&mut line)?,
+            Reader::Custom(reader) => reader.read_line(&mut line)?,
+        };
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(line)
+    }
+}
+
+impl fmt::Debug for InputStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("InputStream").finish_non_exhaustive()
+    }
+}
+
+// An in-memory writer whose contents remain accessible
+// to the host after it has been handed to a session
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBuffer {
+    buffer: Rc<RefCell<Vec<u8>>>,
+}
+
+impl MemoryBuffer {
+    #[inline]
+    pub fn new() -> MemoryBuffer {
+        MemoryBuffer {
+            buffer: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    #[inline]
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.buffer.borrow()).into_owned()
+    }
+
+    // Returns the contents and empties the buffer
+    #[inline]
+    pub fn take(&self) -> String {
+        let contents = self.contents();
+        self.buffer.borrow_mut().clear();
+        contents
+    }
+}
+
+impl Write for MemoryBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct CallbackWriter<F: FnMut(&str)> {
+    callback: F,
+}
+
+impl<F: FnMut(&str)> Write for CallbackWriter<F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (self.callback)(&String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,7 +1,11 @@
-use crate::error::{Context, Error, ErrorKind};
+use crate::error::{Context, Error, ErrorKind, SyntaxErrorDetails};
 use crate::process_to_string;
 use crate::token::Operator;
-use crate::Session;
+use crate::{HostValue, InputStream, Limits, MemoryBuffer, NativeClass, OutputStream, Session};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 #[test]
 fn math() {
@@ -270,6 +274,61 @@ fn expression() {
     let result = process_to_string("print(2 != 1)", &mut session);
     let expected = "true";
     assert_eq!(result, expected);
+
+    let result = process_to_string("print(1 <= 1)", &mut session);
+    let expected = "true";
+    assert_eq!(result, expected);
+
+    let result = process_to_string("print(2 <= 1)", &mut session);
+    let expected = "false";
+    assert_eq!(result, expected);
+
+    let result = process_to_string("print(1.5 <= 2)", &mut session);
+    let expected = "true";
+    assert_eq!(result, expected);
+
+    let result = process_to_string("print(2 >= 2)", &mut session);
+    let expected = "true";
+    assert_eq!(result, expected);
+
+    let result = process_to_string("print(1 >= 2.5)", &mut session);
+    let expected = "false";
+    assert_eq!(result, expected);
+
+    let result = process_to_string("print(\"asd\" >= \"asd\")", &mut session);
+    let expected = "true";
+    assert_eq!(result, expected);
+
+    let result = process_to_string("print(1 + 1 <= 2 and 3 >= 1)", &mut session);
+    let expected = "true";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("print(1 <= \"a\")", &mut session);
+    let error = Error::new(
+        Context { start: 6, end: 14 },
+        2,
+        ErrorKind::InvalidOperationForTypes(
+            Operator::LessEqual,
+            "int".to_string(),
+            "string".to_string(),
+        ),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    // the operator that failed is the one that is reported
+    session.clear();
+    let result = process_to_string("print(\"a\" >= 1)", &mut session);
+    let expected = "In line 1:\n\n 1| print(\"a\" >= 1)\n              ^\n\n\
+                    Error: Invalid operation '>=' for types 'string' and 'int'";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("print(1.5 > \"a\")", &mut session);
+    let expected = "In line 1:\n\n 1| print(1.5 > \"a\")\n              ^\n\n\
+                    Error: Invalid operation '>' for types 'float' and 'string'";
+    assert_eq!(result, expected);
 }
 
 #[test]
@@ -317,6 +376,89 @@ fn variables() {
     assert_eq!(result, expected);
 }
 
+#[test]
+fn compound_assignment() {
+    let mut session = Session::new();
+
+    let result = process_to_string(
+        "let x = 10.0\nx += 5\nx -= 3\nx *= 2\nx /= 4\nprint(x)",
+        &mut session,
+    );
+    let expected = "6";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let s = \"ab\"; s += \"cd\"; print(s)", &mut session);
+    let expected = "abcd";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let x: float = 1.5; x += 1; print(x)", &mut session);
+    let expected = "2.5";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let v = [1, 2, 3]; v[1] += 10; v[2] *= v[1]; print(v)",
+        &mut session,
+    );
+    let expected = "(1, 12, 36)";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let m = {\"a\": 1}; m[\"a\"] -= 3; print(m)", &mut session);
+    let expected = "{\"a\": -2}";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "class Counter { pub count: int = 0 }\nlet c = Counter::new()\nc.count += 2\nc.count += 3\nprint(c.count)",
+        &mut session,
+    );
+    let expected = "5";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("y += 1", &mut session);
+    let error = Error::new(
+        Context { start: 0, end: 6 },
+        0,
+        ErrorKind::IdentifierNotFound,
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let x = 1; x += 0.5", &mut session);
+    let error = Error::new(
+        Context { start: 11, end: 19 },
+        2,
+        ErrorKind::InvalidAssignment("int".to_string(), "float".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let b = true; b -= 1", &mut session);
+    let error = Error::new(
+        Context { start: 14, end: 20 },
+        2,
+        ErrorKind::InvalidOperationForTypes(Operator::Sub, "bool".to_string(), "int".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let v = [1]; v[3] += 1", &mut session);
+    let error = Error::new(
+        Context { start: 13, end: 22 },
+        1,
+        ErrorKind::IndexOutOfRange(3, 1),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+}
+
 #[test]
 fn looping() {
     let mut session = Session::new();
@@ -421,6 +563,41 @@ A::new().test();",
     );
     let expected = "1\n456\n2\n456\n3\n456\n";
     assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let x = 0; while x < 6 { x = x + 1; if x % 2 == 0 { continue } print(x) }",
+        &mut session,
+    );
+    let expected = "135";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "
+for i in range(0, 3) {
+    for j in range(0, 3) {
+        if i == j {
+            continue;
+        }
+        print(i * 10 + j)
+        print(\" \")
+    }
+    if i == 1 { continue }
+    println(\"\")
+}",
+        &mut session,
+    );
+    let expected = "1 2 \n10 12 20 21 \n";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let continued = 0; for i in range(0, 4) { continued = continued + i; continue; print(i) } print(continued)",
+        &mut session,
+    );
+    let expected = "6";
+    assert_eq!(result, expected);
 }
 
 #[test]
@@ -669,8 +846,7 @@ fn type_system() {
         2,
         ErrorKind::InvalidAssignment("int".to_string(), "bool".to_string()),
     );
-    let expected = "true".to_string()
-        + &error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
     assert_eq!(result, expected);
 
     session.clear();
@@ -700,8 +876,7 @@ fn type_system() {
         2,
         ErrorKind::InvalidAssignment("int".to_string(), "bool".to_string()),
     );
-    let expected = "Backtrace:\n\n  fun called at 2:1\n  root\n\nIn function fun:\n".to_string()
-        + &error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
     assert_eq!(result, expected);
 
     session.clear();
@@ -735,8 +910,7 @@ fn type_system() {
         21,
         ErrorKind::InvalidReturnType("int".to_string(), "bool".to_string()),
     );
-    let expected = "Backtrace:\n\n  f called at 8:1\n  root\n\nIn function f:\n".to_string()
-        + &error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
     assert_eq!(result, expected);
 
     session.clear();
@@ -746,8 +920,7 @@ fn type_system() {
         10,
         ErrorKind::InvalidReturnType("none".to_string(), "int".to_string()),
     );
-    let expected = "Backtrace:\n\n  f called at 6:1\n  root\n\nIn function f:\n".to_string()
-        + &error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
     assert_eq!(result, expected);
 
     session.clear();
@@ -757,8 +930,7 @@ fn type_system() {
         0,
         ErrorKind::InvalidReturnType("none".to_string(), "bool".to_string()),
     );
-    let expected = "Backtrace:\n\n  f called at 5:1\n  root\n\nIn function f:\n".to_string()
-        + &error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
     assert_eq!(result, expected);
 
     session.clear();
@@ -910,8 +1082,7 @@ fn class() {
         10,
         ErrorKind::InvalidAssignment("bool".to_string(), "int".to_string()),
     );
-    let expected = "Backtrace:\n\n  new called at 2:9\n  root\n\nIn function new:\n".to_string()
-        + &error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
     assert_eq!(result, expected);
 
     session.clear();
@@ -1187,6 +1358,96 @@ fn string() {
     assert_eq!(result, expected);
 }
 
+#[test]
+fn string_literal() {
+    let mut session = Session::new();
+
+    session.clear();
+    let result = process_to_string("print(\"a\\tb\\nc\")", &mut session);
+    let expected = "a\tb\nc";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "print(\"quote \\\" backslash \\\\ braces \\{\\}\")",
+        &mut session,
+    );
+    let expected = "quote \" backslash \\ braces {}";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("print(\"\\u{48}\\u{e9}\\u{1F600}\")", &mut session);
+    let expected = "Hé😀";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let name = \"Dust\"; let age = 3; print(\"Hello {name}, you are {age}\")",
+        &mut session,
+    );
+    let expected = "Hello Dust, you are 3";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "print(\"{1 + 2} and {2.5 * 2} {true} {none}\")",
+        &mut session,
+    );
+    let expected = "3 and 5 true none";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let v = [1, 2]; let m = {\"a\": v}; print(\"v = {v}, a = {m[\"a\"][1]}\")",
+        &mut session,
+    );
+    let expected = "v = (1, 2), a = 2";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let s = \"x\"; print(\"{ s + \"y\" }{s}\")", &mut session);
+    let expected = "xyx";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let n = 2; let s = \"{n}\"; n = 3; print(s + \"{n}\")",
+        &mut session,
+    );
+    let expected = "23";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("print(\"a\\qb\")", &mut session);
+    let error = Error::new(
+        Context { start: 6, end: 12 },
+        2,
+        ErrorKind::InvalidEscapeSequence("\\q".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("print(\"\\u{110000}\")", &mut session);
+    let error = Error::new(
+        Context { start: 6, end: 18 },
+        1,
+        ErrorKind::InvalidEscapeSequence("\\u{110000}".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("print(\"a {x} b\")", &mut session);
+    let error = Error::new(
+        Context { start: 10, end: 11 },
+        0,
+        ErrorKind::IdentifierNotFound,
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+}
+
 #[test]
 fn vec() {
     let mut session = Session::new();
@@ -1395,10 +1656,60 @@ fn iteration_limit() {
     let error = Error::new(
         Context { start: 0, end: 0 },
         0,
-        ErrorKind::IterationLimitReached,
+        ErrorKind::CallDepthLimitReached,
     );
     let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
     assert_eq!(result, expected);
+
+    // the limits are kept when the session is reset
+    session.set_limits(Limits::new().max_call_depth(3));
+    session.clear();
+    let result = process_to_string(
+        "fn depth(n: int) -> int { if n == 0 { return 0 } return depth(n - 1) }\nprint(depth(2))\ndepth(3)",
+        &mut session,
+    );
+    let expected = "0Error: Maximum call depth reached";
+    assert_eq!(result, expected);
+
+    session.set_limits(Limits::new().max_loop_iterations(3));
+    let result = process_to_string(
+        "for i in [1, 2, 3] { print(i) }\nwhile true { print(0) }",
+        &mut session,
+    );
+    let expected = "123000Error: Maximum iteration count reached";
+    assert_eq!(result, expected);
+
+    session.set_limits(Limits::new().max_steps(1000));
+    let result = process_to_string("let i = 0\nwhile true { i += 1 }", &mut session);
+    let expected = "Error: Maximum number of steps reached";
+    assert_eq!(result, expected);
+
+    // every input gets the same amount of time
+    session.set_limits(Limits::new().timeout(std::time::Duration::from_millis(20)));
+    for _ in 0..2 {
+        let result = process_to_string("while true { }", &mut session);
+        let expected = "Error: Time limit reached";
+        assert_eq!(result, expected);
+    }
+
+    session.set_limits(Limits::new());
+    let interrupt = session.interrupt_handle();
+    let done = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let done = done.clone();
+        // an interrupt before the input starts running is discarded
+        std::thread::spawn(move || {
+            while !done.load(Ordering::Relaxed) {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                interrupt.interrupt();
+            }
+        })
+    };
+    let result = process_to_string("while true { }", &mut session);
+    done.store(true, Ordering::Relaxed);
+    thread.join().unwrap();
+    let expected = "Error: Execution was interrupted";
+    assert_eq!(result, expected);
 }
 
 #[test]
@@ -1407,13 +1718,70 @@ fn syntax() {
 
     session.clear();
     let result = process_to_string("break", &mut session);
-    let error = Error::new(Context { start: 0, end: 5 }, 0, ErrorKind::SyntaxError);
+    let error = Error::new(
+        Context { start: 0, end: 5 },
+        0,
+        ErrorKind::OutsideLoop("break".to_string()),
+    );
     let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
     assert_eq!(result, expected);
 
     session.clear();
     let result = process_to_string("fn breaker() { break }", &mut session);
-    let error = Error::new(Context { start: 15, end: 18 }, 0, ErrorKind::SyntaxError);
+    let error = Error::new(
+        Context { start: 15, end: 18 },
+        0,
+        ErrorKind::OutsideLoop("break".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("continue", &mut session);
+    let error = Error::new(
+        Context { start: 0, end: 8 },
+        0,
+        ErrorKind::OutsideLoop("continue".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("fn skipper() { continue; }", &mut session);
+    let error = Error::new(
+        Context { start: 15, end: 24 },
+        0,
+        ErrorKind::OutsideLoop("continue".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    // in a function they are reported before anything runs, even in a nested block
+    session.clear();
+    let result = process_to_string(
+        "print(1)\nfn f() { if true { continue } }\nlet g = fn() { while true { break } }",
+        &mut session,
+    );
+    let error = Error::new(
+        Context { start: 28, end: 36 },
+        0,
+        ErrorKind::OutsideLoop("continue".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("if true { break }\nprint(1)", &mut session);
+    let expected = "1";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let continue = 1", &mut session);
+    let error = Error::new(
+        Context { start: 0, end: 16 },
+        4,
+        ErrorKind::IdentifierIsKeyword,
+    );
     let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
     assert_eq!(result, expected);
 
@@ -1467,11 +1835,163 @@ fn syntax() {
     );
     let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
     assert_eq!(result, expected);
-}
 
-#[test]
-fn error_format() {
-    let mut session = Session::new();
+    // every statement with an error is reported
+    session.clear();
+    let result = process_to_string(
+        "fn f() {}\nprint(1 +)\nlet b: Foo = 1\nlet c = ;",
+        &mut session,
+    );
+    let syntax_error =
+        |expected: &[&str], found: &str, construct: Option<&str>, hint: Option<&str>| {
+            ErrorKind::UnexpectedToken(Box::new(SyntaxErrorDetails {
+                expected: expected.iter().map(|item| item.to_string()).collect(),
+                found: found.to_string(),
+                construct: construct.map(String::from),
+                hint: hint.map(String::from),
+            }))
+        };
+    let errors = [
+        Error::new(
+            Context { start: 10, end: 20 },
+            9,
+            syntax_error(&["an expression"], "')'", None, None),
+        ),
+        Error::new(
+            Context { start: 21, end: 36 },
+            7,
+            ErrorKind::UnknownType("Foo".to_string()),
+        ),
+        Error::new(
+            Context { start: 36, end: 45 },
+            8,
+            syntax_error(&["an expression"], "';'", None, None),
+        ),
+    ];
+    let expected = errors
+        .iter()
+        .map(|e| e.print_to_string(session.parse_session.get_source_code(), &Vec::new()))
+        .collect::<Vec<String>>()
+        .join("\n");
+    assert_eq!(result, expected);
+
+    // nothing is defined by input with syntax errors
+    let result = process_to_string("fn f() {}", &mut session);
+    assert_eq!(result, "");
+
+    session.clear();
+    let result = process_to_string("let x = 1\nwhile x then { x = 0 }", &mut session);
+    let error = Error::new(
+        Context { start: 10, end: 32 },
+        8,
+        syntax_error(
+            &["'{'", "an operator"],
+            "'then'",
+            Some("while loop"),
+            Some("the body of the while loop has to be in braces"),
+        ),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("fn f(x: int) {\n  if x = 1 { print(x) }\n}", &mut session);
+    let error = Error::new(
+        Context { start: 15, end: 38 },
+        7,
+        syntax_error(
+            &["'{'", "an operator"],
+            "'='",
+            Some("if statement"),
+            Some("use '==' to compare values"),
+        ),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    // keywords that start a statement are not listed one by one
+    session.clear();
+    let result = process_to_string("fn f() {\n    let x = 1 $\n}", &mut session);
+    let error = Error::new(
+        Context { start: 9, end: 24 },
+        14,
+        syntax_error(
+            &["'}'", "a statement", "an operator"],
+            "'$'",
+            Some("function definition"),
+            None,
+        ),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    // a brace in a string starts an interpolation, even when it is meant literally
+    session.clear();
+    let result = process_to_string("println(\"set {\");", &mut session);
+    let error = Error::new(
+        Context { start: 0, end: 17 },
+        17,
+        syntax_error(
+            &["'}'"],
+            "end of input",
+            Some("string"),
+            Some("'{' starts an interpolation in a string, use '\\{' for a literal brace"),
+        ),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("fn f(a: int {}\nlet s = \"abc\nclass A(1) {}", &mut session);
+    let errors = [
+        Error::new(
+            Context { start: 0, end: 14 },
+            12,
+            syntax_error(&["')'", "','"], "'{'", Some("function signature"), None),
+        ),
+        Error::new(
+            Context { start: 15, end: 27 },
+            12,
+            syntax_error(
+                &["'\"'"],
+                "end of line",
+                Some("string"),
+                Some("strings have to be closed on the line they start on"),
+            ),
+        ),
+        Error::new(
+            Context { start: 28, end: 41 },
+            8,
+            syntax_error(&["')'", "a name"], "'1'", Some("class definition"), None),
+        ),
+    ];
+    let expected = errors
+        .iter()
+        .map(|e| e.print_to_string(session.parse_session.get_source_code(), &Vec::new()))
+        .collect::<Vec<String>>()
+        .join("\n");
+    assert_eq!(result, expected);
+
+    // a stray bracket does not close the block it is in
+    session.clear();
+    let result = process_to_string("fn f() {\n)\n}\nprint(1)", &mut session);
+    let error = Error::new(
+        Context { start: 9, end: 10 },
+        0,
+        syntax_error(
+            &["'}'", "a statement"],
+            "')'",
+            Some("function definition"),
+            None,
+        ),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn error_format() {
+    let mut session = Session::new();
 
     let result = process_to_string(
         "7 + 2\n0 + 0\nprint(1 +\n2 + f(12) - 3\n+ 9 * 5)",
@@ -1568,3 +2088,1612 @@ In line 13:
 Error: Division by zero";
     assert_eq!(result, expected);
 }
+
+#[test]
+fn host_api() {
+    let mut session = Session::new();
+
+    let counter = Rc::new(Cell::new(0));
+    let counter_clone = counter.clone();
+    session
+        .register_function("count", &[], move |_| {
+            counter_clone.set(counter_clone.get() + 1);
+            Ok(HostValue::Int(counter_clone.get()))
+        })
+        .unwrap();
+    session
+        .register_function("add", &[("a", "int"), ("b", "int")], |args| {
+            match (&args[0], &args[1]) {
+                (HostValue::Int(a), HostValue::Int(b)) => Ok(HostValue::Int(a + b)),
+                _ => unreachable!(),
+            }
+        })
+        .unwrap();
+    session
+        .register_function("fail", &[("a", "string")], |args| match &args[0] {
+            HostValue::Str(message) => Err(message.clone()),
+            _ => unreachable!(),
+        })
+        .unwrap();
+
+    let result = process_to_string("count(); count(); print(count())", &mut session);
+    let expected = "3";
+    assert_eq!(result, expected);
+    assert_eq!(counter.get(), 3);
+
+    let result = process_to_string("print(add(2, 40))", &mut session);
+    let expected = "42";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("print(add(2, \"40\"))", &mut session);
+    let error = Error::new(
+        Context { start: 6, end: 18 },
+        7,
+        ErrorKind::InvalidArgumentType("string".to_string(), "int".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("fail(\"Host error\")", &mut session);
+    let error = Error::new(
+        Context { start: 0, end: 18 },
+        0,
+        ErrorKind::CustomError("Host error".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    assert!(matches!(
+        session.register_function("add", &[], |_| Ok(HostValue::None)),
+        Err(ErrorKind::FunctionAlreadyDefined)
+    ));
+    assert!(matches!(
+        session.register_function("g", &[("a", "Nope")], |_| Ok(HostValue::None)),
+        Err(ErrorKind::UnknownType(_))
+    ));
+
+    let class = NativeClass::new()
+        .function("sum", &[("a", "Vec")], |args| match &args[0] {
+            HostValue::Vec(items) => Ok(HostValue::Int(
+                items
+                    .iter()
+                    .map(|item| match item {
+                        HostValue::Int(i) => *i,
+                        _ => 0,
+                    })
+                    .sum(),
+            )),
+            _ => unreachable!(),
+        })
+        .function("greeting", &[], |_| {
+            Ok(HostValue::Str("Hello from the host".to_string()))
+        });
+    session.register_class("Host", class).unwrap();
+
+    let result = process_to_string(
+        "let v = Vec::new(); v.push(1); v.push(2); v.push(3); println(Host::sum(v)); print(Host::greeting())",
+        &mut session,
+    );
+    let expected = "6\nHello from the host";
+    assert_eq!(result, expected);
+
+    let result = process_to_string("let h: Host = none", &mut session);
+    assert!(result.contains("Cannot assign to variable with type 'Host' a value of type 'none'"));
+
+    session.clear();
+    let result = process_to_string("print(Host::sum(range(0, 5)) + add(1, 1))", &mut session);
+    let expected = "12";
+    assert_eq!(result, expected);
+
+    assert!(matches!(
+        session.register_class("Vec", NativeClass::new()),
+        Err(ErrorKind::IdentifierIsTypename)
+    ));
+}
+
+#[test]
+fn eval() {
+    let mut session = Session::new();
+
+    assert_eq!(session.eval("1 + 2").unwrap(), (HostValue::Int(3)));
+    assert_eq!(
+        session.eval("let x = 5\nx * 2.5").unwrap(),
+        (HostValue::Float(12.5))
+    );
+    assert_eq!(session.eval("let y = x").unwrap(), (HostValue::None));
+    assert_eq!(
+        session.eval("\"a\" + \"b\"").unwrap(),
+        (HostValue::Str("ab".to_string()))
+    );
+
+    let result = session.eval("let v = Vec::new()\nv.push(true)\nv.push(none)\nv");
+    assert_eq!(
+        result.unwrap(),
+        (HostValue::Vec(vec![HostValue::Bool(true), HostValue::None]))
+    );
+
+    // the value of a function call is returned but nothing is printed
+    session
+        .eval("fn twice(n: int) -> int { return n * 2 }")
+        .unwrap();
+    assert_eq!(session.eval("twice(21)").unwrap(), (HostValue::Int(42)));
+
+    let result = session.eval("class Point(x: int) { pub x: int = x; }\nPoint::new(1)");
+    match result {
+        Ok(HostValue::Object(object)) => assert_eq!(object.typename(), "Point"),
+        _ => panic!("expected an object"),
+    }
+
+    session.clear();
+    let error = session.eval("1 + 1\nprint(1 / 0)").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::ZeroDivision));
+    assert_eq!((error.line(), error.column()), (2, 9));
+    assert!(error.backtrace().is_empty());
+    let expected = Error::new(Context { start: 12, end: 17 }, 2, ErrorKind::ZeroDivision)
+        .print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(error.message(), expected);
+    assert_eq!(error.to_string(), error.message());
+
+    session.clear();
+    let error = session
+        .eval("fn f() {\n    g()\n}\nfn g() {\n    h()\n}\nf()")
+        .unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::FunctionNotFound));
+    assert_eq!((error.line(), error.column()), (5, 5));
+    let backtrace: Vec<(&str, usize, usize)> = error
+        .backtrace()
+        .iter()
+        .map(|item| (item.name.as_str(), item.line, item.col))
+        .collect();
+    assert_eq!(backtrace, vec![("g", 2, 5), ("f", 7, 1)]);
+    assert!(error.message().starts_with("Backtrace:"));
+
+    // a failed evaluation does not affect the next one
+    assert_eq!(session.eval("2 * 3").unwrap(), (HostValue::Int(6)));
+
+    session.clear();
+    let error = session.eval("let x = (1").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::UnexpectedToken(..)));
+}
+
+#[test]
+fn streams() {
+    let mut session = Session::new();
+    let buffer = MemoryBuffer::new();
+    session.set_output(OutputStream::new(buffer.clone()));
+    session.set_input(InputStream::from_string("Alice\r\n42\n"));
+
+    session.process(
+        "let name = input(\"Name: \")
+let age = int::parse(input(\"Age: \")).unwrap()
+println(name + \" \" + (age + 1).to_string())
+print(input(\"> \").len())",
+    );
+    assert_eq!(buffer.take(), "Name: Age: Alice 43\n> 0");
+
+    // error messages are written to the same stream
+    session.clear();
+    session.process("print(1)\nprint(f())");
+    let error = Error::new(
+        Context { start: 9, end: 19 },
+        6,
+        ErrorKind::FunctionNotFound,
+    );
+    let expected = format!(
+        "1{}\n",
+        error.print_to_string(session.parse_session.get_source_code(), &Vec::new())
+    );
+    assert_eq!(buffer.contents(), expected);
+
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let captured = output.clone();
+    session.set_output(OutputStream::from_callback(move |text| {
+        captured.borrow_mut().push(text.to_string())
+    }));
+    session.process("for i in range(0, 3) { print(i) }");
+    assert_eq!(*output.borrow(), vec!["0", "1", "2"]);
+    assert_eq!(buffer.take(), expected);
+}
+
+#[test]
+fn map() {
+    let mut session = Session::new();
+
+    let result = process_to_string("let m = Map::new(); print(m); print(m.len())", &mut session);
+    let expected = "{}0";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let m = { \"b\": 2, \"a\": 1 + 1, \"c\": \"three\", }
+m.insert(\"a\", 1)
+print(m)
+print(m.get(\"c\"))
+print(m.len())",
+        &mut session,
+    );
+    let expected = "{\"a\": 1, \"b\": 2, \"c\": \"three\"}three3";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let m = { 2: true, 1: none }
+let v = Vec::new(); v.push(1)
+m.insert(3, v); m.insert(4, m)
+print(m.to_string() + \" \" + (typeof m))",
+        &mut session,
+    );
+    let expected = "{1: none, 2: true, 3: [...], 4: {...}} Map";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let m = { \"x\": 1, \"y\": 2 }
+print(m.contains_key(\"x\")); print(m.contains_key(\"z\"))
+print(m.remove(\"x\")); print(m.contains_key(\"x\"))
+print(m.keys()); print(m.values())
+m.clear(); print(m.len())",
+        &mut session,
+    );
+    let expected = "truefalse1false(\"y\")(2)0";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let m = { \"one\": 1, \"two\": 2, \"three\": 3 }
+for key in m { m.remove(key); print(key + \" \"); }
+print(m.len())",
+        &mut session,
+    );
+    let expected = "one three two 0";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let m = {}; let n = m; print(m == n); print(m != {}); print(m == none)",
+        &mut session,
+    );
+    let expected = "truefalsefalse";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let m = { \"a\": 1 }; m.get(\"b\");", &mut session);
+    let error = Error::new(
+        Context { start: 20, end: 31 },
+        2,
+        ErrorKind::KeyNotFound("b".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let m = Map::new(); m.insert(1.5, 1);", &mut session);
+    let error = Error::new(
+        Context { start: 20, end: 37 },
+        2,
+        ErrorKind::InvalidKeyType("float".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let m = { 1: 1, Vec::new(): 2 }", &mut session);
+    let error = Error::new(
+        Context { start: 16, end: 26 },
+        0,
+        ErrorKind::InvalidKeyType("Vec".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("for i in 5 { }", &mut session);
+    let error = Error::new(
+        Context { start: 0, end: 14 },
+        9,
+        ErrorKind::ForLoopNotVec("int".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn index() {
+    let mut session = Session::new();
+
+    let result = process_to_string(
+        "let v = [1, 2.5, \"a\", [3, 4], ]; print(v); print([]); print(typeof [1])",
+        &mut session,
+    );
+    let expected = "(1, 2.5, \"a\", [...])()Vec";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let v = [1, 2, [3, 4]]
+print(v[0] + v[2][1] * 2)
+print(-v[1] * 2)
+print([5, 6][1])
+print(v[v[0]])",
+        &mut session,
+    );
+    let expected = "9-462";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let v = [1, 2, [3, 4]]
+v[0] = \"x\"; v[2][1] = 40
+print(v[0]); print(v[2])",
+        &mut session,
+    );
+    let expected = "x(3, 40)";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "class Bag(items: Vec) {
+    pub items: Vec = items;
+    pub fn first(self) -> dyn { return self.items[0]; }
+}
+let bags = [Bag::new([1, 2])]
+bags[0].items[1] = 20
+bags[0].items = [bags[0].first(), bags[0].items[1]]
+print(bags[0].items)",
+        &mut session,
+    );
+    let expected = "(1, 20)";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let s = \"hello\"; print(s[0] + s[4]); print(typeof s[1])",
+        &mut session,
+    );
+    let expected = "hostring";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let m = { \"a\": 1 }; m[\"b\"] = 2; m[\"a\"] = m[\"a\"] + m[\"b\"]; print(m)",
+        &mut session,
+    );
+    let expected = "{\"a\": 3, \"b\": 2}";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let v = [1, 2]; print(v[2])", &mut session);
+    let error = Error::new(
+        Context { start: 22, end: 26 },
+        1,
+        ErrorKind::IndexOutOfRange(2, 2),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let v = [1, 2]; v[-1] = 0", &mut session);
+    let error = Error::new(
+        Context { start: 16, end: 26 },
+        1,
+        ErrorKind::IndexOutOfRange(-1, 2),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let s = \"abc\"; print(s[3])", &mut session);
+    let error = Error::new(
+        Context { start: 21, end: 25 },
+        1,
+        ErrorKind::IndexOutOfRange(3, 3),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let v = [1]; print(v[\"a\"])", &mut session);
+    let error = Error::new(
+        Context { start: 19, end: 25 },
+        1,
+        ErrorKind::InvalidOperationForTypes(
+            Operator::Index,
+            "Vec".to_string(),
+            "string".to_string(),
+        ),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let s = \"abc\"; s[0] = \"x\"", &mut session);
+    let error = Error::new(
+        Context { start: 15, end: 25 },
+        1,
+        ErrorKind::InvalidOperationForTypes(
+            Operator::Index,
+            "string".to_string(),
+            "int".to_string(),
+        ),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let m = {}; print(m[1])", &mut session);
+    let error = Error::new(
+        Context { start: 18, end: 22 },
+        1,
+        ErrorKind::KeyNotFound("1".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn import() {
+    let dir = std::env::temp_dir().join(format!("dust_import_test_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    let write = |name: &str, contents: &str| std::fs::write(dir.join(name), contents).unwrap();
+
+    write("main.dust", "");
+    write("util.dust", "fn twice(x: int) -> int { return x * 2 }\n");
+    write(
+        "lib/shapes.dust",
+        "import \"../util.dust\"\n\
+         class Square(side: int) {\n\
+         \x20   side: int = side\n\
+         \x20   pub fn area(self) -> int { return self.side * self.side }\n\
+         }\n\
+         fn make(side: int) -> Square { return Square::new(util::twice(side)) }\n\
+         fn describe(s: Square) -> string { return prefix() + s.area().to_string() }\n\
+         fn prefix() -> string { return \"area: \" }\n\
+         println(\"shapes loaded\")\n",
+    );
+    write("cycle_a.dust", "import \"cycle_b.dust\"\n");
+    write("cycle_b.dust", "import \"cycle_a.dust\"\n");
+    write("broken.dust", "fn fail() {\n    let x = 1 + true\n}\n");
+    write("bad-name.dust", "");
+    write(
+        "counter.dust",
+        "fn get() -> int { return 1 }\nlet counter = 42\n",
+    );
+
+    let mut session = Session::new();
+    session.set_script_path(dir.join("main.dust"));
+
+    let result = process_to_string(
+        "import \"lib/shapes.dust\"\n\
+         let s: shapes::Square = shapes::make(2)\n\
+         println(shapes::describe(s))\n\
+         println(typeof s)\n\
+         print(shapes::Square::new(3).area())",
+        &mut session,
+    );
+    let expected = "shapes loaded\narea: 16\nshapes::Square\n9";
+    assert_eq!(result, expected);
+
+    // importing a file again has no effect and its namespace is still usable
+    let result = process_to_string("import \"util.dust\"\nprint(util::twice(5))", &mut session);
+    let expected = "10";
+    assert_eq!(result, expected);
+
+    // names of an imported file are only available through its namespace
+    session.clear();
+    session.set_script_path(dir.join("main.dust"));
+    let result = process_to_string("import \"util.dust\"\ntwice(1)", &mut session);
+    let error = Error::new(
+        Context { start: 19, end: 27 },
+        0,
+        ErrorKind::FunctionNotFound,
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    session.set_script_path(dir.join("main.dust"));
+    let result = process_to_string("import \"cycle_a.dust\"", &mut session);
+    let expected = "In file 'cycle_b.dust', line 1:\n\n \
+                    1| import \"cycle_a.dust\"\n           \
+                    ^\n\nError: Circular import of 'cycle_a.dust'";
+    assert_eq!(result, expected);
+
+    session.clear();
+    session.set_script_path(dir.join("main.dust"));
+    let result = process_to_string("import \"main.dust\"", &mut session);
+    let error = Error::new(
+        Context { start: 0, end: 18 },
+        7,
+        ErrorKind::CircularImport("main.dust".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    session.set_script_path(dir.join("main.dust"));
+    let result = process_to_string("import \"missing.dust\"", &mut session);
+    assert!(result.contains("Error: Cannot import 'missing.dust': "));
+
+    session.clear();
+    session.set_script_path(dir.join("main.dust"));
+    let result = process_to_string("import \"bad-name.dust\"", &mut session);
+    let error = Error::new(
+        Context { start: 0, end: 22 },
+        7,
+        ErrorKind::ImportFailed(
+            "bad-name.dust".to_string(),
+            "'bad-name' is not a valid namespace name".to_string(),
+        ),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    // errors inside an imported file report the file and the line within it
+    session.clear();
+    session.set_script_path(dir.join("main.dust"));
+    let result = process_to_string("import \"broken.dust\"\n\nbroken::fail()", &mut session);
+    let expected = "In file 'broken.dust', line 2:\n\n \
+                    2|     let x = 1 + true\n                  \
+                    ^\n\nError: Invalid operation '+' for types 'int' and 'bool'";
+    assert_eq!(result, expected);
+
+    session.clear();
+    session.set_script_path(dir.join("main.dust"));
+    let error = session
+        .eval("import \"broken.dust\"\nbroken::fail()")
+        .unwrap_err();
+    assert_eq!(error.file(), Some("broken.dust"));
+    assert_eq!(error.line(), 2);
+
+    // an imported file cannot replace the variables of the importing one
+    session.clear();
+    session.set_script_path(dir.join("main.dust"));
+    let result = process_to_string(
+        "let counter = 1\nimport \"counter.dust\"\nprint(counter)",
+        &mut session,
+    );
+    let expected = "In file 'counter.dust', line 2:\n\n \
+                    2| let counter = 42\n    \
+                    ^\n\nError: Variables cannot be declared at the top level of an imported file";
+    assert_eq!(result, expected);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn first_class_functions() {
+    let mut session = Session::new();
+
+    let result = process_to_string(
+        "fn double(x: int) -> int { return x * 2 }\nlet f = double\nprint(f(4))",
+        &mut session,
+    );
+    let expected = "8";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let square = fn(x: int) -> int { return x * x }\nprint(square(5))",
+        &mut session,
+    );
+    let expected = "25";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "fn make_adder(n: int) -> Function { return fn(x: int) -> int { return x + n } }\nlet add2 = make_adder(2)\nprint(add2(3))\nprint(make_adder(10)(1))",
+        &mut session,
+    );
+    let expected = "511";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "fn apply(f: Function, x: dyn) -> dyn { return f(x) }\nprint(apply(fn(s: string) -> string { return s + \"!\" }, \"hi\"))",
+        &mut session,
+    );
+    let expected = "hi!";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let fs = [fn(x: int) -> int { return x + 1 }, fn(x: int) -> int { return x - 1 }]\nprint(fs[0](3))\nprint(fs[1](3))",
+        &mut session,
+    );
+    let expected = "42";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let v = [1, 2, 3, 4]\nprint(v.map(fn(x: int) -> int { return x * 10 }))\nprint(v.filter(fn(x: int) -> bool { return x % 2 == 0 }))",
+        &mut session,
+    );
+    let expected = "(10, 20, 30, 40)(2, 4)";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "class Button { pub on_click: Function = fn() -> string { return \"clicked\" } }\nlet b = Button::new()\nprint(b.on_click())",
+        &mut session,
+    );
+    let expected = "clicked";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "fn one() -> int { return 1 }\nlet f = one\nlet g = fn() {}\nprint(typeof f)\nprint(f)\nprint(f == one)\nprint(f == g)",
+        &mut session,
+    );
+    let expected = "Functionfn() -> inttruefalse";
+    assert_eq!(result, expected);
+
+    // closures share the variables they capture with the scope they were created in
+    session.clear();
+    let result = process_to_string(
+        "fn make_counter() -> Function {\n    let count = 0\n    return fn() -> int { count += 1 return count }\n}\n\
+         let next = make_counter()\nnext()\nnext()\nprint(next())\nprint(make_counter()())",
+        &mut session,
+    );
+    let expected = "31";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let items = []\nlet add = fn(x: int) { items.push(x) }\nadd(1)\nadd(2)\nprint(items)\n\
+         let total = 0\nlet bump = fn() { total = total + 5 }\nbump()\nbump()\nprint(total)",
+        &mut session,
+    );
+    let expected = "(1, 2)10";
+    assert_eq!(result, expected);
+
+    // an anonymous function can call the variable it is assigned to
+    session.clear();
+    let result = process_to_string(
+        "let fact = fn(n: int) -> int {\n    if n <= 1 { return 1 }\n    return n * fact(n - 1)\n}\n\
+         fn fib(n: int) -> int {\n    let f = fn(n: int) -> int { if n < 2 { return n } return f(n - 1) + f(n - 2) }\n    return f(n)\n}\n\
+         print(fact(5))\nprint(fib(10))",
+        &mut session,
+    );
+    let expected = "12055";
+    assert_eq!(result, expected);
+
+    // every iteration of a loop declares a new variable
+    session.clear();
+    let result = process_to_string(
+        "fn f() {\n    let fs = []\n    for i in [1, 2, 3] { let x = i fs.push(fn() -> int { return x }) }\n    for g in fs { print(g()) }\n}\nf()",
+        &mut session,
+    );
+    let expected = "123";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("let n = 5; (n)(1)", &mut session);
+    let error = Error::new(
+        Context { start: 11, end: 17 },
+        3,
+        ErrorKind::NotCallable("int".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let v = [1, 2]; v.filter(fn(x: int) -> int { return x })",
+        &mut session,
+    );
+    let error = Error::new(
+        Context { start: 16, end: 56 },
+        2,
+        ErrorKind::InvalidReturnType("int".to_string(), "bool".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn inheritance() {
+    let mut session = Session::new();
+
+    let animal = "class Animal(name: string) {
+    pub name: string = name
+    sound: string = \"...\"
+    pub fn speak(self) -> string { return self.name + \" says \" + self.sound }
+    pub fn describe(self) -> string { return \"an animal\" }
+}
+class Dog(name: string) : Animal {
+    pub fn describe(self) -> string { return \"a dog, \" + super.describe() }
+    pub fn bark(self) -> string { self.sound = \"woof\"; return self.speak() }
+}
+";
+
+    let result = process_to_string(
+        &format!("{}let d = Dog::new(\"Rex\")\nprint(d.bark())", animal),
+        &mut session,
+    );
+    let expected = "Rex says woof";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        &format!(
+            "{}fn describe(a: Animal) -> string {{ return a.describe() }}\nprint(describe(Dog::new(\"Rex\")))\nprint(describe(Animal::new(\"Tom\")))",
+            animal
+        ),
+        &mut session,
+    );
+    let expected = "a dog, an animalan animal";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        &format!(
+            "{}class Owner {{ pub pet: Animal = Animal::new(\"Tom\") }}\nlet o = Owner::new()\no.pet = Dog::new(\"Rex\")\nprint(o.pet.name)\nprint(typeof o.pet)",
+            animal
+        ),
+        &mut session,
+    );
+    let expected = "RexDog";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "class A { pub fn f(self) -> int { return 1 } }\nclass B : A {}\nclass C : B { pub fn f(self) -> int { return super.f() + 10 } }\nprint(C::new().f())",
+        &mut session,
+    );
+    let expected = "11";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("class A : Vec {}", &mut session);
+    let error = Error::new(
+        Context { start: 0, end: 16 },
+        10,
+        ErrorKind::InvalidParentClass("Vec".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("class A : Base {}", &mut session);
+    let error = Error::new(
+        Context { start: 0, end: 17 },
+        10,
+        ErrorKind::UnknownType("Base".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "class A { pub fn f(self) -> int { return super.f() } }",
+        &mut session,
+    );
+    let error = Error::new(Context { start: 41, end: 50 }, 0, ErrorKind::InvalidSuper);
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "class A {}\nclass B : A {}\nfn f(b: B) {}\nf(A::new())",
+        &mut session,
+    );
+    let error = Error::new(
+        Context { start: 40, end: 51 },
+        2,
+        ErrorKind::InvalidArgumentType("A".to_string(), "B".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    // the parent's properties are initialized with the arguments of the child's constructor
+    session.clear();
+    let result = process_to_string(
+        "class Animal(name: string) { pub name: string = name }\nclass Dog(nick: string) : Animal {}",
+        &mut session,
+    );
+    let error = Error::new(
+        Context { start: 55, end: 89 },
+        26,
+        ErrorKind::MissingParentParameter(
+            "Animal".to_string(),
+            "name".to_string(),
+            "string".to_string(),
+        ),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    // an override is called in place of the method it replaces
+    session.clear();
+    let result = process_to_string(
+        "class A { pub fn f(self, x: int) -> int { return x } }\nclass B : A { pub fn f(self) -> string { return \"b\" } }",
+        &mut session,
+    );
+    let error = Error::new(
+        Context {
+            start: 73,
+            end: 108,
+        },
+        3,
+        ErrorKind::OverrideMismatch("A".to_string(), "f".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "class A { pub fn f(self) -> int { return 1 } }\nclass B : A { fn f(self) -> int { return 2 } }",
+        &mut session,
+    );
+    let error = Error::new(
+        Context { start: 61, end: 91 },
+        3,
+        ErrorKind::OverrideMismatch("A".to_string(), "f".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn interfaces() {
+    let mut session = Session::new();
+
+    let shapes = "interface Shape {
+    fn name(self) -> string
+    fn area(self) -> float
+}
+class Square(side: float) impl Shape {
+    side: float = side
+    pub fn name(self) -> string { return \"square\" }
+    pub fn area(self) -> float { return self.side * self.side }
+}
+class Circle(r: float) impl Shape {
+    r: float = r
+    pub fn name(self) -> string { return \"circle\" }
+    pub fn area(self) -> float { return 3.0 * self.r * self.r }
+}
+fn describe(s: Shape) -> string { return s.name() + \" \" + s.area().to_string() }
+";
+
+    let result = process_to_string(
+        &format!(
+            "{}print(describe(Square::new(2.0)))\nprint(describe(Circle::new(1.0)))",
+            shapes
+        ),
+        &mut session,
+    );
+    let expected = "square 4circle 3";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        &format!(
+            "{}class Tile(side: float) : Square {{}}\nlet s: Shape = Tile::new(1.0)\nprint(describe(s))",
+            shapes
+        ),
+        &mut session,
+    );
+    let expected = "square 1";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "interface I { fn f(self) -> int }\nclass A impl I {}",
+        &mut session,
+    );
+    let error = Error::new(
+        Context { start: 34, end: 51 },
+        13,
+        ErrorKind::InterfaceNotImplemented("I".to_string(), "f".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "interface I { fn f(self) -> int }\nclass A impl I { fn f(self) -> int { return 1 } }",
+        &mut session,
+    );
+    let error = Error::new(
+        Context { start: 34, end: 84 },
+        13,
+        ErrorKind::InterfaceMethodMismatch("I".to_string(), "f".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("class A {}\nclass B impl A {}", &mut session);
+    let error = Error::new(
+        Context { start: 11, end: 28 },
+        13,
+        ErrorKind::NotAnInterface("A".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(&format!("{}describe(5)", shapes), &mut session);
+    let error = Error::new(
+        Context {
+            start: shapes.len(),
+            end: shapes.len() + 11,
+        },
+        9,
+        ErrorKind::InvalidArgumentType("int".to_string(), "Shape".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn type_checking() {
+    let mut session = Session::new();
+
+    // every error is reported and nothing is executed
+    let result = process_to_string(
+        "print(1)\nlet a: int = \"s\"\nlet b = 2 * true",
+        &mut session,
+    );
+    let first = Error::new(
+        Context { start: 9, end: 26 },
+        11,
+        ErrorKind::InvalidAssignment("int".to_string(), "string".to_string()),
+    );
+    let second = Error::new(
+        Context { start: 34, end: 42 },
+        2,
+        ErrorKind::InvalidOperationForTypes(Operator::Mult, "int".to_string(), "bool".to_string()),
+    );
+    let expected = first.print_to_string(session.parse_session.get_source_code(), &Vec::new())
+        + "\n"
+        + &second.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    // functions are checked even if they are never called
+    session.clear();
+    let result = process_to_string("fn f(x: int) -> string {\n    return x\n}", &mut session);
+    let error = Error::new(
+        Context { start: 29, end: 38 },
+        7,
+        ErrorKind::InvalidReturnType("int".to_string(), "string".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("fn f() -> int {\n    print(1)\n}", &mut session);
+    let error = Error::new(
+        Context { start: 0, end: 13 },
+        10,
+        ErrorKind::InvalidReturnType("none".to_string(), "int".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("fn f(x: int) {}\nf(\"a\")\nf(1, 2)", &mut session);
+    let first = Error::new(
+        Context { start: 16, end: 23 },
+        2,
+        ErrorKind::InvalidArgumentType("string".to_string(), "int".to_string()),
+    );
+    let second = Error::new(
+        Context { start: 23, end: 30 },
+        0,
+        ErrorKind::InvalidNumberOfArguments,
+    );
+    let expected = first.print_to_string(session.parse_session.get_source_code(), &Vec::new())
+        + "\n"
+        + &second.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    // a variable keeps its type if it is the same after every branch
+    session.clear();
+    let result = process_to_string(
+        "let x = 1\nif true { x = 2 } else { let y = 1 }\nlet z: string = x",
+        &mut session,
+    );
+    let error = Error::new(
+        Context { start: 47, end: 64 },
+        14,
+        ErrorKind::InvalidAssignment("string".to_string(), "int".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    // a value may be an instance of a subclass of its static type
+    session.clear();
+    let result = process_to_string(
+        "class A {}\nclass B : A {}\nfn f(a: A) -> B { return a }\nlet b: A = B::new()\nprint(typeof f(b))",
+        &mut session,
+    );
+    assert_eq!(result, "B");
+
+    // dyn values and unreachable code are not checked
+    session.clear();
+    let result = process_to_string(
+        "fn f(v: dyn) { let x: int = v }\nfn g() -> int { return 1; return \"s\" }\nprint(g())",
+        &mut session,
+    );
+    assert_eq!(result, "1");
+}
+
+#[test]
+fn check_mode() {
+    let mut session = Session::new();
+    let buffer = MemoryBuffer::new();
+    session.set_output(OutputStream::new(buffer.clone()));
+
+    // nothing is executed and every error is printed
+    let is_valid = session.check("print(1)\nprint(x)\nfoo(1)\nlet y: int = \"s\"");
+    assert!(!is_valid);
+    let errors = [
+        Error::new(
+            Context { start: 15, end: 16 },
+            0,
+            ErrorKind::IdentifierNotFound,
+        ),
+        Error::new(
+            Context { start: 18, end: 24 },
+            0,
+            ErrorKind::FunctionNotFound,
+        ),
+        Error::new(
+            Context { start: 25, end: 42 },
+            11,
+            ErrorKind::InvalidAssignment("int".to_string(), "string".to_string()),
+        ),
+    ];
+    let expected: String = errors
+        .iter()
+        .map(|e| e.print_to_string(session.parse_session.get_source_code(), &Vec::new()) + "\n")
+        .collect();
+    assert_eq!(buffer.take(), expected);
+
+    // identifiers have to be declared in the scope they are used in
+    session.clear();
+    let is_valid = session.check("let x = 1\nfn f() {\n    x = 2\n}");
+    assert!(!is_valid);
+    let error = Error::new(
+        Context { start: 23, end: 28 },
+        0,
+        ErrorKind::IdentifierNotFound,
+    );
+    let expected =
+        error.print_to_string(session.parse_session.get_source_code(), &Vec::new()) + "\n";
+    assert_eq!(buffer.take(), expected);
+
+    session.clear();
+    let is_valid = session.check(
+        "class A {\n    n: int = 0\n    pub fn get(self) -> int { return self.n }\n}\n\
+         let a = A::new()\nlet f = fn(x: int) -> int { return x + a.get() }\nprint(f(1))",
+    );
+    assert!(is_valid);
+    assert_eq!(buffer.take(), "");
+
+    session.clear();
+    let is_valid = session.check(
+        "let fact = fn(n: int) -> int {\n    if n <= 1 { return 1 }\n    return n * fact(n - 1)\n}",
+    );
+    assert!(is_valid);
+    assert_eq!(buffer.take(), "");
+
+    // statements that were built are checked when others have errors
+    session.clear();
+    let is_valid = session.check("let z: Nope = 1\nfoo(1)\nfn g() {\n    bar()\n}");
+    assert!(!is_valid);
+    let errors = [
+        Error::new(
+            Context { start: 0, end: 15 },
+            7,
+            ErrorKind::UnknownType("Nope".to_string()),
+        ),
+        Error::new(
+            Context { start: 16, end: 22 },
+            0,
+            ErrorKind::FunctionNotFound,
+        ),
+        Error::new(
+            Context { start: 36, end: 41 },
+            0,
+            ErrorKind::FunctionNotFound,
+        ),
+    ];
+    let expected: String = errors
+        .iter()
+        .map(|e| e.print_to_string(session.parse_session.get_source_code(), &Vec::new()) + "\n")
+        .collect();
+    assert_eq!(buffer.take(), expected);
+
+    // a loop jump outside of a loop is found without calling the function
+    session.clear();
+    let is_valid = session.check("fn f() {\n    if true { break }\n}");
+    assert!(!is_valid);
+    let error = Error::new(
+        Context { start: 23, end: 28 },
+        0,
+        ErrorKind::OutsideLoop("break".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(buffer.take(), expected + "\n");
+}
+
+#[test]
+fn bytecode() {
+    let mut session = Session::new();
+
+    // globals keep their slots when more input is compiled
+    let result = process_to_string("let a = 1", &mut session);
+    assert_eq!(result, "");
+    let result = process_to_string("let b = a + 1\nprint(a + b)", &mut session);
+    let expected = "3";
+    assert_eq!(result, expected);
+
+    // operands are evaluated from left to right
+    session.clear();
+    let result = process_to_string(
+        "fn f(x: int) -> int { print(x) return x }\nprint(f(1) + f(2) * f(3))",
+        &mut session,
+    );
+    let expected = "1237";
+    assert_eq!(result, expected);
+
+    // a function that was not defined when the call was compiled
+    session.clear();
+    let result = process_to_string("fn f() -> int { return g() }\nf()", &mut session);
+    let expected = "Backtrace:
+
+  f called at 2:1
+  root
+
+In function f:
+In line 1:
+
+ 1| fn f() -> int { return g() }
+                           ^
+
+Error: Function not found";
+    assert_eq!(result, expected);
+    let result = process_to_string("fn g() -> int { return 7 }\nprint(f())", &mut session);
+    let expected = "7";
+    assert_eq!(result, expected);
+
+    // a parameter that holds a function is called in place of the function
+    session.clear();
+    let result = process_to_string(
+        "fn g() -> int { return 1 }\nfn h(g: Function) -> int { return g() }\nprint(h(fn() -> int { return 2 }))",
+        &mut session,
+    );
+    let expected = "2";
+    assert_eq!(result, expected);
+
+    // variables that are only used by a nested function are captured as well
+    session.clear();
+    let result = process_to_string(
+        "let n = 5\nlet f = fn() -> Function { return fn() -> int { return n } }\nprint(f()())",
+        &mut session,
+    );
+    let expected = "5";
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn scoping() {
+    let mut session = Session::new();
+
+    // a variable declared in a block shadows the outer one until the block ends
+    let result = process_to_string(
+        "let x = 1\nif true { let x = 2 print(x) }\nprint(x)",
+        &mut session,
+    );
+    let expected = "21";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "fn f() -> int { let a = 1 while a < 3 { let b = a a += 1 } return a }\nprint(f())",
+        &mut session,
+    );
+    let expected = "3";
+    assert_eq!(result, expected);
+
+    // block locals are not visible after the block
+    session.clear();
+    let result = process_to_string("if true { let y = 3 }\nprint(y)", &mut session);
+    let expected = "In line 2:
+
+ 2| print(y)
+          ^
+
+Error: Identifier not found";
+    assert_eq!(result, expected);
+
+    // neither is the variable of a for loop
+    session.clear();
+    let result = process_to_string("for i in [1, 2] { print(i) }\nprint(i)", &mut session);
+    let expected = "12In line 2:
+
+ 2| print(i)
+          ^
+
+Error: Identifier not found";
+    assert_eq!(result, expected);
+
+    // closures capture the variables of the blocks they are created in
+    session.clear();
+    let result = process_to_string(
+        "let n = 1\nlet f = fn() -> int { return 0 }\nfor n in [5] { f = fn() -> int { return n } }\nprint(f() + n)",
+        &mut session,
+    );
+    let expected = "6";
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn globals() {
+    let mut session = Session::new();
+
+    // functions use global variables that they declare
+    let result = process_to_string(
+        "let count = 1\nfn bump() -> none { global count count += 2 }\nbump()\nprint(count)",
+        &mut session,
+    );
+    let expected = "3";
+    assert_eq!(result, expected);
+
+    // constants are visible everywhere, but can be hidden by local variables
+    session.clear();
+    let result = process_to_string(
+        "const LIMIT: int = 4\nfn f() -> int { return LIMIT }\nfn g() -> int { let LIMIT = 1 return LIMIT }\nprint(f() + g())",
+        &mut session,
+    );
+    let expected = "5";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("const LIMIT: int = 4\nLIMIT = 5", &mut session);
+    let error = Error::new(
+        Context { start: 21, end: 30 },
+        0,
+        ErrorKind::ConstantAssignment("LIMIT".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string("const LIMIT = 4\nlet LIMIT = 5", &mut session);
+    let error = Error::new(
+        Context { start: 16, end: 29 },
+        10,
+        ErrorKind::ConstantAssignment("LIMIT".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    // assigning through a global declaration is checked when the function runs
+    session.clear();
+    let result = process_to_string("const LIMIT = 4", &mut session);
+    assert_eq!(result, "");
+    let result = process_to_string(
+        "fn h() -> none { global LIMIT LIMIT = 1 }\nh()",
+        &mut session,
+    );
+    let expected = "Backtrace:
+
+  h called at 3:1
+  root
+
+In function h:
+In line 2:
+
+ 2| fn h() -> none { global LIMIT LIMIT = 1 }
+                                  ^
+
+Error: Cannot assign to constant 'LIMIT'";
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn script_arguments() {
+    let mut session = Session::new();
+
+    session.set_args(vec!["a".to_string(), "bc".to_string()]);
+    let result = process_to_string("print(args()[1].len() + args().len())", &mut session);
+    let expected = "4";
+    assert_eq!(result, expected);
+    assert_eq!(session.exit_code(), None);
+
+    // the rest of the input does not run after exit
+    let result = process_to_string(
+        "fn stop() -> none { exit(3) }\nprint(1)\nstop()\nprint(2)",
+        &mut session,
+    );
+    let expected = "1";
+    assert_eq!(result, expected);
+    assert_eq!(session.exit_code(), Some(3));
+
+    let result = process_to_string("print(5)", &mut session);
+    let expected = "5";
+    assert_eq!(result, expected);
+    assert_eq!(session.exit_code(), None);
+
+    session.clear();
+    let result = process_to_string("exit(9999999999)", &mut session);
+    let error = Error::new(
+        Context { start: 0, end: 16 },
+        0,
+        ErrorKind::CustomError("Exit code '9999999999' is out of range".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+
+    for code in ["300", "-1"] {
+        session.clear();
+        let input = format!("exit({})", code);
+        let result = process_to_string(&input, &mut session);
+        let error = Error::new(
+            Context {
+                start: 0,
+                end: input.len(),
+            },
+            0,
+            ErrorKind::CustomError(format!("Exit code '{}' is out of range", code)),
+        );
+        let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+        assert_eq!(result, expected);
+    }
+}
+
+#[test]
+fn environment() {
+    let mut session = Session::new();
+
+    let result = process_to_string(
+        "Env::set(\"DUST_TEST_VAR\", \"1\")\nprint(Env::get(\"DUST_TEST_VAR\").unwrap() + Env::vars().get(\"DUST_TEST_VAR\"))\nEnv::remove(\"DUST_TEST_VAR\")\nprint(Env::get(\"DUST_TEST_VAR\").is_ok())",
+        &mut session,
+    );
+    let expected = "11false";
+    assert_eq!(result, expected);
+
+    // the changes are only seen by the session and the programs it runs
+    let result = process_to_string(
+        "Env::set(\"DUST_TEST_VAR\", \"2\")\nEnv::remove(\"HOME\")\nprint(Env::get(\"HOME\").is_ok())",
+        &mut session,
+    );
+    let expected = "false";
+    assert_eq!(result, expected);
+    assert!(std::env::var("DUST_TEST_VAR").is_err());
+    if cfg!(unix) {
+        let result = process_to_string(
+            "print(Process::run(\"sh\", [\"-c\", \"echo $DUST_TEST_VAR $\\{HOME-none}\"]).unwrap().get(\"stdout\"))",
+            &mut session,
+        );
+        let expected = "2 none\n";
+        assert_eq!(result, expected);
+    }
+
+    // names that cannot be used are reported in the Result
+    let result = process_to_string("print(Env::set(\"A=B\", \"1\").is_ok())", &mut session);
+    let expected = "false";
+    assert_eq!(result, expected);
+
+    let result = process_to_string(
+        "print(Process::run(\"dust-test-missing-program\", []).is_ok())",
+        &mut session,
+    );
+    let expected = "false";
+    assert_eq!(result, expected);
+
+    if cfg!(unix) {
+        let result = process_to_string(
+            "let r = Process::run_with(\"sh\", [\"-c\", \"read x; echo $x $Y; echo e >&2; exit 2\"], {\"stdin\": \"in\", \"env\": {\"Y\": 7}}).unwrap()\nprint(r.get(\"stdout\") + r.get(\"stderr\") + r.get(\"status\").to_string())",
+            &mut session,
+        );
+        let expected = "in 7\ne\n2";
+        assert_eq!(result, expected);
+    }
+
+    session.clear();
+    let result = process_to_string("Process::run_with(\"sh\", [], {\"cwd\": 1})", &mut session);
+    let error = Error::new(
+        Context { start: 0, end: 40 },
+        0,
+        ErrorKind::InvalidArgumentType("int".to_string(), "string".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn files() {
+    let path = std::env::temp_dir().join(format!("dust_file_test_{}.txt", std::process::id()));
+    let path = path.display();
+    let mut session = Session::new();
+
+    let result = process_to_string(
+        &format!(
+            "let w = File::open(\"{path}\", \"w\").unwrap()\n\
+             w.write(\"one\\ntwo\\n\")\n\
+             w.write(\"three\")\n\
+             w.close()\n\
+             let r = File::open(\"{path}\", \"r\").unwrap()\n\
+             print(r.read_line().unwrap())\n\
+             for line in r.lines() {{ print(\",\" + line) }}\n\
+             print(r.read_line().unwrap() == none)\n\
+             print(r.seek(4).unwrap())\n\
+             print(r.read_line().unwrap())\n\
+             r.close()\n\
+             let e = r.read_line()\n\
+             e.is_ok()\n\
+             print(e.value())"
+        ),
+        &mut session,
+    );
+    let expected = "one,two,threetrue4twoFile is closed";
+    assert_eq!(result, expected);
+
+    // buffered output is only visible after a flush
+    let result = process_to_string(
+        &format!(
+            "let a = File::open(\"{path}\", \"a\").unwrap()\n\
+             a.write(\"!\")\n\
+             print(File::read(\"{path}\").unwrap().len())\n\
+             a.flush()\n\
+             print(File::read(\"{path}\").unwrap())"
+        ),
+        &mut session,
+    );
+    let expected = "13one\ntwo\nthree!";
+    assert_eq!(result, expected);
+
+    let result = process_to_string(
+        &format!(
+            "let e = File::open(\"{path}\", \"x\")\n\
+             e.is_ok()\n\
+             print(e.value())\n\
+             e = File::open(\"{path}\", \"r\").unwrap().write(\"x\")\n\
+             e.is_ok()\n\
+             print(e.value())"
+        ),
+        &mut session,
+    );
+    let expected = "Invalid file mode 'x'File is not open for writing";
+    assert_eq!(result, expected);
+
+    std::fs::remove_file(path.to_string()).unwrap();
+}
+
+#[test]
+fn json() {
+    let mut session = Session::new();
+
+    let result = process_to_string(
+        "let v = Json::parse(\"\\{\\\"a\\\": [1, 2.5, -3e2, true, null], \\\"b\\\": \\{\\\"c\\\": \\\"x\\\\n\\\\u00e9\\\"\\}\\}\").unwrap()\n\
+         print(v.get(\"a\").get(2) + v.get(\"a\").get(0))\n\
+         print(v.get(\"b\").get(\"c\"))\n\
+         print(Json::stringify(v, false))",
+        &mut session,
+    );
+    let expected = "-299x\né{\"a\":[1,2.5,-300.0,true,null],\"b\":{\"c\":\"x\\né\"}}";
+    assert_eq!(result, expected);
+
+    // only the public properties of class instances are written
+    let result = process_to_string(
+        "class Point(x: int) {\n\
+         \x20   pub x: int = x\n\
+         \x20   hidden: int = 0\n\
+         \x20   pub tags: Vec = [\"a\"]\n\
+         }\n\
+         let m = Map::new()\n\
+         m.insert(1, [])\n\
+         print(Json::stringify([Point::new(3), m], true))",
+        &mut session,
+    );
+    let expected = "[\n  {\n    \"tags\": [\n      \"a\"\n    ],\n    \"x\": 3\n  },\n  {\n    \"1\": []\n  }\n]";
+    assert_eq!(result, expected);
+
+    let result = process_to_string(
+        "let r = Json::parse(\"[1,\\n 2,]\")\n\
+         r.is_ok()\n\
+         print(r.value())",
+        &mut session,
+    );
+    let expected = "Unexpected character ']' at line 2 column 4";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "let l = [1]\nl.push(l)\nJson::stringify(l, false)",
+        &mut session,
+    );
+    let error = Error::new(
+        Context { start: 22, end: 47 },
+        0,
+        ErrorKind::CustomError("Cannot convert a value that contains itself to JSON".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn equality() {
+    let mut session = Session::new();
+
+    let result = process_to_string(
+        "class Point(x: int, y: int) {\n\
+         \x20   pub x: int = x\n\
+         \x20   y: int = y\n\
+         }\n\
+         let a = [1, 2.0, \"x\", [Point::new(1, 2)]]\n\
+         let b = [1, 2, \"x\", [Point::new(1, 2)]]\n\
+         print(a == b)\n\
+         print(a != b)\n\
+         print(a is b)\n\
+         print(a is a)\n\
+         print(Point::new(1, 2) == Point::new(1, 3))\n\
+         print([1, \"a\"] == [1, 2])\n\
+         print(int::parse(\"1\") == Result::new(true, 1))",
+        &mut session,
+    );
+    let expected = "truefalsefalsetruefalsefalsetrue";
+    assert_eq!(result, expected);
+
+    // values that contain themselves are compared without recursing forever
+    let result = process_to_string(
+        "let c = [1]\n\
+         c.push(c)\n\
+         let d = [1]\n\
+         d.push(d)\n\
+         print(c == d)\n\
+         print(1 is 1.0)\n\
+         print({} is {})",
+        &mut session,
+    );
+    let expected = "truefalsefalse";
+    assert_eq!(result, expected);
+
+    // Maps are equal if their keys are and the values under them are
+    let result = process_to_string(
+        "print({1: [1]} == {1: [1]})\n\
+         print({1: [1]} == {1: [2]})\n\
+         print({1: 1} == {2: 1})\n\
+         print({1: 1} != {1: 1, 2: 2})",
+        &mut session,
+    );
+    let expected = "truefalsefalsetrue";
+    assert_eq!(result, expected);
+
+    let result = process_to_string(
+        "class Name(text: string) {\n\
+         \x20   text: string = text\n\
+         \x20   pub fn eq(self, other: Name) -> bool {\n\
+         \x20       return self.text.to_lowercase() == other.text.to_lowercase()\n\
+         \x20   }\n\
+         }\n\
+         print(Name::new(\"Ab\") == Name::new(\"aB\"))\n\
+         print([Name::new(\"Ab\")] != [Name::new(\"aB\")])\n\
+         print(Name::new(\"Ab\") == Point::new(1, 2))",
+        &mut session,
+    );
+    let expected = "truefalsefalse";
+    assert_eq!(result, expected);
+
+    session.clear();
+    let result = process_to_string(
+        "class Bad {\n\
+         \x20   pub fn eq(self, other: dyn) -> int { return 1 }\n\
+         }\n\
+         let x = Bad::new() == Bad::new()",
+        &mut session,
+    );
+    let error = Error::new(
+        Context { start: 66, end: 98 },
+        19,
+        ErrorKind::InvalidReturnType("int".to_string(), "bool".to_string()),
+    );
+    let expected = error.print_to_string(session.parse_session.get_source_code(), &Vec::new());
+    assert_eq!(result, expected);
+}
@@ -1,6 +1,8 @@
-use crate::function::FunctionCall;
+use crate::function::{CallArguments, Function, FunctionCall};
+use crate::literal::{MapLiteral, StringInterpolation, VecLiteral};
 use crate::variable::Value;
 use std::fmt;
+use std::rc::Rc;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Operator {
@@ -16,10 +18,15 @@ pub enum Operator {
     Not,
     LessThan,
     GreaterThan,
+    LessEqual,
+    GreaterEqual,
     Equal,
     NotEqual,
+    Is,
     Typeof,
     Dot,
+    Index,
+    Call,
 }
 
 impl Operator {
@@ -38,10 +45,15 @@ impl Operator {
             Operator::Not => 6,
             Operator::LessThan => 4,
             Operator::GreaterThan => 4,
+            Operator::LessEqual => 4,
+            Operator::GreaterEqual => 4,
             Operator::Equal => 3,
             Operator::NotEqual => 3,
+            Operator::Is => 3,
             Operator::Typeof => 3,
             Operator::Dot => 8,
+            Operator::Index => 8,
+            Operator::Call => 8,
         }
     }
 
@@ -58,9 +70,14 @@ impl Operator {
             Operator::Or => true,
             Operator::LessThan => true,
             Operator::GreaterThan => true,
+            Operator::LessEqual => true,
+            Operator::GreaterEqual => true,
             Operator::Equal => true,
             Operator::NotEqual => true,
+            Operator::Is => true,
             Operator::Dot => true,
+            Operator::Index => true,
+            Operator::Call => true,
 
             Operator::Neg => false,
             Operator::Not => false,
@@ -95,10 +112,15 @@ impl fmt::Display for Operator {
             Operator::Not => write!(f, "not"),
             Operator::LessThan => write!(f, "<"),
             Operator::GreaterThan => write!(f, ">"),
+            Operator::LessEqual => write!(f, "<="),
+            Operator::GreaterEqual => write!(f, ">="),
             Operator::Equal => write!(f, "=="),
             Operator::NotEqual => write!(f, "!="),
+            Operator::Is => write!(f, "is"),
             Operator::Typeof => write!(f, "typeof"),
             Operator::Dot => write!(f, "."),
+            Operator::Index => write!(f, "[]"),
+            Operator::Call => write!(f, "()"),
         }
     }
 }
@@ -123,6 +145,11 @@ pub enum TokenKind {
     Value(Value),
     Identifier(Box<String>),
     FunctionCall(Box<FunctionCall>),
+    VecLiteral(Box<VecLiteral>),
+    MapLiteral(Box<MapLiteral>),
+    Interpolation(Box<StringInterpolation>),
+    Lambda(Rc<Function>),
+    Arguments(Box<CallArguments>),
     Operator(Operator),
     Parenthesis(Parenthesis),
 }
@@ -190,6 +217,46 @@ impl Token {
         }
     }
 
+    #[inline]
+    pub fn new_vec_literal(pos: usize, value: VecLiteral) -> Token {
+        Token {
+            pos,
+            kind: TokenKind::VecLiteral(Box::new(value)),
+        }
+    }
+
+    #[inline]
+    pub fn new_map_literal(pos: usize, value: MapLiteral) -> Token {
+        Token {
+            pos,
+            kind: TokenKind::MapLiteral(Box::new(value)),
+        }
+    }
+
+    #[inline]
+    pub fn new_interpolation(pos: usize, value: StringInterpolation) -> Token {
+        Token {
+            pos,
+            kind: TokenKind::Interpolation(Box::new(value)),
+        }
+    }
+
+    #[inline]
+    pub fn new_lambda(pos: usize, value: Function) -> Token {
+        Token {
+            pos,
+            kind: TokenKind::Lambda(Rc::new(value)),
+        }
+    }
+
+    #[inline]
+    pub fn new_arguments(pos: usize, value: CallArguments) -> Token {
+        Token {
+            pos,
+            kind: TokenKind::Arguments(Box::new(value)),
+        }
+    }
+
     #[inline]
     pub fn new_operator(pos: usize, value: Operator) -> Token {
         Token {
@@ -1,9 +1,14 @@
 use crate::builtin::{
-    TYPEID_BOOL, TYPEID_FLOAT, TYPEID_INT, TYPEID_NONE, TYPEID_STRING, TYPEID_VEC,
+    TYPEID_BOOL, TYPEID_FILE_HANDLE, TYPEID_FLOAT, TYPEID_FUNCTION, TYPEID_INT, TYPEID_MAP,
+    TYPEID_NONE, TYPEID_STRING, TYPEID_VEC,
 };
 use crate::class::ClassInstance;
+use crate::file::FileHandle;
+use crate::function::FunctionValue;
+use crate::map::MapKey;
 use crate::session::ParseSession;
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::rc::Rc;
 
@@ -11,12 +16,26 @@ use std::rc::Rc;
 pub struct Variable {
     value: Value,
     is_dynamic: bool,
+    is_constant: bool,
 }
 
 impl Variable {
     #[inline]
     pub fn new(value: Value, is_dynamic: bool) -> Self {
-        Variable { value, is_dynamic }
+        Variable {
+            value,
+            is_dynamic,
+            is_constant: false,
+        }
+    }
+
+    #[inline]
+    pub fn new_constant(value: Value) -> Self {
+        Variable {
+            value,
+            is_dynamic: false,
+            is_constant: true,
+        }
     }
 
     #[inline]
@@ -44,6 +63,11 @@ impl Variable {
         self.is_dynamic
     }
 
+    #[inline]
+    pub fn is_constant(&self) -> bool {
+        self.is_constant
+    }
+
     #[inline]
     pub fn typeid(&self) -> usize {
         self.value.typeid()
@@ -53,6 +77,8 @@ impl Variable {
 pub type StringValue = Rc<RefCell<String>>;
 pub type ClassValue = Rc<RefCell<ClassInstance>>;
 pub type VecValue = Rc<RefCell<Vec<Value>>>;
+pub type MapValue = Rc<RefCell<BTreeMap<MapKey, Value>>>;
+pub type FileValue = Rc<RefCell<FileHandle>>;
 
 #[derive(Debug, Clone)]
 pub enum Value {
@@ -63,6 +89,9 @@ pub enum Value {
     Bool(bool),
     Class(ClassValue),
     Vector(VecValue),
+    Map(MapValue),
+    Function(FunctionValue),
+    File(FileValue),
 }
 
 impl Value {
@@ -98,6 +127,21 @@ impl Value {
         Value::Vector(Rc::new(RefCell::new(vec)))
     }
 
+    #[inline]
+    pub fn new_map_instance() -> Value {
+        Value::Map(Rc::new(RefCell::new(BTreeMap::new())))
+    }
+
+    #[inline]
+    pub fn new_map_instance_from(map: BTreeMap<MapKey, Value>) -> Value {
+        Value::Map(Rc::new(RefCell::new(map)))
+    }
+
+    #[inline]
+    pub fn new_file(file: FileHandle) -> Value {
+        Value::File(Rc::new(RefCell::new(file)))
+    }
+
     #[inline]
     pub fn to_string(&self, parse_session: &ParseSession) -> String {
         match self {
@@ -114,19 +158,7 @@ impl Value {
                 let mut result = String::from("(");
                 let vec: &Vec<Value> = &l.borrow();
                 for item in vec {
-                    match item {
-                        Value::Vector(_) => {
-                            result.push_str("[...]");
-                        }
-                        Value::Str(_) => {
-                            result.push_str("\"");
-                            result.push_str(&item.to_string(parse_session));
-                            result.push_str("\"");
-                        }
-                        _ => {
-                            result.push_str(&item.to_string(parse_session));
-                        }
-                    }
+                    result.push_str(&item.to_element_string(parse_session));
                     result.push_str(", ");
                 }
                 if !vec.is_empty() {
@@ -136,6 +168,36 @@ impl Value {
                 result.push_str(")");
                 result
             }
+            Value::Map(m) => {
+                let mut result = String::from("{");
+                let map: &BTreeMap<MapKey, Value> = &m.borrow();
+                for (key, value) in map {
+                    result.push_str(&key.to_value().to_element_string(parse_session));
+                    result.push_str(": ");
+                    result.push_str(&value.to_element_string(parse_session));
+                    result.push_str(", ");
+                }
+                if !map.is_empty() {
+                    result.pop();
+                    result.pop();
+                }
+                result.push('}');
+                result
+            }
+            Value::Function(f) => f.signature(parse_session),
+            Value::File(_) => parse_session.get_typename(TYPEID_FILE_HANDLE),
+        }
+    }
+
+    // Representation of a value inside of a container,
+    // nested containers are not expanded
+    #[inline]
+    fn to_element_string(&self, parse_session: &ParseSession) -> String {
+        match self {
+            Value::Vector(_) => "[...]".to_string(),
+            Value::Map(_) => "{...}".to_string(),
+            Value::Str(s) => format!("\"{}\"", s.borrow()),
+            _ => self.to_string(parse_session),
         }
     }
 
@@ -149,6 +211,9 @@ impl Value {
             Value::Bool(_) => TYPEID_BOOL,
             Value::Class(c) => c.borrow().typeid(),
             Value::Vector(_) => TYPEID_VEC,
+            Value::Map(_) => TYPEID_MAP,
+            Value::Function(_) => TYPEID_FUNCTION,
+            Value::File(_) => TYPEID_FILE_HANDLE,
         }
     }
 }
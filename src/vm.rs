@@ -0,0 +1,588 @@
+use crate::builtin::{TYPEID_BOOL, TYPEID_DYN};
+use crate::compiler::{Chunk, Op, Target};
+use crate::error::{Context, Error, ErrorKind};
+use crate::function::FunctionValue;
+use crate::map::MapKey;
+use crate::operation;
+use crate::session::{
+    ExecSession, FnQueryOptions, ParseSession, Scope, SharedVariable, VariableRef, VariableRefMut,
+};
+use crate::token::Operator;
+use crate::variable::{FileValue, Value, Variable};
+use std::collections::BTreeMap;
+
+// Runs a chunk in the current scope of the session and returns its result
+
+pub fn run(
+    chunk: &Chunk,
+    exec_session: &mut ExecSession,
+    parse_session: &ParseSession,
+) -> Result<Value, Error> {
+    let mut stack: Vec<Value> = Vec::new();
+    // the for loops that are running, each iterates over a snapshot
+    // of its container so the body is free to modify it
+    let mut iterators: Vec<LoopItems> = Vec::new();
+    // how many times the body of each running loop has started
+    let mut iterations: Vec<u64> = Vec::new();
+
+    let code = chunk.code();
+    let mut ip = 0;
+    while ip < code.len() {
+        let op = code[ip];
+        let (context, pos) = chunk.span(ip);
+        ip += 1;
+        exec_session
+            .budget_mut()
+            .step()
+            .map_err(|kind| Error::new(context, pos, kind))?;
+
+        match op {
+            Op::Constant(index) => stack.push(chunk.constant(index).clone()),
+            Op::Pop => {
+                stack.pop();
+            }
+            Op::CopyString => {
+                if let Some(Value::Str(s)) = stack.last() {
+                    let copy = Value::new_string(s.borrow().clone());
+                    *stack.last_mut().unwrap() = copy;
+                }
+            }
+            Op::Load(target) => {
+                let value = match variable(chunk, exec_session, target) {
+                    Some(var) => var.get_value_clone(),
+                    None => {
+                        // constants and the names of functions can be used anywhere
+                        let name = target_name(chunk, target);
+                        if let Some(var) = exec_session.get_constant(name) {
+                            var.get_value_clone()
+                        } else if parse_session.get_function(name, None).is_ok() {
+                            Value::Function(FunctionValue::Named(name.to_string()))
+                        } else {
+                            return Err(Error::new(context, pos, ErrorKind::IdentifierNotFound));
+                        }
+                    }
+                };
+                stack.push(value);
+            }
+            Op::Init(target, typeid) | Op::InitConstant(target, typeid) => {
+                if variable(chunk, exec_session, target).is_some_and(|var| var.is_constant()) {
+                    let name = target_name(chunk, target).to_string();
+                    return Err(Error::new(
+                        context,
+                        pos,
+                        ErrorKind::ConstantAssignment(name),
+                    ));
+                }
+                let value = stack.last().unwrap();
+                let mut var = match typeid {
+                    None => Variable::new(value.clone(), false),
+                    Some(TYPEID_DYN) => Variable::new(value.clone(), true),
+                    Some(typeid) if parse_session.is_subtype(value.typeid(), typeid) => {
+                        Variable::new(value.clone(), false)
+                    }
+                    Some(typeid) => {
+                        return Err(Error::new(
+                            context,
+                            pos,
+                            ErrorKind::InvalidAssignment(
+                                parse_session.get_typename(typeid),
+                                parse_session.get_typename(value.typeid()),
+                            ),
+                        ));
+                    }
+                };
+                if let Op::InitConstant(..) = op {
+                    var = Variable::new_constant(var.get_value_clone());
+                }
+                init_variable(chunk, exec_session, target, var);
+            }
+            Op::Assign(target, operator) => {
+                let mut rhs = stack.pop().unwrap();
+                if let Some(operator) = operator {
+                    let current = match variable(chunk, exec_session, target) {
+                        Some(var) => var.get_value_clone(),
+                        None => return Err(missing_variable(chunk, exec_session, target, context)),
+                    };
+                    rhs = operation::apply_binary(operator, current, rhs, parse_session)
+                        .map_err(|e| Error::new(context, pos, e))?;
+                }
+
+                let Some(mut lhs_var) = variable_mut(chunk, exec_session, target) else {
+                    return Err(missing_variable(chunk, exec_session, target, context));
+                };
+                if lhs_var.is_constant() {
+                    let name = target_name(chunk, target).to_string();
+                    return Err(Error::new(context, 0, ErrorKind::ConstantAssignment(name)));
+                }
+                if lhs_var.is_dynamic() || parse_session.is_subtype(rhs.typeid(), lhs_var.typeid())
+                {
+                    lhs_var.set_value(rhs);
+                } else {
+                    return Err(Error::new(
+                        context,
+                        pos,
+                        ErrorKind::InvalidAssignment(
+                            parse_session.get_typename(lhs_var.typeid()),
+                            parse_session.get_typename(rhs.typeid()),
+                        ),
+                    ));
+                }
+            }
+            Op::IterNext(target, exit) => match iterators.last_mut().unwrap().next() {
+                Ok(Some(item)) => {
+                    set_variable(chunk, exec_session, target, Variable::new(item, false))
+                }
+                Ok(None) => ip = exit,
+                Err(e) => return Err(Error::new(context, pos, ErrorKind::CustomError(e))),
+            },
+            Op::SetProperty(index) => {
+                let source = stack.pop().unwrap();
+                let rhs = stack.pop().unwrap();
+                set_property(chunk, index, source, rhs, parse_session, context)?;
+            }
+            Op::SetIndex(operator) => {
+                let index = stack.pop().unwrap();
+                let container = stack.pop().unwrap();
+                let mut rhs = stack.pop().unwrap();
+                if let Some((operator, operator_pos)) = operator {
+                    let current = operation::get_item(&container, &index, parse_session)
+                        .map_err(|e| Error::new(context, pos, e))?;
+                    rhs = operation::apply_binary(operator, current, rhs, parse_session)
+                        .map_err(|e| Error::new(context, operator_pos, e))?;
+                }
+                operation::set_item(&container, &index, rhs, parse_session)
+                    .map_err(|e| Error::new(context, pos, e))?;
+            }
+            Op::Binary(operator @ (Operator::Equal | Operator::NotEqual)) => {
+                let rhs = stack.pop().unwrap();
+                let lhs = stack.pop().unwrap();
+                operation::check_comparable(&lhs, &rhs, operator, parse_session)
+                    .map_err(|e| Error::new(context, pos, e))?;
+                let is_equal = operation::values_equal(&lhs, &rhs, &mut |lhs, rhs| {
+                    class_eq(lhs, rhs, exec_session, parse_session, context, pos)
+                })?;
+                stack.push(Value::Bool(is_equal == (operator == Operator::Equal)));
+            }
+            Op::Binary(operator) => {
+                let rhs = stack.pop().unwrap();
+                let lhs = stack.pop().unwrap();
+                let value = operation::apply_binary(operator, lhs, rhs, parse_session)
+                    .map_err(|e| Error::new(context, pos, e))?;
+                stack.push(value);
+            }
+            Op::Unary(operator) => {
+                let operand = stack.pop().unwrap();
+                let value = operation::apply_unary(operator, operand, parse_session)
+                    .map_err(|e| Error::new(context, pos, e))?;
+                stack.push(value);
+            }
+            Op::GetProperty(index) => {
+                let site = chunk.property_site(index);
+                let value = match stack.pop().unwrap() {
+                    Value::Class(c) => {
+                        let private_access = match site.private_access_typeid {
+                            Some(id) => parse_session.is_subtype(c.borrow().typeid(), id),
+                            None => false,
+                        };
+                        match c
+                            .borrow()
+                            .get_property(&site.name, private_access, parse_session)
+                        {
+                            Ok(var) => var.get_value_clone(),
+                            Err(e) => return Err(Error::new(context, site.id_pos, e)),
+                        }
+                    }
+                    _ => return Err(Error::new(context, pos, ErrorKind::InvalidMemberAccess)),
+                };
+                stack.push(value);
+            }
+            Op::Call(index) => {
+                let site = chunk.call_site(index);
+                let call = site.call();
+                let arguments = stack.split_off(stack.len() - call.arguments().len());
+
+                // a variable that holds a function is called
+                // in place of a function with the same name
+                let shadow = site
+                    .shadow()
+                    .and_then(|target| variable(chunk, exec_session, target))
+                    .and_then(|var| match var.get_value() {
+                        Value::Function(f) => Some(f.clone()),
+                        _ => None,
+                    });
+
+                let value = match shadow {
+                    Some(f) => f.call(
+                        arguments,
+                        exec_session,
+                        parse_session,
+                        context,
+                        pos + call.name_pos(),
+                        Some(call.line_col()),
+                    )?,
+                    None => {
+                        let caller_object = match site.is_method() {
+                            true => stack.pop(),
+                            false => None,
+                        };
+                        call.call(
+                            arguments,
+                            caller_object,
+                            exec_session,
+                            parse_session,
+                            context,
+                            pos,
+                        )?
+                    }
+                };
+                stack.push(value);
+            }
+            Op::CheckCallable => {
+                let callee = stack.last().unwrap();
+                if !matches!(callee, Value::Function(_)) {
+                    return Err(Error::new(
+                        context,
+                        pos,
+                        ErrorKind::NotCallable(parse_session.get_typename(callee.typeid())),
+                    ));
+                }
+            }
+            Op::CallValue(count, line_col) => {
+                let arguments = stack.split_off(stack.len() - count);
+                let function = match stack.pop().unwrap() {
+                    Value::Function(f) => f,
+                    _ => unreachable!("The callee should have been checked"),
+                };
+                let value = function.call(
+                    arguments,
+                    exec_session,
+                    parse_session,
+                    context,
+                    pos,
+                    Some(line_col),
+                )?;
+                stack.push(value);
+            }
+            Op::MakeVec(count) => {
+                let items = stack.split_off(stack.len() - count);
+                stack.push(Value::new_vec_instance_from(items));
+            }
+            Op::CheckMapKey => {
+                let key = stack.last().unwrap();
+                if MapKey::from_value(key).is_none() {
+                    return Err(Error::new(
+                        context,
+                        pos,
+                        ErrorKind::InvalidKeyType(parse_session.get_typename(key.typeid())),
+                    ));
+                }
+            }
+            Op::MakeMap(count) => {
+                let mut map = BTreeMap::new();
+                let mut entries = stack.split_off(stack.len() - 2 * count).into_iter();
+                while let (Some(key), Some(value)) = (entries.next(), entries.next()) {
+                    let key = MapKey::from_value(&key).expect("The key should have been checked");
+                    map.insert(key, value);
+                }
+                stack.push(Value::new_map_instance_from(map));
+            }
+            Op::Concat(count) => {
+                let mut result = String::new();
+                for part in stack.split_off(stack.len() - count) {
+                    result.push_str(&part.to_string(parse_session));
+                }
+                stack.push(Value::new_string(result));
+            }
+            Op::Closure(index) => {
+                // the closure shares the variables currently in scope
+                let site = chunk.closure_site(index);
+                let mut captured = match &site.captured {
+                    Some(visible) => {
+                        let scope = exec_session.scope_mut();
+                        visible
+                            .iter()
+                            .filter_map(|(name, slot)| {
+                                scope.share_slot(*slot).map(|cell| (name.clone(), cell))
+                            })
+                            .collect()
+                    }
+                    None => exec_session.capture_scope(),
+                };
+                // the variable the closure is assigned to gets its value afterwards
+                if let Some((name, target)) = &site.binding {
+                    let cell = declare_shared(chunk, exec_session, *target);
+                    captured.insert(name.clone(), cell);
+                }
+                stack.push(Value::Function(FunctionValue::new_closure(
+                    site.function.clone(),
+                    captured,
+                )));
+            }
+            Op::Jump(target) => ip = target,
+            Op::JumpIfFalse(target) => match stack.pop().unwrap() {
+                Value::Bool(true) => (),
+                Value::Bool(false) => ip = target,
+                value => {
+                    return Err(Error::new(
+                        context,
+                        pos,
+                        ErrorKind::ConditionalExpressionNotBool(
+                            parse_session.get_typename(value.typeid()),
+                        ),
+                    ));
+                }
+            },
+            Op::IterStart => {
+                let items = match stack.pop().unwrap() {
+                    Value::Vector(v) => LoopItems::Items(v.borrow().clone().into_iter()),
+                    Value::Map(m) => LoopItems::Items(
+                        m.borrow()
+                            .keys()
+                            .map(|key| key.to_value())
+                            .collect::<Vec<Value>>()
+                            .into_iter(),
+                    ),
+                    Value::File(file) => LoopItems::Lines(file),
+                    value => {
+                        return Err(Error::new(
+                            context,
+                            pos,
+                            ErrorKind::ForLoopNotVec(parse_session.get_typename(value.typeid())),
+                        ));
+                    }
+                };
+                iterators.push(items);
+            }
+            Op::IterEnd => {
+                iterators.pop();
+            }
+            Op::LoopStart => iterations.push(0),
+            Op::Iteration => {
+                let count = iterations.last_mut().unwrap();
+                *count += 1;
+                exec_session
+                    .budget()
+                    .check_iterations(*count)
+                    .map_err(|kind| Error::new(context, pos, kind))?;
+            }
+            Op::LoopEnd => {
+                iterations.pop();
+            }
+            Op::Return => {
+                let value = stack.pop().unwrap();
+                if let Some(expected_typeid) = chunk.return_typeid() {
+                    if !parse_session.is_subtype(value.typeid(), expected_typeid) {
+                        return Err(Error::new(
+                            context,
+                            pos,
+                            ErrorKind::InvalidReturnType(
+                                parse_session.get_typename(value.typeid()),
+                                parse_session.get_typename(expected_typeid),
+                            ),
+                        ));
+                    }
+                }
+                return Ok(value);
+            }
+            Op::Fail(index) => return Err(chunk.failure(index).clone()),
+        }
+    }
+
+    unreachable!("Compiled code should end with a return")
+}
+
+enum LoopItems {
+    Items(std::vec::IntoIter<Value>),
+    // files are read lazily, one line at a time
+    Lines(FileValue),
+}
+
+impl LoopItems {
+    #[inline]
+    fn next(&mut self) -> Result<Option<Value>, String> {
+        match self {
+            LoopItems::Items(items) => Ok(items.next()),
+            LoopItems::Lines(file) => Ok(file.borrow_mut().read_line()?.map(Value::new_string)),
+        }
+    }
+}
+
+// A class that defines eq(self, other) decides whether its instances
+// are equal, values that its parameter does not accept never are
+fn class_eq(
+    lhs: &Value,
+    rhs: &Value,
+    exec_session: &mut ExecSession,
+    parse_session: &ParseSession,
+    context: Context,
+    pos: usize,
+) -> Result<Option<bool>, Error> {
+    let options = FnQueryOptions::new(lhs.typeid(), true, true);
+    let function = match parse_session.get_function("eq", Some(options)) {
+        Ok(function) if function.arguments().len() == 1 => function,
+        _ => return Ok(None),
+    };
+    let expected = function.arguments()[0].typeid();
+    if expected != TYPEID_DYN && !parse_session.is_subtype(rhs.typeid(), expected) {
+        return Ok(Some(false));
+    }
+
+    let arguments = vec![rhs.clone()];
+    match function.call_method(
+        lhs.clone(),
+        arguments,
+        exec_session,
+        parse_session,
+        context,
+        pos,
+    )? {
+        Value::Bool(is_equal) => Ok(Some(is_equal)),
+        value => Err(Error::new(
+            context,
+            pos,
+            ErrorKind::InvalidReturnType(
+                parse_session.get_typename(value.typeid()),
+                parse_session.get_typename(TYPEID_BOOL),
+            ),
+        )),
+    }
+}
+
+#[inline]
+fn target_name(chunk: &Chunk, target: Target) -> &str {
+    match target {
+        Target::Slot(slot) => chunk.slot_name(slot),
+        Target::Name(index) | Target::Global(index) => chunk.name(index),
+    }
+}
+
+// Assigning to a variable that does not exist, a constant
+// that is not hidden by a local variable cannot be assigned to
+#[inline]
+fn missing_variable(
+    chunk: &Chunk,
+    exec_session: &ExecSession,
+    target: Target,
+    context: Context,
+) -> Error {
+    let name = target_name(chunk, target);
+    match exec_session.get_constant(name) {
+        Some(_) => Error::new(context, 0, ErrorKind::ConstantAssignment(name.to_string())),
+        None => Error::new(context, 0, ErrorKind::IdentifierNotFound),
+    }
+}
+
+#[inline]
+fn variable<'a>(
+    chunk: &Chunk,
+    exec_session: &'a ExecSession,
+    target: Target,
+) -> Option<VariableRef<'a>> {
+    match target {
+        Target::Slot(slot) => exec_session.scope().slot(slot),
+        Target::Name(index) => exec_session.scope().get(chunk.name(index)),
+        Target::Global(index) => exec_session.global_scope().get(chunk.name(index)),
+    }
+}
+
+#[inline]
+fn variable_mut<'a>(
+    chunk: &Chunk,
+    exec_session: &'a mut ExecSession,
+    target: Target,
+) -> Option<VariableRefMut<'a>> {
+    match target {
+        Target::Slot(slot) => exec_session.scope_mut().slot_mut(slot),
+        Target::Name(index) => exec_session.scope_mut().get_mut(chunk.name(index)),
+        Target::Global(index) => exec_session.global_scope_mut().get_mut(chunk.name(index)),
+    }
+}
+
+// The scope and slot of the variable, names that are not in the scope yet get a new slot
+#[inline]
+fn scope_slot<'a>(
+    chunk: &Chunk,
+    exec_session: &'a mut ExecSession,
+    target: Target,
+) -> (&'a mut Scope, usize) {
+    match target {
+        Target::Slot(slot) => (exec_session.scope_mut(), slot),
+        Target::Name(index) => {
+            let scope = exec_session.scope_mut();
+            let slot = scope.slot_of(chunk.name(index));
+            (scope, slot)
+        }
+        Target::Global(index) => {
+            let scope = exec_session.global_scope_mut();
+            let slot = scope.slot_of(chunk.name(index));
+            (scope, slot)
+        }
+    }
+}
+
+// A new variable, closures that captured the previous one keep it
+#[inline]
+fn set_variable(chunk: &Chunk, exec_session: &mut ExecSession, target: Target, var: Variable) {
+    let (scope, slot) = scope_slot(chunk, exec_session, target);
+    scope.set_slot(slot, var);
+}
+
+#[inline]
+fn init_variable(chunk: &Chunk, exec_session: &mut ExecSession, target: Target, var: Variable) {
+    let (scope, slot) = scope_slot(chunk, exec_session, target);
+    scope.init_slot(slot, var);
+}
+
+#[inline]
+fn declare_shared(chunk: &Chunk, exec_session: &mut ExecSession, target: Target) -> SharedVariable {
+    let (scope, slot) = scope_slot(chunk, exec_session, target);
+    scope.declare_shared(slot)
+}
+
+// Assignment to a property of an object, e.g. a.x = 1
+fn set_property(
+    chunk: &Chunk,
+    index: usize,
+    source: Value,
+    rhs: Value,
+    parse_session: &ParseSession,
+    context: Context,
+) -> Result<(), Error> {
+    let site = chunk.property_site(index);
+    let c = match source {
+        Value::Class(c) => c,
+        _ => return Err(Error::new(context, 0, ErrorKind::InvalidMemberAccess)),
+    };
+    let private_access = match site.private_access_typeid {
+        Some(id) => parse_session.is_subtype(c.borrow().typeid(), id),
+        None => false,
+    };
+
+    let value = match site.operator {
+        Some(operator) => {
+            let current = match c
+                .borrow()
+                .get_property(&site.name, private_access, parse_session)
+            {
+                Ok(var) => var.get_value_clone(),
+                Err(e @ ErrorKind::HasNoMember(_, _)) => {
+                    return Err(Error::new(context, site.pos, e));
+                }
+                Err(e) => return Err(Error::new(context, site.id_pos, e)),
+            };
+            operation::apply_binary(operator, current, rhs, parse_session)
+                .map_err(|e| Error::new(context, site.assign_pos, e))?
+        }
+        None => rhs,
+    };
+
+    let result = c
+        .borrow_mut()
+        .set_property(&site.name, private_access, value, parse_session);
+    match result {
+        Ok(()) => Ok(()),
+        Err(e @ ErrorKind::HasNoMember(_, _)) => Err(Error::new(context, site.pos, e)),
+        Err(e @ ErrorKind::MemberIsPrivate(_)) => Err(Error::new(context, site.id_pos, e)),
+        Err(e) => Err(Error::new(context, site.assign_pos, e)),
+    }
+}
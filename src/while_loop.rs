@@ -1,8 +1,6 @@
-use crate::error::{Context, Error, ErrorKind};
+use crate::error::Context;
 use crate::expression::Expression;
-use crate::instruction::{Instruction, ReturnValue};
-use crate::session::{ExecSession, ParseSession};
-use crate::variable::Value;
+use crate::instruction::Instruction;
 
 #[derive(Debug, Clone)]
 pub struct WhileLoop {
@@ -28,61 +26,12 @@ impl WhileLoop {
     }
 
     #[inline]
-    fn eval_condition(
-        &self,
-        exec_session: &mut ExecSession,
-        parse_session: &ParseSession,
-    ) -> Result<bool, Error> {
-        match self.condition.exec(exec_session, parse_session) {
-            Ok(return_value) => {
-                let value =
-                    return_value.expect("Expressions should always return a value on success");
-
-                match value {
-                    Value::Bool(b) => Ok(b),
-                    _ => {
-                        return Err(Error::new(
-                            self.context,
-                            0,
-                            ErrorKind::ConditionalExpressionNotBool(
-                                parse_session.get_typename(value.typeid()),
-                            ),
-                        ));
-                    }
-                }
-            }
-            Err(e) => return Err(e),
-        }
+    pub fn condition(&self) -> &Expression {
+        &self.condition
     }
 
-    pub fn exec(
-        &self,
-        exec_session: &mut ExecSession,
-        parse_session: &ParseSession,
-    ) -> Result<ReturnValue, Error> {
-        let mut condition_result = self.eval_condition(exec_session, parse_session)?;
-
-        'main_loop: while condition_result != false {
-            for item in &self.body {
-                match item.exec(exec_session, parse_session) {
-                    Ok(return_value) => match return_value {
-                        ReturnValue::Return(value) => {
-                            return Ok(ReturnValue::Return(value));
-                        }
-                        ReturnValue::Break => {
-                            break 'main_loop;
-                        }
-                        ReturnValue::Value(_) => (),
-                    },
-                    Err(e) => {
-                        return Err(e);
-                    }
-                }
-            }
-
-            condition_result = self.eval_condition(exec_session, parse_session)?;
-        }
-
-        Ok(ReturnValue::Value(Value::None))
+    #[inline]
+    pub fn body(&self) -> &Vec<Instruction> {
+        &self.body
     }
 }